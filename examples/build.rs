@@ -1,17 +1,30 @@
+extern crate rust_windows;
+
 use std::process::Command;
 use std::env;
 use std::path::Path;
 
+use rust_windows::dpi::DpiAwareness;
+use rust_windows::manifest::Manifest;
+
 fn main() {
-    let out_dir = env::var("OUT_DIR").ok().expect("can't find out_dir");
+    let out_dir = env::var("OUT_DIR").expect("can't find out_dir");
+    let out_dir = Path::new(&out_dir);
 
-    Command::new("windres").args(&["src/hello.rc",  "-o"])
-                       .arg(&format!("{}/hello.rc.o", out_dir))
+    Command::new("windres").args(["src/hello.rc", "-o"])
+                       .arg(out_dir.join("hello.rc.o"))
                        .status().unwrap();
-    Command::new("ar").args(&["crus", "libhello_rc.a", "hello.rc.o"])
-                      .current_dir(&Path::new(&out_dir))
+    Command::new("ar").args(["crus", "libhello_rc.a", "hello.rc.o"])
+                      .current_dir(out_dir)
                       .status().unwrap();
 
-    println!("cargo:rustc-link-search=native={}", out_dir);
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
     println!("cargo:rustc-link-lib=static=hello_rc");
-}
\ No newline at end of file
+
+    Manifest::new()
+        .common_controls_v6(true)
+        .dpi_awareness(DpiAwareness::PerMonitorV2)
+        .supported_os_all()
+        .embed(out_dir, "hello")
+        .unwrap();
+}
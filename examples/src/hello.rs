@@ -27,11 +27,12 @@ use winapi::{DWORD, WORD, LPARAM, WPARAM, LRESULT};
 use winapi::{WM_COMMAND, WM_DESTROY};
 use winapi::minwindef::LOWORD;
 
-use windows::main_window_loop;
+use windows::main_window_loop_filtered;
+use windows::accel::Accelerators;
 use windows::instance::Instance;
 use windows::resource::*;
 use windows::window::{WindowImpl, Window, WndClass, WindowParams};
-use windows::window::{OnCreate, OnSize, OnDestroy, OnPaint, OnFocus, OnMessage};
+use windows::window::{OnCreate, OnSize, OnDestroy, OnPaint, OnFocus, OnMessage, OnDpiChanged};
 use windows::window;
 use windows::gdi::PaintDc;
 use windows::font::Font;
@@ -52,7 +53,7 @@ struct MainFrame {
     font: RefCell<Option<Font>>,
 }
 
-wnd_proc!(MainFrame, win, WM_CREATE, WM_DESTROY, WM_SIZE, WM_SETFOCUS, WM_PAINT, ANY);
+wnd_proc!(MainFrame, win, WM_CREATE, WM_DESTROY, WM_SIZE, WM_SETFOCUS, WM_PAINT, WM_DPICHANGED, ANY);
 
 impl OnCreate for MainFrame {
     fn on_create(&self, _cs: &CREATESTRUCTW) -> bool {
@@ -118,6 +119,8 @@ impl OnFocus for MainFrame {
     }
 }
 
+impl OnDpiChanged for MainFrame {}
+
 impl OnMessage for MainFrame {
     fn on_message(&self, _message: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
         match _message {
@@ -195,6 +198,7 @@ fn main() {
     main.show(1);
     main.update();
 
-    let exit_code = main_window_loop();
+    let accel = Accelerators::parse(&[("Ctrl+N", MENU_NEW)]).unwrap();
+    let exit_code = main_window_loop_filtered(|msg| accel.translate(main, msg));
     //env::set_exit_status(exit_code as i32);
 }
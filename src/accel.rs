@@ -0,0 +1,274 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keyboard accelerator tables.
+//!
+//! ```ignore
+//! let accel = Accelerators::parse(&[("Ctrl+N", MENU_NEW), ("Ctrl+Shift+S", MENU_SAVE_AS)])
+//!     .unwrap();
+//! main_window_loop_filtered(|msg| accel.translate(main, msg));
+//! ```
+//!
+//! Accelerators arrive as `WM_COMMAND` with a high word of 1 in WPARAM.
+
+use std::fmt;
+
+#[cfg(windows)] use std::ptr;
+
+#[cfg(windows)] use user32;
+#[cfg(windows)] use winapi::{ACCEL, BYTE, HACCEL, HWND, LPCWSTR, MSG, WORD, c_int};
+
+use input::{ModifierKeys, VirtualKey};
+#[cfg(windows)] use instance::Instance;
+#[cfg(windows)] use window::Window;
+
+// ACCEL.fVirt flags.
+const FVIRTKEY: u8 = 0x01;
+const FSHIFT: u8 = 0x04;
+const FCONTROL: u8 = 0x08;
+const FALT: u8 = 0x10;
+
+// Missing from user32-sys.
+#[cfg(windows)]
+#[link(name = "user32")]
+extern "system" {
+    fn TranslateAcceleratorW(hWnd: HWND, hAccTable: HACCEL, lpMsg: *const MSG) -> c_int;
+}
+
+/// One accelerator table entry: a key combination and the command it sends.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct Accelerator {
+    pub modifiers: ModifierKeys,
+    pub key: VirtualKey,
+    pub command: u16,
+}
+
+impl Accelerator {
+    pub fn new(modifiers: ModifierKeys, key: VirtualKey, command: u16) -> Accelerator {
+        Accelerator {
+            modifiers,
+            key,
+            command,
+        }
+    }
+
+    /// Parses a key combination such as `"Ctrl+Shift+S"`, `"Alt+Enter"`, `"F5"`
+    /// or `"Ctrl++"`. Modifiers are `Ctrl` (or `Control`), `Shift` and `Alt`, in
+    /// any order and case; the key is looked up with `VirtualKey::from_name`.
+    pub fn parse(s: &str, command: u16) -> Option<Accelerator> {
+        let s = s.trim();
+        // A trailing "+" is the key itself, as in "Ctrl++".
+        let (mods, key) = if s.ends_with("++") || s == "+" {
+            (&s[..s.len() - 1], "+")
+        } else {
+            match s.rfind('+') {
+                Some(i) => (&s[..i + 1], &s[i + 1..]),
+                None => ("", s),
+            }
+        };
+
+        let mut modifiers = ModifierKeys::none();
+        for m in mods.split('+').map(|m| m.trim()).filter(|m| !m.is_empty()) {
+            let flag = if m.eq_ignore_ascii_case("Ctrl") || m.eq_ignore_ascii_case("Control") {
+                &mut modifiers.control
+            } else if m.eq_ignore_ascii_case("Shift") {
+                &mut modifiers.shift
+            } else if m.eq_ignore_ascii_case("Alt") {
+                &mut modifiers.alt
+            } else {
+                return None;
+            };
+            if *flag {
+                return None;
+            }
+            *flag = true;
+        }
+
+        VirtualKey::from_name(key.trim()).map(|key| Accelerator::new(modifiers, key, command))
+    }
+
+    /// Decodes an `ACCEL` entry. Character (non-`FVIRTKEY`) entries are not
+    /// supported and yield `None`.
+    pub fn from_raw(flags: u8, key: u16, command: u16) -> Option<Accelerator> {
+        if flags & FVIRTKEY == 0 || key > 0xFF {
+            return None;
+        }
+        let modifiers = ModifierKeys {
+            shift: flags & FSHIFT != 0,
+            control: flags & FCONTROL != 0,
+            alt: flags & FALT != 0,
+        };
+        Some(Accelerator::new(modifiers, VirtualKey::from_raw(key as u8), command))
+    }
+
+    /// The `fVirt` flags of the `ACCEL` entry.
+    pub fn raw_flags(&self) -> u8 {
+        let mut flags = FVIRTKEY;
+        if self.modifiers.shift { flags |= FSHIFT; }
+        if self.modifiers.control { flags |= FCONTROL; }
+        if self.modifiers.alt { flags |= FALT; }
+        flags
+    }
+
+    #[cfg(windows)]
+    fn to_accel(self) -> ACCEL {
+        ACCEL {
+            fVirt: self.raw_flags() as BYTE,
+            key: self.key.to_raw() as WORD,
+            cmd: self.command as WORD,
+        }
+    }
+}
+
+/// Formats the key combination the way menus show it, e.g. `Ctrl+Shift+S`.
+impl fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.control { f.write_str("Ctrl+")?; }
+        if self.modifiers.alt { f.write_str("Alt+")?; }
+        if self.modifiers.shift { f.write_str("Shift+")?; }
+        let short = match self.key {
+            VirtualKey::Return => "Enter",
+            VirtualKey::Escape => "Esc",
+            VirtualKey::Delete => "Del",
+            VirtualKey::Insert => "Ins",
+            VirtualKey::PageUp => "PgUp",
+            VirtualKey::PageDown => "PgDn",
+            VirtualKey::Back => "Backspace",
+            VirtualKey::OemPlus => "+",
+            VirtualKey::OemMinus => "-",
+            VirtualKey::OemComma => ",",
+            VirtualKey::OemPeriod => ".",
+            key => match key.name() {
+                Some(name) if name.starts_with("Key") && name.len() == 4 => &name[3..],
+                Some(name) => name,
+                None => return write!(f, "0x{:02X}", key.to_raw()),
+            },
+        };
+        f.write_str(short)
+    }
+}
+
+/// An accelerator table (`HACCEL`).
+#[cfg(windows)]
+pub struct Accelerators {
+    pub accel: HACCEL,
+    // Tables loaded from resources are freed by the system.
+    owned: bool,
+}
+
+#[cfg(windows)]
+impl Accelerators {
+    pub fn new(entries: &[Accelerator]) -> Option<Accelerators> {
+        let mut raw: Vec<ACCEL> = entries.iter().map(|a| a.to_accel()).collect();
+        let accel = unsafe {
+            user32::CreateAcceleratorTableW(raw.as_mut_ptr(), raw.len() as c_int)
+        };
+        if accel.is_null() {
+            None
+        } else {
+            Some(Accelerators { accel, owned: true })
+        }
+    }
+
+    /// Builds a table from `(key combination, command)` pairs; see
+    /// `Accelerator::parse`. Returns `None` if any combination is invalid.
+    pub fn parse(entries: &[(&str, u16)]) -> Option<Accelerators> {
+        let mut parsed = Vec::with_capacity(entries.len());
+        for &(s, command) in entries {
+            parsed.push(Accelerator::parse(s, command)?);
+        }
+        Accelerators::new(&parsed)
+    }
+
+    /// Loads an `ACCELERATORS` resource.
+    pub fn load_resource(instance: Instance, id: isize) -> Option<Accelerators> {
+        let accel = unsafe { user32::LoadAcceleratorsW(instance.instance, id as usize as LPCWSTR) };
+        if accel.is_null() {
+            None
+        } else {
+            Some(Accelerators { accel, owned: false })
+        }
+    }
+
+    /// The entries of the table. Character entries are skipped.
+    pub fn entries(&self) -> Vec<Accelerator> {
+        unsafe {
+            let len = user32::CopyAcceleratorTableW(self.accel, ptr::null_mut(), 0);
+            let mut raw: Vec<ACCEL> = Vec::with_capacity(len as usize);
+            let len = user32::CopyAcceleratorTableW(self.accel, raw.as_mut_ptr(), len);
+            raw.set_len(len as usize);
+            raw.iter()
+                .filter_map(|a| Accelerator::from_raw(a.fVirt, a.key, a.cmd))
+                .collect()
+        }
+    }
+
+    /// Turns a matching key message into a `WM_COMMAND` for `wnd`. Returns `true`
+    /// if it did, in which case the message must not be dispatched.
+    pub fn translate(&self, wnd: Window, msg: &MSG) -> bool {
+        unsafe { TranslateAcceleratorW(wnd.wnd, self.accel, msg) != 0 }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Accelerators {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { user32::DestroyAcceleratorTable(self.accel) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Accelerator;
+    use input::{ModifierKeys, VirtualKey};
+
+    #[test]
+    fn test_parse() {
+        let a = Accelerator::parse("Ctrl+Shift+S", 7).unwrap();
+        assert!(a.modifiers.control && a.modifiers.shift && !a.modifiers.alt);
+        assert_eq!(a.key, VirtualKey::S);
+        assert_eq!(a.command, 7);
+
+        let a = Accelerator::parse("alt + enter", 1).unwrap();
+        assert!(a.modifiers.alt);
+        assert_eq!(a.key, VirtualKey::Return);
+
+        assert_eq!(Accelerator::parse("F5", 1).unwrap().modifiers, ModifierKeys::none());
+        assert_eq!(Accelerator::parse("Ctrl++", 1).unwrap().key, VirtualKey::OemPlus);
+        assert_eq!(Accelerator::parse("+", 1).unwrap().key, VirtualKey::OemPlus);
+
+        assert!(Accelerator::parse("", 1).is_none());
+        assert!(Accelerator::parse("Ctrl+", 1).is_none());
+        assert!(Accelerator::parse("Ctrl+Ctrl+S", 1).is_none());
+        assert!(Accelerator::parse("Hyper+S", 1).is_none());
+        assert!(Accelerator::parse("Ctrl+Foo", 1).is_none());
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let a = Accelerator::parse("Ctrl+Alt+Del", 3).unwrap();
+        assert_eq!(a.raw_flags(), 0x01 | 0x08 | 0x10);
+        assert_eq!(Accelerator::from_raw(a.raw_flags(), 0x2E, 3), Some(a));
+        // Character accelerators are not supported.
+        assert_eq!(Accelerator::from_raw(0x08, 'A' as u16, 3), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let cases = ["Ctrl+N", "Ctrl+Shift+S", "Ctrl+Alt+Del", "Alt+Enter", "F5", "Ctrl+0",
+                     "Ctrl++", "Shift+PgDn"];
+        for s in cases.iter() {
+            assert_eq!(Accelerator::parse(s, 1).unwrap().to_string(), *s);
+        }
+        let a = Accelerator::new(ModifierKeys::none(), VirtualKey::Other(0x07), 1);
+        assert_eq!(a.to_string(), "0x07");
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Clipboard access.
+//!
+//! ```ignore
+//! if let Some(clipboard) = ClipboardGuard::open(Some(&main)) {
+//!     clipboard.clear();
+//!     clipboard.set_text("Hello");
+//! }
+//! ```
+//!
+//! The clipboard is shared by every application, so keep it open only briefly.
+//! Windows that call `add_clipboard_listener` are told about changes through
+//! `OnClipboardUpdate`.
+
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::thread;
+use std::time::Duration;
+
+use kernel32;
+use user32;
+use winapi::{BOOL, HDROP, HGLOBAL, HWND, UINT, c_int};
+
+use dragdrop;
+use wchar::{MultiString, WideCString, from_c_u16_buf};
+use window::Window;
+
+// Standard clipboard formats.
+pub const CF_TEXT: u32 = 1;
+pub const CF_BITMAP: u32 = 2;
+pub const CF_DIB: u32 = 8;
+pub const CF_UNICODETEXT: u32 = 13;
+pub const CF_HDROP: u32 = 15;
+pub const CF_DIBV5: u32 = 17;
+
+const GMEM_MOVEABLE: UINT = 0x0002;
+
+// Size of BITMAPINFOHEADER, the smallest header a CF_DIB may start with.
+const BITMAPINFOHEADER_SIZE: usize = 40;
+
+// Size of DROPFILES, which precedes the file names of a CF_HDROP.
+const DROPFILES_SIZE: usize = 20;
+
+// Missing from the sys crates.
+#[link(name = "user32")]
+extern "system" {
+    fn RemoveClipboardFormatListener(hWnd: HWND) -> BOOL;
+}
+
+/// Registers a custom clipboard format. Every process gets the same id for the
+/// same name.
+pub fn register_format(name: &str) -> Option<u32> {
+    let name_u = match WideCString::new(name) {
+        Ok(n) => n,
+        Err(_) => return None,
+    };
+    match unsafe { user32::RegisterClipboardFormatW(name_u.as_ptr()) } {
+        0 => None,
+        format => Some(format),
+    }
+}
+
+/// The name of a registered format; `None` for standard formats.
+pub fn format_name(format: u32) -> Option<String> {
+    let mut buf = [0u16; 256];
+    let len = unsafe {
+        user32::GetClipboardFormatNameW(format, buf.as_mut_ptr(), buf.len() as c_int)
+    };
+    if len <= 0 { None } else { Some(from_c_u16_buf(&buf[..len as usize])) }
+}
+
+/// Whether the clipboard has data in `format`. Does not need the clipboard open.
+pub fn is_format_available(format: u32) -> bool {
+    unsafe { user32::IsClipboardFormatAvailable(format) != 0 }
+}
+
+/// A number that changes whenever the clipboard contents change.
+pub fn sequence_number() -> u32 {
+    unsafe { user32::GetClipboardSequenceNumber() }
+}
+
+/// Copies the contents of a global memory block.
+pub unsafe fn read_global(handle: HGLOBAL) -> Option<Vec<u8>> {
+    let size = kernel32::GlobalSize(handle) as usize;
+    let data = kernel32::GlobalLock(handle) as *const u8;
+    if data.is_null() {
+        return None;
+    }
+    let copy = slice::from_raw_parts(data, size).to_vec();
+    kernel32::GlobalUnlock(handle);
+    Some(copy)
+}
+
+/// Copies `data` into a new movable global memory block, as clipboard and OLE
+/// data is passed.
+pub fn alloc_global(data: &[u8]) -> Option<HGLOBAL> {
+    unsafe {
+        let handle = kernel32::GlobalAlloc(GMEM_MOVEABLE, data.len() as _);
+        if handle.is_null() {
+            return None;
+        }
+        let dest = kernel32::GlobalLock(handle) as *mut u8;
+        if dest.is_null() {
+            kernel32::GlobalFree(handle);
+            return None;
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), dest, data.len());
+        kernel32::GlobalUnlock(handle);
+        Some(handle)
+    }
+}
+
+/// `text` as `CF_UNICODETEXT` data.
+pub fn encode_text(text: &str) -> Vec<u8> {
+    WideCString::from_str_truncate(text).as_slice_with_nul().iter()
+        .flat_map(|c| c.to_le_bytes().to_vec()).collect()
+}
+
+/// Decodes `CF_UNICODETEXT` data, up to the first NUL.
+pub fn decode_text(data: &[u8]) -> String {
+    let wide: Vec<u16> = data.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    from_c_u16_buf(&wide)
+}
+
+/// `files` as `CF_HDROP` data: a `DROPFILES` header followed by NUL-terminated
+/// names and a final NUL.
+pub fn encode_files<P: AsRef<Path>>(files: &[P]) -> Vec<u8> {
+    // DROPFILES { pFiles, pt, fNC, fWide }
+    let mut data = Vec::new();
+    data.extend_from_slice(&(DROPFILES_SIZE as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&1u32.to_le_bytes());
+    let mut names = MultiString::new();
+    for file in files {
+        // No file has an empty name or one with a NUL; leave them out.
+        if let Ok(name) = WideCString::from_path(file) {
+            names.push(&name).ok();
+        }
+    }
+    for c in names.as_slice() {
+        data.extend_from_slice(&c.to_le_bytes());
+    }
+    data
+}
+
+/// Holds the clipboard open, closing it when dropped.
+pub struct ClipboardGuard {
+    _private: (),
+}
+
+impl ClipboardGuard {
+    /// Opens the clipboard, failing if another window has it open. Data set while
+    /// open is owned by `owner`; with `None`, `clear` releases ownership instead,
+    /// and setting data then fails.
+    pub fn open(owner: Option<&Window>) -> Option<ClipboardGuard> {
+        let wnd = owner.map_or(ptr::null_mut(), |w| w.wnd);
+        match unsafe { user32::OpenClipboard(wnd) } {
+            0 => None,
+            _ => Some(ClipboardGuard { _private: () }),
+        }
+    }
+
+    /// Like `open`, but tries `attempts` times, 10ms apart, to get past other
+    /// applications briefly holding the clipboard.
+    pub fn open_with_retry(owner: Option<&Window>, attempts: u32) -> Option<ClipboardGuard> {
+        for i in 0..attempts {
+            if i > 0 {
+                thread::sleep(Duration::from_millis(10));
+            }
+            if let Some(guard) = ClipboardGuard::open(owner) {
+                return Some(guard);
+            }
+        }
+        None
+    }
+
+    /// Empties the clipboard and takes ownership of it. Must be called before
+    /// setting data.
+    pub fn clear(&self) -> bool {
+        unsafe { user32::EmptyClipboard() != 0 }
+    }
+
+    /// The formats on the clipboard, in the order the owner provided them.
+    pub fn formats(&self) -> Vec<u32> {
+        let mut formats = Vec::new();
+        let mut format = 0;
+        loop {
+            format = unsafe { user32::EnumClipboardFormats(format) };
+            if format == 0 {
+                return formats;
+            }
+            formats.push(format);
+        }
+    }
+
+    /// The data of a memory-based format, such as a registered one.
+    pub fn get_data(&self, format: u32) -> Option<Vec<u8>> {
+        let handle = unsafe { user32::GetClipboardData(format) };
+        if handle.is_null() {
+            return None;
+        }
+        unsafe { read_global(handle) }
+    }
+
+    /// Puts `data` on the clipboard in `format`, replacing data in that format.
+    pub fn set_data(&self, format: u32, data: &[u8]) -> bool {
+        let handle = match alloc_global(data) {
+            Some(handle) => handle,
+            None => return false,
+        };
+        unsafe {
+            // On success the clipboard owns the memory.
+            if user32::SetClipboardData(format, handle).is_null() {
+                kernel32::GlobalFree(handle);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The `CF_UNICODETEXT` text. Text in other text formats is converted by the
+    /// system.
+    pub fn get_text(&self) -> Option<String> {
+        self.get_data(CF_UNICODETEXT).map(|data| decode_text(&data))
+    }
+
+    pub fn set_text(&self, text: &str) -> bool {
+        self.set_data(CF_UNICODETEXT, &encode_text(text))
+    }
+
+    /// A `CF_DIB` image: a `BITMAPINFOHEADER`, its color table and the pixels.
+    /// The system converts bitmaps in other formats.
+    pub fn get_dib(&self) -> Option<Vec<u8>> {
+        self.get_data(CF_DIB).and_then(|dib| {
+            if dib.len() >= BITMAPINFOHEADER_SIZE { Some(dib) } else { None }
+        })
+    }
+
+    /// Puts a packed DIB, as returned by `get_dib`, on the clipboard. Fails if
+    /// `dib` is too short to hold a header.
+    pub fn set_dib(&self, dib: &[u8]) -> bool {
+        dib.len() >= BITMAPINFOHEADER_SIZE && self.set_data(CF_DIB, dib)
+    }
+
+    /// The files copied by e.g. Explorer.
+    pub fn get_files(&self) -> Option<Vec<PathBuf>> {
+        let handle = unsafe { user32::GetClipboardData(CF_HDROP) };
+        if handle.is_null() {
+            return None;
+        }
+        Some(unsafe { dragdrop::hdrop_files(handle as HDROP) })
+    }
+
+    /// Puts a list of files on the clipboard, to be pasted into e.g. Explorer.
+    pub fn set_files<P: AsRef<Path>>(&self, files: &[P]) -> bool {
+        self.set_data(CF_HDROP, &encode_files(files))
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        unsafe { user32::CloseClipboard() };
+    }
+}
+
+impl Window {
+    /// Sends the window `WM_CLIPBOARDUPDATE` (see `OnClipboardUpdate`) whenever the
+    /// clipboard contents change, until the window is destroyed or
+    /// `remove_clipboard_listener` is called.
+    pub fn add_clipboard_listener(&self) -> bool {
+        unsafe { user32::AddClipboardFormatListener(self.wnd) != 0 }
+    }
+
+    pub fn remove_clipboard_listener(&self) -> bool {
+        unsafe { RemoveClipboardFormatListener(self.wnd) != 0 }
+    }
+}
@@ -0,0 +1,787 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! COM support: initialization, interface pointers and objects implemented in
+//! Rust.
+//!
+//! An interface is a type implementing `Interface`, naming its IID and vtable.
+//! `ComPtr` holds a reference to one:
+//!
+//! ```ignore
+//! let _com = ComGuard::initialize(Apartment::SingleThreaded)?;
+//! let list: ComPtr<ITaskbarList3> = com::create_instance(&CLSID_TASKBARLIST)?;
+//! com::check(unsafe { (list.vtbl().HrInit)(list.as_raw()) })?;
+//! ```
+//!
+//! `ComObject` implements interfaces: a static vtable whose `IUnknown` methods
+//! are `query_interface`, `add_ref` and `release`, and methods that reach the
+//! object's state through `ComObject::state`:
+//!
+//! ```ignore
+//! static DROP_SOURCE_VTBL: IDropSourceVtbl = IDropSourceVtbl {
+//!     QueryInterface: com::query_interface::<IDropSourceVtbl, DropSourceState>,
+//!     AddRef: com::add_ref::<IDropSourceVtbl, DropSourceState>,
+//!     Release: com::release::<IDropSourceVtbl, DropSourceState>,
+//!     ...
+//! };
+//!
+//! let source: ComPtr<IDropSource> = ComObject::create(&DROP_SOURCE_VTBL, state);
+//! ```
+//!
+//! The reference counting does not need COM to be initialized, so objects can be
+//! tested on any host.
+
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+#[cfg(windows)] use std::slice;
+
+use error::{Error, Result, E_NOINTERFACE, E_POINTER, S_OK};
+#[cfg(windows)] use wchar::{WideCStr, WideCString};
+
+/// A GUID, such as an interface or class id. Laid out like the Windows `GUID`.
+#[repr(C)]
+#[derive(Clone,Copy,PartialEq,Eq,Hash)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl Guid {
+    /// Parses the registry form, `{6B29FC40-CA47-1067-B31D-00DD010662DA}`; the
+    /// braces are optional.
+    pub fn parse(s: &str) -> Option<Guid> {
+        let s = if s.starts_with('{') && s.ends_with('}') { &s[1..s.len() - 1] } else { s };
+        let groups: Vec<&str> = s.split('-').collect();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len() ||
+           groups.iter().zip(lengths.iter()).any(|(g, &len)| g.len() != len) ||
+           !s.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let hex = |s: &str| u64::from_str_radix(s, 16).unwrap();
+        let tail = hex(groups[3]) << 48 | hex(groups[4]);
+        let mut data4 = [0; 8];
+        for (i, b) in data4.iter_mut().enumerate() {
+            *b = (tail >> (56 - 8 * i)) as u8;
+        }
+        Some(Guid {
+            data1: hex(groups[0]) as u32,
+            data2: hex(groups[1]) as u16,
+            data3: hex(groups[2]) as u16,
+            data4,
+        })
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = &self.data4;
+        write!(f, "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+               self.data1, self.data2, self.data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7])
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub const IID_IUNKNOWN: Guid = Guid {
+    data1: 0x0000_0000, data2: 0x0000, data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// Turns a failed `HRESULT` into an error. Success codes such as `S_FALSE` are
+/// `Ok`.
+pub fn check(hr: i32) -> Result<()> {
+    if hr < 0 { Err(Error::from_hresult(hr)) } else { Ok(()) }
+}
+
+pub type QueryInterfaceFn = unsafe extern "system" fn(*mut c_void, *const Guid, *mut *mut c_void) -> i32;
+pub type AddRefFn = unsafe extern "system" fn(*mut c_void) -> u32;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct IUnknownVtbl {
+    pub QueryInterface: QueryInterfaceFn,
+    pub AddRef: AddRefFn,
+    pub Release: AddRefFn,
+}
+
+/// A COM interface, usually an empty enum standing for the interface type.
+///
+/// # Safety
+///
+/// `Vtbl` must be the interface's vtable layout, starting with the
+/// `IUnknownVtbl` methods.
+pub unsafe trait Interface {
+    type Vtbl: 'static;
+    const IID: Guid;
+}
+
+pub enum IUnknown {}
+
+unsafe impl Interface for IUnknown {
+    type Vtbl = IUnknownVtbl;
+    const IID: Guid = IID_IUNKNOWN;
+}
+
+/// A reference to a COM interface, released when dropped.
+pub struct ComPtr<T: Interface> {
+    ptr: *mut c_void,
+    marker: PhantomData<T>,
+}
+
+impl<T: Interface> ComPtr<T> {
+    /// Takes over a reference, such as one returned through an out parameter.
+    /// `None` if `ptr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or point to a `T` whose reference the caller owns.
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Option<ComPtr<T>> {
+        if ptr.is_null() { None } else { Some(ComPtr { ptr, marker: PhantomData }) }
+    }
+
+    /// Adds a reference to a pointer borrowed from the caller, such as a method
+    /// argument.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or point to a live `T`.
+    pub unsafe fn from_raw_add_ref(ptr: *mut c_void) -> Option<ComPtr<T>> {
+        let p: Option<ComPtr<T>> = ComPtr::from_raw(ptr);
+        if let Some(ref p) = p {
+            (p.unknown().AddRef)(p.ptr);
+        }
+        p
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    /// Gives up the reference without releasing it.
+    pub fn into_raw(self) -> *mut c_void {
+        let ptr = self.ptr;
+        mem::forget(self);
+        ptr
+    }
+
+    pub fn vtbl(&self) -> &T::Vtbl {
+        unsafe { &**(self.ptr as *const *const T::Vtbl) }
+    }
+
+    fn unknown(&self) -> &IUnknownVtbl {
+        unsafe { &**(self.ptr as *const *const IUnknownVtbl) }
+    }
+
+    /// Asks the object for another of its interfaces.
+    pub fn query<U: Interface>(&self) -> Result<ComPtr<U>> {
+        let mut out = ptr::null_mut();
+        let hr = unsafe { (self.unknown().QueryInterface)(self.ptr, &U::IID, &mut out) };
+        check(hr)?;
+        unsafe { ComPtr::from_raw(out) }.ok_or_else(|| Error::from_hresult(E_POINTER))
+    }
+}
+
+impl<T: Interface> Clone for ComPtr<T> {
+    fn clone(&self) -> ComPtr<T> {
+        unsafe { (self.unknown().AddRef)(self.ptr) };
+        ComPtr { ptr: self.ptr, marker: PhantomData }
+    }
+}
+
+impl<T: Interface> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        unsafe { (self.unknown().Release)(self.ptr) };
+    }
+}
+
+impl<T: Interface> fmt::Debug for ComPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ComPtr({:p})", self.ptr)
+    }
+}
+
+/// A COM object implemented in Rust: a vtable pointer followed by a reference
+/// count and the state the methods work on. It answers `QueryInterface` for
+/// `IUnknown` and one interface.
+///
+/// Objects are freed on the last `Release`, and must only be used on the thread
+/// that made them.
+#[repr(C)]
+pub struct ComObject<V: 'static, T> {
+    vtbl: &'static V,
+    refs: Cell<u32>,
+    iid: Guid,
+    state: T,
+}
+
+impl<V: 'static, T> ComObject<V, T> {
+    /// A new object with one reference, as a raw interface pointer. `vtbl` must
+    /// start with `query_interface`, `add_ref` and `release` for `V` and `T`.
+    pub fn create_raw(vtbl: &'static V, iid: &Guid, state: T) -> *mut c_void {
+        let obj = Box::new(ComObject { vtbl, refs: Cell::new(1), iid: *iid, state });
+        Box::into_raw(obj) as *mut c_void
+    }
+
+    /// A new object implementing the interface `I`; see `create_raw`.
+    pub fn create<I: Interface<Vtbl = V>>(vtbl: &'static V, state: T) -> ComPtr<I> {
+        ComPtr { ptr: ComObject::create_raw(vtbl, &I::IID, state), marker: PhantomData }
+    }
+
+    /// The state of the object `this` points to, for use in its methods.
+    ///
+    /// # Safety
+    ///
+    /// `this` must point to a live `ComObject<V, T>`.
+    pub unsafe fn state<'a>(this: *mut c_void) -> &'a T {
+        &(*(this as *const ComObject<V, T>)).state
+    }
+}
+
+/// The `QueryInterface` of a `ComObject<V, T>`.
+///
+/// # Safety
+///
+/// Only to be called through the object's vtable.
+pub unsafe extern "system" fn query_interface<V: 'static, T>(this: *mut c_void, iid: *const Guid,
+                                                             out: *mut *mut c_void) -> i32 {
+    if out.is_null() {
+        return E_POINTER;
+    }
+    let obj = &*(this as *const ComObject<V, T>);
+    if *iid == IID_IUNKNOWN || *iid == obj.iid {
+        obj.refs.set(obj.refs.get() + 1);
+        *out = this;
+        S_OK
+    } else {
+        *out = ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+/// The `AddRef` of a `ComObject<V, T>`.
+///
+/// # Safety
+///
+/// Only to be called through the object's vtable.
+pub unsafe extern "system" fn add_ref<V: 'static, T>(this: *mut c_void) -> u32 {
+    let obj = &*(this as *const ComObject<V, T>);
+    obj.refs.set(obj.refs.get() + 1);
+    obj.refs.get()
+}
+
+/// The `Release` of a `ComObject<V, T>`, which frees the object with the last
+/// reference.
+///
+/// # Safety
+///
+/// Only to be called through the object's vtable.
+pub unsafe extern "system" fn release<V: 'static, T>(this: *mut c_void) -> u32 {
+    let refs = {
+        let obj = &*(this as *const ComObject<V, T>);
+        obj.refs.set(obj.refs.get() - 1);
+        obj.refs.get()
+    };
+    if refs == 0 {
+        drop(Box::from_raw(this as *mut ComObject<V, T>));
+    }
+    refs
+}
+
+/// A value of a `VARIANT`, for the types this crate converts.
+#[derive(Clone,PartialEq,Debug)]
+pub enum Variant {
+    Empty,
+    Null,
+    Bool(bool),
+    I4(i32),
+    I8(i64),
+    R8(f64),
+    Str(String),
+}
+
+// VARTYPEs.
+#[cfg(windows)] const VT_EMPTY: u16 = 0;
+#[cfg(windows)] const VT_NULL: u16 = 1;
+#[cfg(windows)] const VT_I2: u16 = 2;
+#[cfg(windows)] const VT_I4: u16 = 3;
+#[cfg(windows)] const VT_R4: u16 = 4;
+#[cfg(windows)] const VT_R8: u16 = 5;
+#[cfg(windows)] const VT_BSTR: u16 = 8;
+#[cfg(windows)] const VT_BOOL: u16 = 11;
+#[cfg(windows)] const VT_UI1: u16 = 17;
+#[cfg(windows)] const VT_UI2: u16 = 18;
+#[cfg(windows)] const VT_UI4: u16 = 19;
+#[cfg(windows)] const VT_I8: u16 = 20;
+
+/// The Windows `VARIANT`: a type tag and a value.
+#[cfg(windows)]
+#[repr(C)]
+pub struct VARIANT {
+    pub vt: u16,
+    pub reserved: [u16; 3],
+    pub data: [usize; 2],
+}
+
+// Missing from the sys crates.
+#[cfg(windows)]
+#[link(name = "ole32")]
+extern "system" {
+    fn CoInitializeEx(pvReserved: *mut c_void, dwCoInit: u32) -> i32;
+    fn CoUninitialize();
+    fn CoCreateInstance(rclsid: *const Guid, pUnkOuter: *mut c_void, dwClsContext: u32,
+                        riid: *const Guid, ppv: *mut *mut c_void) -> i32;
+}
+
+#[cfg(windows)]
+#[link(name = "oleaut32")]
+extern "system" {
+    fn SysAllocStringLen(strIn: *const u16, ui: u32) -> *mut u16;
+    fn SysFreeString(bstrString: *mut u16);
+    fn SysStringLen(pbstr: *mut u16) -> u32;
+    fn VariantClear(pvarg: *mut VARIANT) -> i32;
+}
+
+#[cfg(windows)]
+impl Variant {
+    /// Converts to a `VARIANT`, which owns a copy of any string and must be
+    /// released with `clear_variant`. Strings are cut at their first NUL, as
+    /// `Bstr::new` does.
+    pub fn to_raw(&self) -> VARIANT {
+        let mut v = VARIANT { vt: VT_EMPTY, reserved: [0; 3], data: [0; 2] };
+        let data = v.data.as_mut_ptr() as *mut u8;
+        unsafe {
+            match *self {
+                Variant::Empty => {}
+                Variant::Null => v.vt = VT_NULL,
+                Variant::Bool(b) => {
+                    v.vt = VT_BOOL;
+                    // VARIANT_TRUE is -1.
+                    ptr::write_unaligned(data as *mut i16, if b { -1 } else { 0 });
+                }
+                Variant::I4(i) => {
+                    v.vt = VT_I4;
+                    ptr::write_unaligned(data as *mut i32, i);
+                }
+                Variant::I8(i) => {
+                    v.vt = VT_I8;
+                    ptr::write_unaligned(data as *mut i64, i);
+                }
+                Variant::R8(f) => {
+                    v.vt = VT_R8;
+                    ptr::write_unaligned(data as *mut f64, f);
+                }
+                Variant::Str(ref s) => {
+                    v.vt = VT_BSTR;
+                    ptr::write_unaligned(data as *mut *mut u16, Bstr::new(s).into_raw());
+                }
+            }
+        }
+        v
+    }
+
+    /// Reads a `VARIANT`, widening smaller integer and float types. `None` for
+    /// other types, including by-reference ones.
+    pub unsafe fn from_raw(v: &VARIANT) -> Option<Variant> {
+        let data = v.data.as_ptr() as *const u8;
+        Some(match v.vt {
+            VT_EMPTY => Variant::Empty,
+            VT_NULL => Variant::Null,
+            VT_BOOL => Variant::Bool(ptr::read_unaligned(data as *const i16) != 0),
+            VT_I2 => Variant::I4(ptr::read_unaligned(data as *const i16) as i32),
+            VT_I4 => Variant::I4(ptr::read_unaligned(data as *const i32)),
+            VT_UI1 => Variant::I4(*data as i32),
+            VT_UI2 => Variant::I4(ptr::read_unaligned(data as *const u16) as i32),
+            VT_UI4 => Variant::I8(ptr::read_unaligned(data as *const u32) as i64),
+            VT_I8 => Variant::I8(ptr::read_unaligned(data as *const i64)),
+            VT_R4 => Variant::R8(ptr::read_unaligned(data as *const f32) as f64),
+            VT_R8 => Variant::R8(ptr::read_unaligned(data as *const f64)),
+            VT_BSTR => {
+                let bstr = ptr::read_unaligned(data as *const *mut u16);
+                Variant::Str(Bstr::borrowed(bstr).to_string())
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// Frees what a `VARIANT` owns, and makes it empty.
+#[cfg(windows)]
+pub unsafe fn clear_variant(v: &mut VARIANT) {
+    VariantClear(v);
+}
+
+/// An owned `BSTR`: a length-prefixed UTF-16 string allocated by the system, as
+/// used by automation interfaces. It may contain NULs.
+#[cfg(windows)]
+pub struct Bstr {
+    ptr: *mut u16,
+}
+
+#[cfg(windows)]
+impl Bstr {
+    /// Converts `s` up to its first NUL, as `WideCString::from_str_truncate` does.
+    /// Use `from_wide` for strings with embedded NULs.
+    pub fn new(s: &str) -> Bstr {
+        Bstr::from_wide_cstr(&WideCString::from_str_truncate(s))
+    }
+
+    pub fn from_wide_cstr(s: &WideCStr) -> Bstr {
+        Bstr::from_wide(s.as_slice())
+    }
+
+    pub fn from_wide(s: &[u16]) -> Bstr {
+        let ptr = unsafe { SysAllocStringLen(s.as_ptr(), s.len() as u32) };
+        if ptr.is_null() && !s.is_empty() {
+            panic!("out of memory allocating a BSTR");
+        }
+        Bstr { ptr }
+    }
+
+    /// Takes over a `BSTR`, such as one returned through an out parameter. A null
+    /// pointer is an empty string.
+    pub unsafe fn from_raw(ptr: *mut u16) -> Bstr {
+        Bstr { ptr }
+    }
+
+    // Views a `BSTR` owned by someone else.
+    unsafe fn borrowed(ptr: *mut u16) -> mem::ManuallyDrop<Bstr> {
+        mem::ManuallyDrop::new(Bstr { ptr })
+    }
+
+    /// The pointer to pass to methods taking a `BSTR`, which keep no reference.
+    pub fn as_ptr(&self) -> *mut u16 {
+        self.ptr
+    }
+
+    /// Gives up ownership, e.g. to return the string through an out parameter.
+    pub fn into_raw(self) -> *mut u16 {
+        let ptr = self.ptr;
+        mem::forget(self);
+        ptr
+    }
+
+    /// The length in UTF-16 units.
+    pub fn len(&self) -> usize {
+        if self.ptr.is_null() { 0 } else { unsafe { SysStringLen(self.ptr) as usize } }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_wide(&self) -> &[u16] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr, self.len()) }
+        }
+    }
+
+    /// Copies the string up to its first NUL, to pass to APIs taking a
+    /// NUL-terminated one.
+    pub fn to_wide_cstring(&self) -> WideCString {
+        WideCString::from_vec_truncate(self.as_wide().to_vec())
+    }
+}
+
+#[cfg(windows)]
+impl Clone for Bstr {
+    fn clone(&self) -> Bstr {
+        Bstr::from_wide(self.as_wide())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Bstr {
+    fn drop(&mut self) {
+        unsafe { SysFreeString(self.ptr) };
+    }
+}
+
+#[cfg(windows)]
+impl fmt::Display for Bstr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        String::from_utf16_lossy(self.as_wide()).fmt(f)
+    }
+}
+
+#[cfg(windows)]
+impl fmt::Debug for Bstr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", String::from_utf16_lossy(self.as_wide()))
+    }
+}
+
+/// The kind of COM apartment a thread joins.
+#[cfg(windows)]
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Apartment {
+    /// For threads with windows; objects are called through the message loop.
+    /// Needed for OLE drag and drop and the clipboard.
+    SingleThreaded,
+    MultiThreaded,
+}
+
+/// Initializes COM on the current thread, and uninitializes it when dropped.
+#[cfg(windows)]
+pub struct ComGuard {
+    // Uninitializing must happen on the same thread.
+    _marker: PhantomData<*const ()>,
+}
+
+#[cfg(windows)]
+impl ComGuard {
+    /// Fails with `RPC_E_CHANGED_MODE` if the thread is in the other kind of
+    /// apartment.
+    pub fn initialize(apartment: Apartment) -> Result<ComGuard> {
+        let flags = match apartment {
+            // COINIT_APARTMENTTHREADED | COINIT_DISABLE_OLE1DDE
+            Apartment::SingleThreaded => 0x2 | 0x4,
+            // COINIT_MULTITHREADED
+            Apartment::MultiThreaded => 0x0,
+        };
+        // Succeeds with S_FALSE if the thread had already joined the apartment;
+        // each success is balanced by one CoUninitialize.
+        check(unsafe { CoInitializeEx(ptr::null_mut(), flags) })?;
+        Ok(ComGuard { _marker: PhantomData })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe { CoUninitialize() };
+    }
+}
+
+/// Creates an object of the class `clsid`, in process or in a local server.
+#[cfg(windows)]
+pub fn create_instance<T: Interface>(clsid: &Guid) -> Result<ComPtr<T>> {
+    let mut out = ptr::null_mut();
+    // CLSCTX_INPROC_SERVER | CLSCTX_LOCAL_SERVER
+    check(unsafe { CoCreateInstance(clsid, ptr::null_mut(), 0x1 | 0x4, &T::IID, &mut out) })?;
+    unsafe { ComPtr::from_raw(out) }.ok_or_else(|| Error::from_hresult(E_POINTER))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::{E_FAIL, S_FALSE};
+    use std::os::raw::c_void;
+    use std::rc::Rc;
+
+    const IID_ICOUNTER: Guid = Guid {
+        data1: 0x1234_5678, data2: 0x9ABC, data3: 0xDEF0,
+        data4: [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF],
+    };
+
+    #[repr(C)]
+    #[allow(non_snake_case)]
+    struct ICounterVtbl {
+        QueryInterface: QueryInterfaceFn,
+        AddRef: AddRefFn,
+        Release: AddRefFn,
+        Next: unsafe extern "system" fn(*mut c_void) -> u32,
+    }
+
+    enum ICounter {}
+
+    unsafe impl Interface for ICounter {
+        type Vtbl = ICounterVtbl;
+        const IID: Guid = IID_ICOUNTER;
+    }
+
+    struct Counter {
+        count: Cell<u32>,
+        // Counts the drops of the state.
+        dropped: Rc<Cell<u32>>,
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+
+    unsafe extern "system" fn next(this: *mut c_void) -> u32 {
+        let state = ComObject::<ICounterVtbl, Counter>::state(this);
+        state.count.set(state.count.get() + 1);
+        state.count.get()
+    }
+
+    static COUNTER_VTBL: ICounterVtbl = ICounterVtbl {
+        QueryInterface: query_interface::<ICounterVtbl, Counter>,
+        AddRef: add_ref::<ICounterVtbl, Counter>,
+        Release: release::<ICounterVtbl, Counter>,
+        Next: next,
+    };
+
+    fn counter(dropped: &Rc<Cell<u32>>) -> ComPtr<ICounter> {
+        ComObject::create(&COUNTER_VTBL, Counter { count: Cell::new(0), dropped: dropped.clone() })
+    }
+
+    // The reference count, read by adding and releasing a reference.
+    fn refs<T: Interface>(p: &ComPtr<T>) -> u32 {
+        unsafe {
+            let unknown = &**(p.as_raw() as *const *const IUnknownVtbl);
+            (unknown.AddRef)(p.as_raw());
+            (unknown.Release)(p.as_raw())
+        }
+    }
+
+    #[test]
+    fn test_ref_counting() {
+        let dropped = Rc::new(Cell::new(0));
+        let a = counter(&dropped);
+        assert_eq!(refs(&a), 1);
+        let b = a.clone();
+        assert_eq!(refs(&a), 2);
+        assert_eq!(a.as_raw(), b.as_raw());
+        drop(a);
+        assert_eq!(refs(&b), 1);
+        assert_eq!(dropped.get(), 0);
+        drop(b);
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn test_methods() {
+        let dropped = Rc::new(Cell::new(0));
+        let c = counter(&dropped);
+        unsafe {
+            assert_eq!((c.vtbl().Next)(c.as_raw()), 1);
+            assert_eq!((c.vtbl().Next)(c.as_raw()), 2);
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let dropped = Rc::new(Cell::new(0));
+        let c = counter(&dropped);
+        let unknown = c.query::<IUnknown>().unwrap();
+        assert_eq!(unknown.as_raw(), c.as_raw());
+        assert_eq!(refs(&c), 2);
+        let again = unknown.query::<ICounter>().unwrap();
+        assert_eq!(refs(&c), 3);
+        drop((unknown, again));
+
+        // An interface the object does not implement.
+        enum IOther {}
+        unsafe impl Interface for IOther {
+            type Vtbl = IUnknownVtbl;
+            const IID: Guid = Guid { data1: 1, data2: 2, data3: 3, data4: [4; 8] };
+        }
+        let err = c.query::<IOther>().unwrap_err();
+        assert_eq!(err.hresult(), E_NOINTERFACE);
+        assert_eq!(refs(&c), 1);
+        drop(c);
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn test_raw() {
+        let dropped = Rc::new(Cell::new(0));
+        let raw = counter(&dropped).into_raw();
+        let borrowed = unsafe { ComPtr::<ICounter>::from_raw_add_ref(raw) }.unwrap();
+        assert_eq!(refs(&borrowed), 2);
+        drop(borrowed);
+        let owned = unsafe { ComPtr::<ICounter>::from_raw(raw) }.unwrap();
+        drop(owned);
+        assert_eq!(dropped.get(), 1);
+        assert!(unsafe { ComPtr::<ICounter>::from_raw(ptr::null_mut()) }.is_none());
+    }
+
+    #[test]
+    fn test_guid() {
+        let s = "{12345678-9ABC-DEF0-0123-456789ABCDEF}";
+        assert_eq!(Guid::parse(s), Some(IID_ICOUNTER));
+        assert_eq!(Guid::parse("12345678-9abc-def0-0123-456789abcdef"), Some(IID_ICOUNTER));
+        assert_eq!(IID_ICOUNTER.to_string(), s);
+        assert_eq!(IID_IUNKNOWN.to_string(), "{00000000-0000-0000-C000-000000000046}");
+        assert_eq!(Guid::parse("{12345678-9ABC-DEF0-0123-456789ABCDE}"), None);
+        assert_eq!(Guid::parse("{12345678-9ABC-DEF0-0123-456789ABCDEG}"), None);
+        assert_eq!(Guid::parse("12345678+9ABC-DEF0-0123-456789ABCDEF"), None);
+        assert_eq!(Guid::parse(""), None);
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(S_OK).is_ok());
+        assert!(check(S_FALSE).is_ok());
+        assert_eq!(check(E_FAIL).unwrap_err().hresult(), E_FAIL);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_bstr() {
+        let s = Bstr::new("h\u{e9}llo \u{1F600}");
+        assert_eq!(s.len(), 8);
+        assert_eq!(s.to_string(), "h\u{e9}llo \u{1F600}");
+        assert_eq!(s.clone().as_wide(), s.as_wide());
+        assert_ne!(s.clone().as_ptr(), s.as_ptr());
+
+        // Cut at a NUL, unless built from the units.
+        assert_eq!(Bstr::new("ab\0c").as_wide(), &[0x61, 0x62]);
+        let nul = Bstr::from_wide(&[0x61, 0, 0x62]);
+        assert_eq!(nul.len(), 3);
+        assert_eq!(nul.to_wide_cstring().as_slice(), &[0x61]);
+
+        assert!(Bstr::new("").is_empty());
+        let null = unsafe { Bstr::from_raw(ptr::null_mut()) };
+        assert!(null.is_empty());
+        assert!(null.as_wide().is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_variant() {
+        let values = [
+            Variant::Empty,
+            Variant::Null,
+            Variant::Bool(true),
+            Variant::Bool(false),
+            Variant::I4(-7),
+            Variant::I8(1 << 40),
+            Variant::R8(0.5),
+            Variant::Str("h\u{e9}llo".to_string()),
+            Variant::Str(String::new()),
+        ];
+        for value in &values {
+            let mut raw = value.to_raw();
+            assert_eq!(unsafe { Variant::from_raw(&raw) }.as_ref(), Some(value));
+            unsafe { clear_variant(&mut raw) };
+            assert_eq!(raw.vt, VT_EMPTY);
+        }
+
+        // Smaller types widen.
+        let mut raw = VARIANT { vt: VT_I2, reserved: [0; 3], data: [0; 2] };
+        unsafe { ptr::write_unaligned(raw.data.as_mut_ptr() as *mut i16, -2) };
+        assert_eq!(unsafe { Variant::from_raw(&raw) }, Some(Variant::I4(-2)));
+        raw.vt = VT_UI4;
+        raw.data = [0; 2];
+        unsafe { ptr::write_unaligned(raw.data.as_mut_ptr() as *mut u32, 0xFFFF_FFFF) };
+        assert_eq!(unsafe { Variant::from_raw(&raw) }, Some(Variant::I8(0xFFFF_FFFF)));
+
+        // VT_BSTR | VT_BYREF
+        raw.vt = VT_BSTR | 0x4000;
+        assert_eq!(unsafe { Variant::from_raw(&raw) }, None);
+    }
+}
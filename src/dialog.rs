@@ -7,7 +7,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use super::wchar::ToCU16Str;
+use super::wchar::WideCString;
 use super::window::Window;
 
 use user32;
@@ -18,8 +18,8 @@ pub trait DialogUtil {
 
 impl DialogUtil for Window {
     fn message_box(&self, msg: &str, title: &str) {
-        let msg_u = msg.to_c_u16();
-        let title_u = title.to_c_u16();
+        let msg_u = WideCString::from_str_truncate(msg);
+        let title_u = WideCString::from_str_truncate(title);
         unsafe {
             user32::MessageBoxW(self.wnd, msg_u.as_ptr(), title_u.as_ptr(), 0u32);
         }
@@ -0,0 +1,298 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Running closures on a window's thread from other threads.
+//!
+//! ```ignore
+//! let dispatcher = main.dispatcher().unwrap();
+//! thread::spawn(move || {
+//!     let text = expensive_computation();
+//!     dispatcher.post(move || edit.set_window_text(&text));
+//! });
+//! ```
+//!
+//! Closures are queued here rather than passed through `PostMessage`, whose
+//! messages are silently discarded when the window is destroyed. A window that
+//! goes away drops whatever is still queued and refuses further posts.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use kernel32;
+use user32;
+use winapi::{DWORD, LONG_PTR, UINT, c_int};
+
+use message;
+use window::{Window, main_wnd_proc};
+
+// The GetWindowLongPtrW index of the window procedure.
+const GWLP_WNDPROC: c_int = -4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Receives the values of a `UiSender`, boxed.
+type Handler = Box<dyn FnMut(Box<dyn Any>)>;
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    closed: bool,
+    // A `dispatch_message()` has been posted and not yet drained.
+    wake_pending: bool,
+}
+
+/// Posts closures to be run by a window's thread. Cheap to clone and `Send`.
+#[derive(Clone)]
+pub struct Dispatcher {
+    wnd: Window,
+    thread_id: DWORD,
+    queue: Arc<Mutex<Queue>>,
+}
+
+thread_local!(static QUEUES: RefCell<HashMap<Window, Arc<Mutex<Queue>>>> =
+    RefCell::new(HashMap::new()));
+
+thread_local!(static HANDLERS: RefCell<HashMap<usize, (Window, Handler)>> =
+    RefCell::new(HashMap::new()));
+
+static DISPATCH_MESSAGE: AtomicUsize = AtomicUsize::new(0);
+static NEXT_HANDLER_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The registered message that tells a window to run its queued closures.
+pub fn dispatch_message() -> UINT {
+    message::register_message_cached(&DISPATCH_MESSAGE, "RustWindowsDispatch")
+}
+
+impl Dispatcher {
+    /// Must be called on the thread that created `wnd`. Returns `None` otherwise,
+    /// or if `wnd` is not a window using `main_wnd_proc` (i.e. created by
+    /// `Window::new`), since only those drop their queue when destroyed.
+    pub fn new(wnd: Window) -> Option<Dispatcher> {
+        let thread_id = unsafe { kernel32::GetCurrentThreadId() };
+        if !wnd.is_window() || wnd.thread_id() != thread_id {
+            return None;
+        }
+        let wproc = unsafe { user32::GetWindowLongPtrW(wnd.wnd, GWLP_WNDPROC) };
+        if wproc != main_wnd_proc as *const () as LONG_PTR {
+            return None;
+        }
+        let queue = QUEUES.with(|q| {
+            q.borrow_mut().entry(wnd).or_insert_with(|| {
+                let queue = Queue { jobs: VecDeque::new(), closed: false, wake_pending: false };
+                Arc::new(Mutex::new(queue))
+            }).clone()
+        });
+        Some(Dispatcher { wnd, thread_id, queue })
+    }
+
+    pub fn window(&self) -> Window {
+        self.wnd
+    }
+
+    /// Whether the caller is on the window's thread.
+    pub fn is_ui_thread(&self) -> bool {
+        unsafe { kernel32::GetCurrentThreadId() == self.thread_id }
+    }
+
+    /// Queues `f` to run on the window's thread. Returns `false`, dropping `f`,
+    /// if the window has been destroyed or cannot be woken.
+    pub fn post<F>(&self, f: F) -> bool
+        where F: FnOnce() + Send + 'static {
+        let mut queue = match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(_) => return false,
+        };
+        if queue.closed {
+            return false;
+        }
+        // One message drains the whole queue, so only post when none is pending.
+        // `PostMessage` does not wait for the window, so the lock is held to keep
+        // the job out of the queue if it fails.
+        if !queue.wake_pending {
+            let msg = dispatch_message();
+            if msg == 0 || !self.wnd.post_message(msg, 0, 0) {
+                return false;
+            }
+            queue.wake_pending = true;
+        }
+        queue.jobs.push_back(Box::new(f));
+        true
+    }
+
+    /// Runs `f` on the window's thread and waits for its result. Returns `None`
+    /// if the window was destroyed before `f` ran.
+    ///
+    /// Called on the window's thread, this runs `f` directly instead of
+    /// deadlocking.
+    pub fn invoke<F, R>(&self, f: F) -> Option<R>
+        where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+        if self.is_ui_thread() {
+            return Some(f());
+        }
+        let (tx, rx) = mpsc::channel();
+        if !self.post(move || { let _ = tx.send(f()); }) {
+            return None;
+        }
+        // The sender is dropped unused if the queue is discarded.
+        rx.recv().ok()
+    }
+
+    /// Creates a sender of typed messages, which `handler` receives on the
+    /// window's thread. Returns `None` if not called on that thread, where the
+    /// handler is kept.
+    pub fn sender<T, F>(&self, mut handler: F) -> Option<UiSender<T>>
+        where T: Send + 'static, F: FnMut(T) + 'static {
+        if !self.is_ui_thread() {
+            return None;
+        }
+        let id = NEXT_HANDLER_ID.fetch_add(1, Ordering::Relaxed);
+        let handler = Box::new(move |value: Box<dyn Any>| {
+            if let Ok(value) = value.downcast::<T>() {
+                handler(*value);
+            }
+        });
+        HANDLERS.with(|h| h.borrow_mut().insert(id, (self.wnd, handler)));
+        Some(UiSender {
+            dispatcher: self.clone(),
+            id,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// Sends values of type `T` from any thread to a handler on a window's thread;
+/// see `Dispatcher::sender`.
+pub struct UiSender<T> {
+    dispatcher: Dispatcher,
+    id: usize,
+    marker: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for UiSender<T> {
+    fn clone(&self) -> UiSender<T> {
+        UiSender {
+            dispatcher: self.dispatcher.clone(),
+            id: self.id,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send + 'static> UiSender<T> {
+    /// Returns `false` if the window has been destroyed.
+    pub fn send(&self, value: T) -> bool {
+        let id = self.id;
+        self.dispatcher.post(move || deliver(id, Box::new(value)))
+    }
+}
+
+fn deliver(id: usize, value: Box<dyn Any>) {
+    // Take the handler out while it runs so that it may create senders or
+    // pump messages without a double borrow.
+    let entry = HANDLERS.with(|h| h.borrow_mut().remove(&id));
+    if let Some((wnd, mut handler)) = entry {
+        handler(value);
+        // Unless the handler destroyed its window.
+        if QUEUES.with(|q| q.borrow().contains_key(&wnd)) {
+            HANDLERS.with(|h| { h.borrow_mut().entry(id).or_insert((wnd, handler)); });
+        }
+    }
+}
+
+/// Runs the closures queued for `wnd`. Called from `main_wnd_proc` on
+/// `dispatch_message()`.
+pub fn run_pending(wnd: Window) {
+    let queue = match QUEUES.with(|q| q.borrow().get(&wnd).cloned()) {
+        Some(queue) => queue,
+        None => return,
+    };
+    loop {
+        let job = match queue.lock() {
+            Ok(mut queue) => {
+                let job = queue.jobs.pop_front();
+                if job.is_none() {
+                    // Drained: the next post needs a new wake.
+                    queue.wake_pending = false;
+                }
+                job
+            }
+            Err(_) => None,
+        };
+        match job {
+            Some(job) => job(),
+            None => break,
+        }
+    }
+}
+
+/// Drops everything queued for a window that is being destroyed, along with its
+/// typed message handlers. Called from `main_wnd_proc` on `WM_NCDESTROY`.
+pub fn close_window(wnd: Window) {
+    if let Some(queue) = QUEUES.with(|q| q.borrow_mut().remove(&wnd)) {
+        let jobs: Vec<Job> = match queue.lock() {
+            Ok(mut queue) => {
+                queue.closed = true;
+                queue.jobs.drain(..).collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        // Dropped outside the lock, since dropping a closure may post again.
+        drop(jobs);
+    }
+
+    let handlers: Vec<_> = HANDLERS.with(|h| {
+        let mut handlers = h.borrow_mut();
+        let ids: Vec<_> = handlers.iter().filter(|e| (e.1).0 == wnd).map(|e| *e.0).collect();
+        ids.iter().filter_map(|id| handlers.remove(id)).collect()
+    });
+    drop(handlers);
+}
+
+impl Window {
+    /// A `Dispatcher` for this window; see `Dispatcher::new`.
+    pub fn dispatcher(&self) -> Option<Dispatcher> {
+        Dispatcher::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::ptr;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use kernel32;
+
+    use super::{Dispatcher, HANDLERS, Queue};
+    use window::Window;
+
+    #[test]
+    fn test_sender_off_thread() {
+        let queue = Queue { jobs: VecDeque::new(), closed: false, wake_pending: false };
+        let dispatcher = Dispatcher {
+            wnd: Window { wnd: ptr::null_mut() },
+            thread_id: unsafe { kernel32::GetCurrentThreadId() },
+            queue: Arc::new(Mutex::new(queue)),
+        };
+        let other = dispatcher.clone();
+        let registered = thread::spawn(move || {
+            let sender = other.sender(|_: u32| {});
+            (sender.is_none(), HANDLERS.with(|h| h.borrow().len()))
+        }).join().unwrap();
+        assert_eq!(registered, (true, 0));
+
+        let sender = dispatcher.sender(|_: u32| {}).unwrap();
+        assert!(HANDLERS.with(|h| h.borrow().contains_key(&sender.id)));
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! High-DPI support.
+//!
+//! Sizes in this crate are physical pixels. A DPI-aware application should scale
+//! its layout with `scale` and friends, using `Window::dpi`, and handle
+//! `WM_DPICHANGED` (see `window::OnDpiChanged`) when it is moved between monitors.
+
+// `DpiAwareness` and the scaling arithmetic are portable so that `manifest`
+// can be used from build scripts.
+#[cfg(windows)] use std::mem;
+
+#[cfg(windows)] use winapi::{BOOL, HANDLE, HRESULT, HWND, RECT, UINT, c_int};
+
+#[cfg(windows)] use font::FontAttr;
+#[cfg(windows)] use gdi::WindowDc;
+#[cfg(windows)] use window::Window;
+
+/// The DPI at which no scaling is applied.
+pub const DEFAULT_DPI: u32 = 96;
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum DpiAwareness {
+    /// The system bitmap-stretches the window on high-DPI displays.
+    Unaware,
+    /// The DPI of the primary monitor at logon is used everywhere.
+    System,
+    /// The window receives `WM_DPICHANGED` when moved between monitors.
+    PerMonitor,
+    /// Like `PerMonitor`, but the system also scales the non-client area,
+    /// child windows and dialogs (Windows 10 1703 and later).
+    PerMonitorV2,
+}
+
+/// Sets the DPI awareness of the current process. This must happen before any
+/// window is created, and fails if it has already been set (e.g. by the manifest).
+///
+/// On systems that lack the requested level the closest supported one is used:
+/// `PerMonitorV2` falls back to `PerMonitor`, which falls back to `System`.
+#[cfg(windows)]
+pub fn set_process_dpi_awareness(awareness: DpiAwareness) -> bool {
+    // Windows 10 1703: DPI_AWARENESS_CONTEXT values are small negative handles.
+    if let Some(f) = ::get_proc_address("user32.dll", "SetProcessDpiAwarenessContext") {
+        let f: unsafe extern "system" fn(HANDLE) -> BOOL = unsafe { mem::transmute(f) };
+        let context = match awareness {
+            DpiAwareness::Unaware => -1isize,
+            DpiAwareness::System => -2,
+            DpiAwareness::PerMonitor => -3,
+            DpiAwareness::PerMonitorV2 => -4,
+        };
+        if unsafe { f(context as HANDLE) } != 0 {
+            return true;
+        }
+        if awareness != DpiAwareness::PerMonitorV2 {
+            return false;
+        }
+    }
+
+    // Windows 8.1: PROCESS_DPI_AWARENESS.
+    if let Some(f) = ::get_proc_address("shcore.dll", "SetProcessDpiAwareness") {
+        let f: unsafe extern "system" fn(c_int) -> HRESULT = unsafe { mem::transmute(f) };
+        let value = match awareness {
+            DpiAwareness::Unaware => 0,
+            DpiAwareness::System => 1,
+            DpiAwareness::PerMonitor | DpiAwareness::PerMonitorV2 => 2,
+        };
+        return unsafe { f(value) } >= 0;
+    }
+
+    // Windows Vista: system awareness only.
+    if awareness == DpiAwareness::Unaware {
+        return true;
+    }
+    match ::get_proc_address("user32.dll", "SetProcessDPIAware") {
+        Some(f) => {
+            let f: unsafe extern "system" fn() -> BOOL = unsafe { mem::transmute(f) };
+            unsafe { f() != 0 }
+        }
+        None => false,
+    }
+}
+
+/// The DPI of the primary monitor, which is what DPI-unaware and system-aware
+/// windows are laid out for.
+#[cfg(windows)]
+pub fn system_dpi() -> u32 {
+    if let Some(f) = ::get_proc_address("user32.dll", "GetDpiForSystem") {
+        let f: unsafe extern "system" fn() -> UINT = unsafe { mem::transmute(f) };
+        return unsafe { f() };
+    }
+    match WindowDc::screen() {
+        Some(dc) => dc.dc.logical_dpi().1,
+        None => DEFAULT_DPI,
+    }
+}
+
+#[cfg(windows)]
+impl Window {
+    /// The DPI the window should be laid out for. Without per-monitor awareness
+    /// (or before Windows 10 1607) this is the system DPI.
+    pub fn dpi(&self) -> u32 {
+        if let Some(f) = ::get_proc_address("user32.dll", "GetDpiForWindow") {
+            let f: unsafe extern "system" fn(HWND) -> UINT = unsafe { mem::transmute(f) };
+            let dpi = unsafe { f(self.wnd) };
+            if dpi != 0 {
+                return dpi;
+            }
+        }
+        system_dpi()
+    }
+}
+
+/// Scales a length given at `DEFAULT_DPI` to `dpi`.
+pub fn scale(value: isize, dpi: u32) -> isize {
+    scale_between(value, DEFAULT_DPI, dpi)
+}
+
+/// Scales a length given at `dpi` back to `DEFAULT_DPI`.
+pub fn unscale(value: isize, dpi: u32) -> isize {
+    scale_between(value, dpi, DEFAULT_DPI)
+}
+
+/// Scales a length from one DPI to another, rounding to the nearest pixel.
+pub fn scale_between(value: isize, from_dpi: u32, to_dpi: u32) -> isize {
+    if from_dpi == 0 {
+        return value;
+    }
+    let (num, den) = (value as i64 * to_dpi as i64, from_dpi as i64);
+    let rounded = if num < 0 { (num - den / 2) / den } else { (num + den / 2) / den };
+    rounded as isize
+}
+
+/// Scales a `(width, height)` or `(x, y)` pair from one DPI to another.
+pub fn scale_pair(pair: (isize, isize), from_dpi: u32, to_dpi: u32) -> (isize, isize) {
+    (scale_between(pair.0, from_dpi, to_dpi), scale_between(pair.1, from_dpi, to_dpi))
+}
+
+/// Scales every edge of a rectangle from one DPI to another.
+#[cfg(windows)]
+pub fn scale_rect(rect: RECT, from_dpi: u32, to_dpi: u32) -> RECT {
+    let s = |v: i32| scale_between(v as isize, from_dpi, to_dpi) as i32;
+    RECT {
+        left: s(rect.left),
+        top: s(rect.top),
+        right: s(rect.right),
+        bottom: s(rect.bottom),
+    }
+}
+
+#[cfg(windows)]
+impl FontAttr {
+    /// Returns a copy with height and width scaled from one DPI to another,
+    /// e.g. to recreate a font in response to `WM_DPICHANGED`.
+    pub fn scaled(&self, from_dpi: u32, to_dpi: u32) -> FontAttr {
+        let mut attr = self.clone();
+        attr.height = scale_between(self.height, from_dpi, to_dpi);
+        attr.width = scale_between(self.width, from_dpi, to_dpi);
+        attr
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scale, unscale, scale_between};
+
+    #[cfg(windows)] use winapi::RECT;
+    #[cfg(windows)] use super::scale_rect;
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(100, 96), 100);
+        assert_eq!(scale(100, 144), 150);
+        assert_eq!(scale(-12, 120), -15);
+        assert_eq!(scale(1, 120), 1);
+        assert_eq!(unscale(150, 144), 100);
+        assert_eq!(scale_between(7, 96, 144), 11);
+        assert_eq!(scale_between(-7, 96, 144), -11);
+        assert_eq!(scale_between(7, 0, 144), 7);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_scale_rect() {
+        let r = RECT { left: -10, top: 0, right: 200, bottom: 100 };
+        let s = scale_rect(r, 96, 192);
+        assert_eq!((s.left, s.top, s.right, s.bottom), (-20, 0, 400, 200));
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Files dropped from Explorer.
+//!
+//! ```ignore
+//! wnd_proc!(MainFrame, win, WM_CREATE, WM_DROPFILES, ANY);
+//!
+//! impl OnDropFiles for MainFrame {
+//!     fn on_drop_files(&self, files: Vec<PathBuf>, point: Option<(isize, isize)>) { ... }
+//! }
+//!
+//! main.accept_files(true);
+//! ```
+//!
+//! The point is in client coordinates, and `None` when the files were dropped on
+//! the non-client area, such as the title bar.
+
+use std::ffi::OsString;
+use std::mem;
+use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
+use std::ptr;
+use std::slice;
+
+use kernel32;
+use winapi::{BOOL, DWORD, HDROP, HWND, POINT, UINT};
+
+use wchar::MultiString;
+use window::Window;
+
+// Missing from the sys crates.
+#[link(name = "shell32")]
+extern "system" {
+    fn DragAcceptFiles(hWnd: HWND, fAccept: BOOL);
+    fn DragFinish(hDrop: HDROP);
+    fn DragQueryFileW(hDrop: HDROP, iFile: UINT, lpszFile: *mut u16, cch: UINT) -> UINT;
+}
+
+// The header of the memory behind an HDROP.
+#[repr(C)]
+#[allow(non_snake_case)]
+struct DROPFILES {
+    pFiles: DWORD,
+    pt: POINT,
+    fNC: BOOL,
+    fWide: BOOL,
+}
+
+/// Decodes the file list of an `HDROP`, as found on the clipboard or in
+/// `WM_DROPFILES`. The handle is not released.
+///
+/// # Safety
+///
+/// `hdrop` must be a global memory handle. Its contents may come from another
+/// process and are checked.
+pub unsafe fn hdrop_files(hdrop: HDROP) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let size = kernel32::GlobalSize(hdrop as _) as usize;
+    if size < mem::size_of::<DROPFILES>() {
+        return files;
+    }
+    let header = kernel32::GlobalLock(hdrop as _) as *const DROPFILES;
+    if header.is_null() {
+        return files;
+    }
+    let offset = (*header).pFiles as usize;
+    if (*header).fWide != 0 {
+        // NUL-separated names, ending with an empty one. Bounded by the size of
+        // the memory, in case the final NUL is missing.
+        if offset <= size && offset % 2 == 0 {
+            let names = (header as *const u8).add(offset) as *const u16;
+            let names = slice::from_raw_parts(names, (size - offset) / 2);
+            files.extend(MultiString::parse(names).map(|name| name.to_path_buf()));
+        }
+    } else {
+        // ANSI names, from applications predating Unicode; let the shell convert.
+        let count = DragQueryFileW(hdrop, 0xFFFF_FFFF, ptr::null_mut(), 0);
+        for i in 0..count {
+            let len = DragQueryFileW(hdrop, i, ptr::null_mut(), 0);
+            let mut buf = vec![0u16; len as usize + 1];
+            let len = DragQueryFileW(hdrop, i, buf.as_mut_ptr(), buf.len() as UINT);
+            buf.truncate(len as usize);
+            files.push(PathBuf::from(OsString::from_wide(&buf)));
+        }
+    }
+    kernel32::GlobalUnlock(hdrop as _);
+    files
+}
+
+/// The `HDROP` of a `WM_DROPFILES` message, released when dropped.
+pub struct DropHandle {
+    hdrop: HDROP,
+}
+
+impl DropHandle {
+    /// # Safety
+    ///
+    /// `hdrop` must come from `WM_DROPFILES` and not be released elsewhere.
+    pub unsafe fn new(hdrop: HDROP) -> DropHandle {
+        DropHandle { hdrop }
+    }
+
+    pub fn files(&self) -> Vec<PathBuf> {
+        unsafe { hdrop_files(self.hdrop) }
+    }
+
+    /// Where the files were dropped, in client coordinates. `None` if they were
+    /// dropped on the non-client area.
+    pub fn point(&self) -> Option<(isize, isize)> {
+        unsafe {
+            if (kernel32::GlobalSize(self.hdrop as _) as usize) < mem::size_of::<DROPFILES>() {
+                return None;
+            }
+            let header = kernel32::GlobalLock(self.hdrop as _) as *const DROPFILES;
+            if header.is_null() {
+                return None;
+            }
+            let header = &*header;
+            let point = if header.fNC != 0 {
+                None
+            } else {
+                Some((header.pt.x as isize, header.pt.y as isize))
+            };
+            kernel32::GlobalUnlock(self.hdrop as _);
+            point
+        }
+    }
+}
+
+impl Drop for DropHandle {
+    fn drop(&mut self) {
+        unsafe { DragFinish(self.hdrop) };
+    }
+}
+
+impl Window {
+    /// Lets the window receive files dropped from Explorer (see `OnDropFiles`),
+    /// or stops it.
+    ///
+    /// An elevated process does not normally receive drops from unelevated ones,
+    /// so accepting also lets the messages involved through the window's message
+    /// filter. Stopping leaves the filter alone.
+    pub fn accept_files(&self, accept: bool) {
+        unsafe { DragAcceptFiles(self.wnd, accept as BOOL) };
+        if accept {
+            // WM_DROPFILES, WM_COPYDATA and WM_COPYGLOBALDATA
+            for &msg in &[0x0233, 0x004A, 0x0049] {
+                self.allow_message(msg);
+            }
+        }
+    }
+}
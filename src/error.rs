@@ -0,0 +1,163 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The crate's error type: an `HRESULT`, which also covers Win32 error codes.
+//!
+//! ```ignore
+//! let taskbar: ComPtr<ITaskbarList3> = com::create_instance(&CLSID_TASKBARLIST)?;
+//! ```
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+#[cfg(windows)] use std::ptr;
+
+#[cfg(windows)] use kernel32;
+
+// FACILITY_WIN32, the facility of HRESULTs wrapping Win32 error codes.
+const FACILITY_WIN32: u32 = 7;
+
+#[derive(Clone,Copy,PartialEq,Eq,Hash)]
+pub struct Error {
+    code: i32,
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+// Common HRESULTs.
+pub const S_OK: i32 = 0;
+pub const S_FALSE: i32 = 1;
+pub const E_NOTIMPL: i32 = 0x8000_4001u32 as i32;
+pub const E_NOINTERFACE: i32 = 0x8000_4002u32 as i32;
+pub const E_POINTER: i32 = 0x8000_4003u32 as i32;
+pub const E_FAIL: i32 = 0x8000_4005u32 as i32;
+pub const E_OUTOFMEMORY: i32 = 0x8007_000Eu32 as i32;
+pub const E_INVALIDARG: i32 = 0x8007_0057u32 as i32;
+
+impl Error {
+    /// An error from a failed `HRESULT`. Success codes make nonsensical errors,
+    /// so use `com::check` on results that may be either.
+    pub fn from_hresult(hr: i32) -> Error {
+        Error { code: hr }
+    }
+
+    /// An error from a Win32 error code, as `HRESULT_FROM_WIN32` maps it.
+    pub fn from_win32(code: u32) -> Error {
+        if code as i32 <= 0 {
+            Error { code: code as i32 }
+        } else {
+            Error { code: ((code & 0xFFFF) | (FACILITY_WIN32 << 16) | 0x8000_0000) as i32 }
+        }
+    }
+
+    /// The calling thread's last Win32 error.
+    #[cfg(windows)]
+    pub fn last_error() -> Error {
+        Error::from_win32(unsafe { kernel32::GetLastError() })
+    }
+
+    pub fn hresult(&self) -> i32 {
+        self.code
+    }
+
+    /// The Win32 error code, if the error wraps one.
+    pub fn win32_code(&self) -> Option<u32> {
+        let code = self.code as u32;
+        if code & 0x8000_0000 != 0 && (code >> 16) & 0x1FFF == FACILITY_WIN32 {
+            Some(code & 0xFFFF)
+        } else {
+            None
+        }
+    }
+
+    /// The system's description of the error, if it has one.
+    #[cfg(windows)]
+    pub fn message(&self) -> Option<String> {
+        let mut buf = [0u16; 512];
+        let len = unsafe {
+            // FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS
+            kernel32::FormatMessageW(0x1000 | 0x0200, ptr::null(), self.code as u32, 0,
+                                     buf.as_mut_ptr(), buf.len() as u32, ptr::null_mut())
+        };
+        if len == 0 {
+            return None;
+        }
+        let message = String::from_utf16_lossy(&buf[..len as usize]);
+        Some(message.trim_end().to_string())
+    }
+
+    #[cfg(not(windows))]
+    pub fn message(&self) -> Option<String> {
+        None
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error(0x{:08X})", self.code as u32)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "{} (0x{:08X})", message, self.code as u32),
+            None => write!(f, "HRESULT 0x{:08X}", self.code as u32),
+        }
+    }
+}
+
+impl error::Error for Error {
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        match err.win32_code() {
+            Some(code) => io::Error::from_raw_os_error(code as i32),
+            None => io::Error::other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, E_NOINTERFACE};
+    use std::io;
+
+    #[test]
+    fn test_from_win32() {
+        // ERROR_ACCESS_DENIED
+        let err = Error::from_win32(5);
+        assert_eq!(err.hresult() as u32, 0x8007_0005);
+        assert_eq!(err.win32_code(), Some(5));
+        assert_eq!(Error::from_win32(0).hresult(), 0);
+
+        // E_NOINTERFACE is not a Win32 error.
+        assert_eq!(Error::from_hresult(E_NOINTERFACE).win32_code(), None);
+        // Nor are values already in HRESULT form.
+        assert_eq!(Error::from_win32(0x8000_4002).hresult() as u32, 0x8000_4002);
+    }
+
+    #[test]
+    fn test_display() {
+        let err = Error::from_hresult(E_NOINTERFACE);
+        assert!(err.to_string().contains("0x80004002"));
+        assert_eq!(format!("{:?}", err), "Error(0x80004002)");
+    }
+
+    #[test]
+    fn test_io_error() {
+        let err: io::Error = Error::from_win32(5).into();
+        assert_eq!(err.raw_os_error(), Some(5));
+        let err: io::Error = Error::from_hresult(E_NOINTERFACE).into();
+        assert_eq!(err.raw_os_error(), None);
+    }
+}
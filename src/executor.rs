@@ -0,0 +1,494 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A single-threaded executor for running futures on the UI thread.
+//!
+//! `main_window_loop` polls the tasks of the current thread's executor between
+//! messages, so UI code can `spawn_local` futures that touch windows directly:
+//!
+//! ```ignore
+//! spawn_local(async move {
+//!     if timer::sleep(Duration::from_secs(1)).await.is_ok() {
+//!         edit.set_window_text("One second later");
+//!     }
+//! });
+//! ```
+//!
+//! Wakers may be used from any thread; waking signals an event that the message
+//! loop waits on alongside window messages. Tasks do not run while a modal loop
+//! (a message box, a menu, window resizing) is active.
+//!
+//! `Executor` itself is platform-independent; only the per-thread instance and
+//! its message loop integration are Windows-specific.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+#[cfg(windows)] use std::ptr;
+#[cfg(windows)] use std::rc::Rc;
+
+#[cfg(windows)] use kernel32;
+#[cfg(windows)] use error::Error;
+#[cfg(windows)] use winapi::HANDLE;
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+type Notify = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Default)]
+struct ReadyQueue {
+    ids: VecDeque<usize>,
+    notify: Option<Notify>,
+}
+
+impl ReadyQueue {
+    fn schedule(ready: &Mutex<ReadyQueue>, id: usize) {
+        let notify = match ready.lock() {
+            Ok(mut ready) => {
+                if ready.ids.contains(&id) {
+                    return;
+                }
+                ready.ids.push_back(id);
+                ready.notify.clone()
+            }
+            Err(_) => return,
+        };
+        // Outside the lock, in case the callback wakes another task.
+        if let Some(notify) = notify {
+            notify();
+        }
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<ReadyQueue>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        ReadyQueue::schedule(&self.ready, self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        ReadyQueue::schedule(&self.ready, self.id);
+    }
+}
+
+/// Runs `!Send` futures on the thread that owns it.
+#[derive(Default)]
+pub struct Executor {
+    tasks: RefCell<HashMap<usize, Task>>,
+    // Tasks being polled, out of `tasks`, and whether they were woken meanwhile.
+    polling: RefCell<HashMap<usize, bool>>,
+    next_id: Cell<usize>,
+    ready: Arc<Mutex<ReadyQueue>>,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Default::default()
+    }
+
+    /// Sets a callback invoked, possibly from another thread, whenever a task
+    /// becomes ready to be polled. Typically it wakes the thread running the
+    /// executor.
+    pub fn set_notify<F>(&self, notify: F)
+        where F: Fn() + Send + Sync + 'static {
+        if let Ok(mut ready) = self.ready.lock() {
+            ready.notify = Some(Arc::new(notify));
+        }
+    }
+
+    /// Adds a task, which is first polled by the next `run_until_stalled`.
+    pub fn spawn<F>(&self, future: F)
+        where F: Future<Output = ()> + 'static {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        self.tasks.borrow_mut().insert(id, Box::pin(future));
+        ReadyQueue::schedule(&self.ready, id);
+    }
+
+    /// The number of unfinished tasks.
+    pub fn task_count(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    pub fn has_ready_tasks(&self) -> bool {
+        self.ready.lock().map(|ready| !ready.ids.is_empty()).unwrap_or(false)
+    }
+
+    /// Polls the tasks that are ready when called, returning the number of polls.
+    /// Tasks woken meanwhile, including by their own poll, wait for the next call,
+    /// so a task that always wakes itself cannot keep the caller here; the notify
+    /// callback has been invoked for them.
+    pub fn run_until_stalled(&self) -> usize {
+        let batch = match self.ready.lock() {
+            Ok(mut ready) => mem::take(&mut ready.ids),
+            Err(_) => return 0,
+        };
+        let mut polls = 0;
+        for id in batch {
+            // Taken out of the map while polled so that the task may spawn others.
+            // A task that is missing has finished, or is being polled further up
+            // the stack by a nested call, which reschedules it once done.
+            let mut task = match self.tasks.borrow_mut().remove(&id) {
+                Some(task) => task,
+                None => {
+                    if let Some(woken) = self.polling.borrow_mut().get_mut(&id) {
+                        *woken = true;
+                    }
+                    continue;
+                }
+            };
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            polls += 1;
+            self.polling.borrow_mut().insert(id, false);
+            let pending = task.as_mut().poll(&mut Context::from_waker(&waker)).is_pending();
+            let woken = self.polling.borrow_mut().remove(&id).unwrap_or(false);
+            if pending {
+                self.tasks.borrow_mut().insert(id, task);
+                if woken {
+                    ReadyQueue::schedule(&self.ready, id);
+                }
+            }
+        }
+        polls
+    }
+}
+
+struct OneshotState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    done: bool,
+}
+
+/// Completes a `Completion`; see `oneshot`.
+pub struct Completer<T> {
+    state: Arc<Mutex<OneshotState<T>>>,
+}
+
+/// A future resolving to the value passed to `Completer::complete`, or to `None`
+/// if the completer is dropped first.
+pub struct Completion<T> {
+    state: Arc<Mutex<OneshotState<T>>>,
+}
+
+/// Creates a single-use channel, e.g. to await the result of a modeless dialog:
+/// give the `Completer` to the dialog, which completes it when closed, and
+/// await the `Completion`.
+pub fn oneshot<T>() -> (Completer<T>, Completion<T>) {
+    let state = Arc::new(Mutex::new(OneshotState { value: None, waker: None, done: false }));
+    (Completer { state: state.clone() }, Completion { state })
+}
+
+impl<T> Completer<T> {
+    pub fn complete(self, value: T) {
+        self.finish(Some(value));
+    }
+
+    fn finish(&self, value: Option<T>) {
+        let waker = match self.state.lock() {
+            Ok(mut state) => {
+                if state.done {
+                    return;
+                }
+                state.done = true;
+                state.value = value;
+                state.waker.take()
+            }
+            Err(_) => None,
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.finish(None);
+    }
+}
+
+impl<T> Future for Completion<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Poll::Ready(None),
+        };
+        if state.done {
+            Poll::Ready(state.value.take())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(windows)]
+struct LocalExecutor {
+    executor: Executor,
+    event: HANDLE,
+}
+
+#[cfg(windows)]
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        unsafe { kernel32::CloseHandle(self.event) };
+    }
+}
+
+#[cfg(windows)]
+thread_local!(static LOCAL: RefCell<Option<Rc<LocalExecutor>>> = RefCell::new(None));
+
+#[cfg(windows)]
+fn local_executor() -> Rc<LocalExecutor> {
+    LOCAL.with(|local| {
+        local.borrow_mut().get_or_insert_with(|| {
+            // Auto-reset, so that a wait consumes the signal.
+            let event = unsafe { kernel32::CreateEventW(ptr::null_mut(), 0, 0, ptr::null()) };
+            if event.is_null() {
+                panic!("cannot create the executor's wake event: {}", Error::last_error());
+            }
+            let executor = Executor::new();
+            // Handles may be used from any thread.
+            let event_addr = event as usize;
+            executor.set_notify(move || unsafe {
+                kernel32::SetEvent(event_addr as HANDLE);
+            });
+            Rc::new(LocalExecutor { executor, event })
+        }).clone()
+    })
+}
+
+/// Spawns a task on the current thread's executor, which `main_window_loop`
+/// drives.
+#[cfg(windows)]
+pub fn spawn_local<F>(future: F)
+    where F: Future<Output = ()> + 'static {
+    local_executor().executor.spawn(future);
+}
+
+/// Polls the current thread's ready tasks, if it has an executor. Called by
+/// `main_window_loop`; a custom message loop should call it before waiting.
+#[cfg(windows)]
+pub fn run_local_tasks() -> usize {
+    match LOCAL.with(|local| local.borrow().clone()) {
+        Some(local) => local.executor.run_until_stalled(),
+        None => 0,
+    }
+}
+
+/// The event signalled when a task of the current thread becomes ready, or null
+/// if the thread has no executor.
+#[cfg(windows)]
+pub fn local_wake_event() -> HANDLE {
+    match LOCAL.with(|local| local.borrow().clone()) {
+        Some(local) => local.event,
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
+    use std::thread;
+
+    use super::{Executor, oneshot};
+
+    /// Pending until polled `n` times, waking itself each time.
+    struct Yield(usize);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    /// Runs `f` once and completes.
+    struct Run<F: FnMut()>(F);
+
+    impl<F: FnMut() + Unpin> Future for Run<F> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+            (self.0)();
+            Poll::Ready(())
+        }
+    }
+
+    #[test]
+    fn test_run_until_stalled() {
+        let ex = Executor::new();
+        ex.spawn(Yield(2));
+        ex.spawn(Yield(0));
+        assert_eq!(ex.task_count(), 2);
+        assert!(ex.has_ready_tasks());
+        // Each call polls both tasks once, then the one still waking itself.
+        assert_eq!(ex.run_until_stalled(), 2);
+        assert_eq!(ex.task_count(), 1);
+        assert!(ex.has_ready_tasks());
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert_eq!(ex.task_count(), 0);
+        assert!(!ex.has_ready_tasks());
+        assert_eq!(ex.run_until_stalled(), 0);
+    }
+
+    #[test]
+    fn test_self_waking_task_returns() {
+        let ex = Executor::new();
+        let notified = Arc::new(AtomicUsize::new(0));
+        let n = notified.clone();
+        ex.set_notify(move || { n.fetch_add(1, Ordering::SeqCst); });
+        ex.spawn(Yield(usize::MAX));
+        for i in 1..4 {
+            assert_eq!(ex.run_until_stalled(), 1);
+            assert!(ex.has_ready_tasks());
+            // The spawn, then one wake per poll.
+            assert_eq!(notified.load(Ordering::SeqCst), i + 1);
+        }
+    }
+
+    #[test]
+    fn test_non_send_task() {
+        let ex = Executor::new();
+        let counter = Rc::new(Cell::new(0));
+        let c = counter.clone();
+        ex.spawn(Run(move || c.set(c.get() + 1)));
+        ex.run_until_stalled();
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[test]
+    fn test_spawn_from_task() {
+        let ex = Rc::new(Executor::new());
+        let ran = Rc::new(Cell::new(false));
+        let (ex2, ran2) = (ex.clone(), ran.clone());
+        let inner = RefCell::new(Some(Run(move || ran2.set(true))));
+        ex.spawn(Run(move || {
+            if let Some(f) = inner.borrow_mut().take() {
+                ex2.spawn(f);
+            }
+        }));
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert!(!ran.get());
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn test_wake_during_nested_run() {
+        // A task that wakes itself and then runs the executor from inside its poll,
+        // as a modal loop pumping messages would.
+        let ex = Rc::new(Executor::new());
+        let polls = Rc::new(Cell::new(0));
+        let (ex2, p) = (ex.clone(), polls.clone());
+        ex.spawn(Nested(move |cx: &mut Context| {
+            p.set(p.get() + 1);
+            if p.get() > 1 {
+                return Poll::Ready(());
+            }
+            cx.waker().wake_by_ref();
+            ex2.run_until_stalled();
+            Poll::Pending
+        }));
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert_eq!(polls.get(), 1);
+        // The wake seen by the nested call is kept for the next one.
+        assert!(ex.has_ready_tasks());
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert_eq!(polls.get(), 2);
+        assert_eq!(ex.task_count(), 0);
+    }
+
+    /// Polls with `f`.
+    struct Nested<F>(F);
+
+    impl<F: FnMut(&mut Context) -> Poll<()> + Unpin> Future for Nested<F> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            (self.0)(cx)
+        }
+    }
+
+    #[test]
+    fn test_oneshot_cross_thread_wake() {
+        let ex = Executor::new();
+        let notified = Arc::new(AtomicUsize::new(0));
+        let n = notified.clone();
+        ex.set_notify(move || { n.fetch_add(1, Ordering::SeqCst); });
+
+        let (completer, completion) = oneshot::<u32>();
+        let result = Rc::new(Cell::new(None));
+        let r = result.clone();
+        ex.spawn(Await(completion, move |v| r.set(Some(v))));
+        assert_eq!(ex.run_until_stalled(), 1);
+        assert_eq!(ex.task_count(), 1);
+        let before = notified.load(Ordering::SeqCst);
+
+        thread::spawn(move || completer.complete(42)).join().unwrap();
+        assert_eq!(notified.load(Ordering::SeqCst), before + 1);
+        assert!(ex.has_ready_tasks());
+        ex.run_until_stalled();
+        assert_eq!(result.get(), Some(Some(42)));
+        assert_eq!(ex.task_count(), 0);
+    }
+
+    #[test]
+    fn test_oneshot_dropped_completer() {
+        let ex = Executor::new();
+        let (completer, completion) = oneshot::<u32>();
+        let result = Rc::new(Cell::new(None));
+        let r = result.clone();
+        ex.spawn(Await(completion, move |v| r.set(Some(v))));
+        ex.run_until_stalled();
+        drop(completer);
+        ex.run_until_stalled();
+        assert_eq!(result.get(), Some(None));
+    }
+
+    /// Awaits `fut` and passes its output to `f`.
+    struct Await<T, F>(T, F);
+
+    impl<T: Future + Unpin, F: FnMut(T::Output) + Unpin> Future for Await<T, F> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            match Pin::new(&mut self.0).poll(cx) {
+                Poll::Ready(v) => {
+                    (self.1)(v);
+                    Poll::Ready(())
+                }
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+}
@@ -10,12 +10,18 @@
 #![allow(non_camel_case_types)]
 
 use std::ptr;
+use std::mem;
 use std::default::Default;
 
 use gdi32;
-use winapi::{DWORD, HFONT, c_int};
+use user32;
+use winapi::{
+    DWORD, ENUMLOGFONTEXW, HFONT, HGDIOBJ, LF_FACESIZE, LOGFONTW, LPARAM, LPVOID,
+    NONCLIENTMETRICSW, RECT, SPI_GETNONCLIENTMETRICS, TEXTMETRICW, UINT, VOID, WCHAR, c_int,
+};
 
-use wchar::ToCU16Str;
+use wchar::{WideCString, from_c_u16_buf, to_c_u16_buf};
+use gdi::Dc;
 
 #[derive(Clone,Copy)]
 pub enum CharSet {
@@ -41,6 +47,34 @@ pub enum CharSet {
     MAC_CHARSET = 77,
 }
 
+impl CharSet {
+    /// Converts an `lfCharSet` or `tmCharSet`. Unknown values map to
+    /// `DEFAULT_CHARSET`.
+    pub fn from_raw(v: u8) -> CharSet {
+        match v {
+            0 => CharSet::ANSI_CHARSET,
+            2 => CharSet::SYMBOL_CHARSET,
+            128 => CharSet::SHIFTJIS_CHARSET,
+            129 => CharSet::HANGUL_CHARSET,
+            134 => CharSet::GB2312_CHARSET,
+            136 => CharSet::CHINESEBIG5_CHARSET,
+            161 => CharSet::GREEK_CHARSET,
+            162 => CharSet::TURKISH_CHARSET,
+            177 => CharSet::HEBREW_CHARSET,
+            178 => CharSet::ARABIC_CHARSET,
+            186 => CharSet::BALTIC_CHARSET,
+            204 => CharSet::RUSSIAN_CHARSET,
+            222 => CharSet::THAI_CHARSET,
+            238 => CharSet::EASTEUROPE_CHARSET,
+            255 => CharSet::OEM_CHARSET,
+            130 => CharSet::JOHAB_CHARSET,
+            163 => CharSet::VIETNAMESE_CHARSET,
+            77 => CharSet::MAC_CHARSET,
+            _ => CharSet::DEFAULT_CHARSET,
+        }
+    }
+}
+
 #[derive(Clone,Copy)]
 pub enum OutputPrecision {
     OUT_DEFAULT_PRECIS = 0,
@@ -55,6 +89,25 @@ pub enum OutputPrecision {
     OUT_PS_ONLY_PRECIS = 10,
 }
 
+impl OutputPrecision {
+    /// Converts an `lfOutPrecision`. Values without a variant, such as 9
+    /// (`OUT_SCREEN_OUTLINE_PRECIS`), map to `OUT_DEFAULT_PRECIS`.
+    pub fn from_raw(v: u8) -> OutputPrecision {
+        match v {
+            1 => OutputPrecision::OUT_STRING_PRECIS,
+            2 => OutputPrecision::OUT_CHARACTER_PRECIS,
+            3 => OutputPrecision::OUT_STROKE_PRECIS,
+            4 => OutputPrecision::OUT_TT_PRECIS,
+            5 => OutputPrecision::OUT_DEVICE_PRECIS,
+            6 => OutputPrecision::OUT_RASTER_PRECIS,
+            7 => OutputPrecision::OUT_TT_ONLY_PRECIS,
+            8 => OutputPrecision::OUT_OUTLINE_PRECIS,
+            10 => OutputPrecision::OUT_PS_ONLY_PRECIS,
+            _ => OutputPrecision::OUT_DEFAULT_PRECIS,
+        }
+    }
+}
+
 #[derive(Clone,Copy)]
 pub enum ClipPrecision {
     CLIP_DEFAULT_PRECIS = 0,
@@ -69,6 +122,22 @@ pub enum ClipPrecision {
     // CLIP_DFA_OVERRIDE
 }
 
+impl ClipPrecision {
+    /// Converts an `lfClipPrecision`. Its values are flags, and combinations
+    /// other than the listed ones map to `CLIP_DEFAULT_PRECIS`.
+    pub fn from_raw(v: u8) -> ClipPrecision {
+        match v {
+            1 => ClipPrecision::CLIP_CHARACTER_PRECIS,
+            2 => ClipPrecision::CLIP_STROKE_PRECIS,
+            15 => ClipPrecision::CLIP_MASK,
+            16 => ClipPrecision::CLIP_LH_ANGLES,
+            32 => ClipPrecision::CLIP_TT_ALWAYS,
+            128 => ClipPrecision::CLIP_EMBEDDED,
+            _ => ClipPrecision::CLIP_DEFAULT_PRECIS,
+        }
+    }
+}
+
 #[derive(Clone,Copy)]
 pub enum Quality {
     DEFAULT_QUALITY = 0,
@@ -80,6 +149,21 @@ pub enum Quality {
     CLEARTYPE_QUALITY = 5,
 }
 
+impl Quality {
+    /// Converts an `lfQuality`. Values from newer systems, such as
+    /// `CLEARTYPE_NATURAL_QUALITY`, map to `DEFAULT_QUALITY`.
+    pub fn from_raw(v: u8) -> Quality {
+        match v {
+            1 => Quality::DRAFT_QUALITY,
+            2 => Quality::PROOF_QUALITY,
+            3 => Quality::NONANTIALIASED_QUALITY,
+            4 => Quality::ANTIALIASED_QUALITY,
+            5 => Quality::CLEARTYPE_QUALITY,
+            _ => Quality::DEFAULT_QUALITY,
+        }
+    }
+}
+
 #[derive(Clone,Copy)]
 pub enum Pitch {
     DEFAULT_PITCH = 0,
@@ -87,6 +171,18 @@ pub enum Pitch {
     VARIABLE_PITCH = 2,
 }
 
+impl Pitch {
+    /// Converts the low four bits of `lfPitchAndFamily`. Not for
+    /// `tmPitchAndFamily`, whose low bits are `TMPF_*` flags instead.
+    pub fn from_raw(v: u8) -> Pitch {
+        match v {
+            1 => Pitch::FIXED_PITCH,
+            2 => Pitch::VARIABLE_PITCH,
+            _ => Pitch::DEFAULT_PITCH,
+        }
+    }
+}
+
 #[derive(Clone,Copy)]
 pub enum Family {
     FF_DECORATIVE = 80,
@@ -97,12 +193,116 @@ pub enum Family {
     FF_SWISS = 32,
 }
 
+impl Family {
+    /// Converts the high four bits of `lfPitchAndFamily` or `tmPitchAndFamily`,
+    /// mapping unknown families to `FF_DONTCARE`.
+    pub fn from_raw(v: u8) -> Family {
+        match v {
+            80 => Family::FF_DECORATIVE,
+            48 => Family::FF_MODERN,
+            16 => Family::FF_ROMAN,
+            64 => Family::FF_SCRIPT,
+            32 => Family::FF_SWISS,
+            _ => Family::FF_DONTCARE,
+        }
+    }
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum Weight {
+    DontCare,
+    Thin,
+    ExtraLight,
+    Light,
+    Normal,
+    Medium,
+    SemiBold,
+    Bold,
+    ExtraBold,
+    Black,
+    /// A weight between the named ones, such as 350 for Semilight, up to 1000.
+    Other(isize),
+}
+
+impl Weight {
+    /// Converts a raw `lfWeight`. Values between the named weights are kept as
+    /// `Other`, so that a font's attributes recreate the same font.
+    pub fn from_raw(v: isize) -> Weight {
+        match v {
+            0 => Weight::DontCare,
+            100 => Weight::Thin,
+            200 => Weight::ExtraLight,
+            300 => Weight::Light,
+            400 => Weight::Normal,
+            500 => Weight::Medium,
+            600 => Weight::SemiBold,
+            700 => Weight::Bold,
+            800 => Weight::ExtraBold,
+            900 => Weight::Black,
+            v => Weight::Other(v),
+        }
+    }
+
+    /// The `lfWeight` value.
+    pub fn to_raw(self) -> isize {
+        match self {
+            Weight::DontCare => 0,
+            Weight::Thin => 100,
+            Weight::ExtraLight => 200,
+            Weight::Light => 300,
+            Weight::Normal => 400,
+            Weight::Medium => 500,
+            Weight::SemiBold => 600,
+            Weight::Bold => 700,
+            Weight::ExtraBold => 800,
+            Weight::Black => 900,
+            Weight::Other(v) => v,
+        }
+    }
+}
+
+#[derive(Clone,Copy)]
+pub enum StockFont {
+    OemFixed = 10,
+    AnsiFixed = 11,
+    AnsiVar = 12,
+    System = 13,
+    DeviceDefault = 14,
+    SystemFixed = 16,
+    DefaultGui = 17,
+}
+
+/// Fonts configured in the "Window Color and Appearance" settings.
+#[derive(Clone,Copy)]
+pub enum SystemFont {
+    Caption,
+    SmallCaption,
+    Menu,
+    Status,
+    Message,
+}
+
+/// Converts a point size to a `FontAttr::height` for a device with `dpi` pixels per inch.
+/// The result is negative so that it selects by character height rather than cell height.
+pub fn height_from_points(points: f32, dpi: u32) -> isize {
+    -((points * dpi as f32 / 72.0).round() as isize)
+}
+
+/// Inverse of `height_from_points`.
+pub fn points_from_height(height: isize, dpi: u32) -> f32 {
+    if dpi == 0 {
+        return 0.0;
+    }
+    height.abs() as f32 * 72.0 / dpi as f32
+}
+
+#[derive(Clone)]
 pub struct FontAttr {
     pub height: isize,
     pub width: isize,
     pub escapement: isize,
     pub orientation: isize,
-    pub weight: isize,
+    pub weight: Weight,
     pub italic: bool,
     pub underline: bool,
     pub strike_out: bool,
@@ -115,6 +315,76 @@ pub struct FontAttr {
     pub face: Option<String>,
 }
 
+impl FontAttr {
+    pub fn from_logfont(lf: &LOGFONTW) -> FontAttr {
+        let face = from_c_u16_buf(&lf.lfFaceName);
+        FontAttr {
+            height: lf.lfHeight as isize,
+            width: lf.lfWidth as isize,
+            escapement: lf.lfEscapement as isize,
+            orientation: lf.lfOrientation as isize,
+            weight: Weight::from_raw(lf.lfWeight as isize),
+            italic: lf.lfItalic != 0,
+            underline: lf.lfUnderline != 0,
+            strike_out: lf.lfStrikeOut != 0,
+            char_set: CharSet::from_raw(lf.lfCharSet),
+            output_precision: OutputPrecision::from_raw(lf.lfOutPrecision),
+            clip_precision: ClipPrecision::from_raw(lf.lfClipPrecision),
+            quality: Quality::from_raw(lf.lfQuality),
+            pitch: Pitch::from_raw(lf.lfPitchAndFamily & 0x0F),
+            family: Family::from_raw(lf.lfPitchAndFamily & 0xF0),
+            face: if face.is_empty() { None } else { Some(face) },
+        }
+    }
+
+    /// Reads one of the fonts the user configured for window captions, menus and so on.
+    pub fn system(which: SystemFont) -> Option<FontAttr> {
+        let mut ncm: NONCLIENTMETRICSW = unsafe { mem::zeroed() };
+        let size = mem::size_of::<NONCLIENTMETRICSW>() as UINT;
+        ncm.cbSize = size;
+        let res = unsafe {
+            user32::SystemParametersInfoW(
+                SPI_GETNONCLIENTMETRICS, size, &mut ncm as *mut NONCLIENTMETRICSW as LPVOID, 0
+            )
+        };
+        if res == 0 {
+            return None;
+        }
+        let lf = match which {
+            SystemFont::Caption => &ncm.lfCaptionFont,
+            SystemFont::SmallCaption => &ncm.lfSmCaptionFont,
+            SystemFont::Menu => &ncm.lfMenuFont,
+            SystemFont::Status => &ncm.lfStatusFont,
+            SystemFont::Message => &ncm.lfMessageFont,
+        };
+        Some(FontAttr::from_logfont(lf))
+    }
+
+    /// Builds a `LOGFONTW`. Face names longer than `LF_FACESIZE - 1` are truncated.
+    pub fn to_logfont(&self) -> LOGFONTW {
+        let mut face = [0 as WCHAR; LF_FACESIZE];
+        if let Some(ref f) = self.face {
+            to_c_u16_buf(&mut face, f);
+        }
+        LOGFONTW {
+            lfHeight: self.height as c_int,
+            lfWidth: self.width as c_int,
+            lfEscapement: self.escapement as c_int,
+            lfOrientation: self.orientation as c_int,
+            lfWeight: self.weight.to_raw() as c_int,
+            lfItalic: self.italic as u8,
+            lfUnderline: self.underline as u8,
+            lfStrikeOut: self.strike_out as u8,
+            lfCharSet: self.char_set as u8,
+            lfOutPrecision: self.output_precision as u8,
+            lfClipPrecision: self.clip_precision as u8,
+            lfQuality: self.quality as u8,
+            lfPitchAndFamily: (self.pitch as u8) | (self.family as u8),
+            lfFaceName: face,
+        }
+    }
+}
+
 impl Default for FontAttr {
     fn default() -> FontAttr {
         FontAttr {
@@ -122,7 +392,7 @@ impl Default for FontAttr {
             width: 0,
             escapement: 0,
             orientation: 0,
-            weight: 400, // FW_NORMAL. TODO use FW_DONTCARE (0)?
+            weight: Weight::Normal,
             italic: false,
             underline: false,
             strike_out: false,
@@ -152,14 +422,14 @@ impl Clone for Font {
 
 impl Font {
     pub fn new(attr: &FontAttr) -> Option<Font> {
-        let face = attr.face.to_c_u16();
+        let face = attr.face.as_ref().map(|f| WideCString::from_str_truncate(f));
         let hfont = unsafe {
             gdi32::CreateFontW(
                 attr.height as c_int,
                 attr.width as c_int,
                 attr.escapement as c_int,
                 attr.orientation as c_int,
-                attr.weight as c_int,
+                attr.weight.to_raw() as c_int,
                 attr.italic as DWORD,
                 attr.underline as DWORD,
                 attr.strike_out as DWORD,
@@ -168,7 +438,7 @@ impl Font {
                 attr.clip_precision as DWORD,
                 attr.quality as DWORD,
                 (attr.pitch as DWORD) | (attr.family as DWORD),
-                if face.len()==0 { ptr::null_mut() } else { face.as_ptr() },
+                face.as_ref().map_or(ptr::null(), |f| f.as_ptr()),
             )
         };
         if hfont == ptr::null_mut() {
@@ -178,4 +448,311 @@ impl Font {
             Some(Font { font: hfont })
         }
     }
+    /// Creates a font of the given point size for a device with `dpi` pixels per inch.
+    pub fn from_points(face: &str, points: f32, dpi: u32) -> Option<Font> {
+        FontAttrBuilder::new().face(face).points(points, dpi).create()
+    }
+
+    /// Returns one of the predefined fonts. Stock fonts must not be deleted.
+    pub fn stock(which: StockFont) -> Option<Font> {
+        let hfont = unsafe { gdi32::GetStockObject(which as c_int) };
+        if hfont.is_null() {
+            None
+        } else {
+            Some(Font { font: hfont as HFONT })
+        }
+    }
+
+    /// Creates a copy of one of the user's system fonts, e.g. `SystemFont::Message`
+    /// for dialog text.
+    pub fn system(which: SystemFont) -> Option<Font> {
+        FontAttr::system(which).and_then(|attr| Font::new(&attr))
+    }
+
+    /// Reads back the attributes the font was created with.
+    pub fn attributes(&self) -> Option<FontAttr> {
+        let mut lf: LOGFONTW = unsafe { mem::zeroed() };
+        let size = mem::size_of::<LOGFONTW>() as c_int;
+        let res = unsafe {
+            gdi32::GetObjectW(self.font as HGDIOBJ, size, &mut lf as *mut LOGFONTW as LPVOID)
+        };
+        if res == 0 {
+            None
+        } else {
+            Some(FontAttr::from_logfont(&lf))
+        }
+    }
+}
+
+/// Builds a `FontAttr` step by step:
+///
+/// ```ignore
+/// let font = FontAttrBuilder::new().face("Segoe UI").points(9.0, dpi).weight(Weight::Bold).create();
+/// ```
+pub struct FontAttrBuilder {
+    attr: FontAttr,
+}
+
+impl FontAttrBuilder {
+    pub fn new() -> FontAttrBuilder {
+        FontAttrBuilder { attr: Default::default() }
+    }
+
+    /// Starts from an existing set of attributes, e.g. `FontAttr::system(..)`.
+    pub fn from_attr(attr: FontAttr) -> FontAttrBuilder {
+        FontAttrBuilder { attr: attr }
+    }
+
+    pub fn face(mut self, face: &str) -> FontAttrBuilder {
+        self.attr.face = Some(face.to_string());
+        self
+    }
+
+    /// Sets the raw logical-unit height; see `points` for the usual case.
+    pub fn height(mut self, height: isize) -> FontAttrBuilder {
+        self.attr.height = height;
+        self
+    }
+
+    pub fn points(mut self, points: f32, dpi: u32) -> FontAttrBuilder {
+        self.attr.height = height_from_points(points, dpi);
+        self
+    }
+
+    pub fn width(mut self, width: isize) -> FontAttrBuilder {
+        self.attr.width = width;
+        self
+    }
+
+    /// Sets both escapement and orientation, in tenths of a degree.
+    pub fn angle(mut self, angle: isize) -> FontAttrBuilder {
+        self.attr.escapement = angle;
+        self.attr.orientation = angle;
+        self
+    }
+
+    pub fn weight(mut self, weight: Weight) -> FontAttrBuilder {
+        self.attr.weight = weight;
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> FontAttrBuilder {
+        self.attr.italic = italic;
+        self
+    }
+
+    pub fn underline(mut self, underline: bool) -> FontAttrBuilder {
+        self.attr.underline = underline;
+        self
+    }
+
+    pub fn strike_out(mut self, strike_out: bool) -> FontAttrBuilder {
+        self.attr.strike_out = strike_out;
+        self
+    }
+
+    pub fn char_set(mut self, char_set: CharSet) -> FontAttrBuilder {
+        self.attr.char_set = char_set;
+        self
+    }
+
+    pub fn quality(mut self, quality: Quality) -> FontAttrBuilder {
+        self.attr.quality = quality;
+        self
+    }
+
+    pub fn pitch(mut self, pitch: Pitch) -> FontAttrBuilder {
+        self.attr.pitch = pitch;
+        self
+    }
+
+    pub fn family(mut self, family: Family) -> FontAttrBuilder {
+        self.attr.family = family;
+        self
+    }
+
+    pub fn attr(self) -> FontAttr {
+        self.attr
+    }
+
+    pub fn create(self) -> Option<Font> {
+        Font::new(&self.attr)
+    }
+}
+
+// Values of the `FontType` parameter of `EnumFontFamExProc`.
+const RASTER_FONTTYPE: DWORD = 0x0001;
+const DEVICE_FONTTYPE: DWORD = 0x0002;
+const TRUETYPE_FONTTYPE: DWORD = 0x0004;
+
+/// A font face reported by `enum_font_families`.
+pub struct FontFamily {
+    pub attr: FontAttr,
+    pub full_name: String,
+    pub style: String,
+    pub script: String,
+    pub raster: bool,
+    pub device: bool,
+    pub true_type: bool,
+}
+
+unsafe extern "system" fn enum_font_families_proc(
+    lf: *const LOGFONTW, _tm: *const VOID, font_type: DWORD, lparam: LPARAM
+) -> c_int {
+    let families = &mut *(lparam as *mut Vec<FontFamily>);
+    // EnumFontFamiliesExW always passes an ENUMLOGFONTEXW.
+    let elf = &*(lf as *const ENUMLOGFONTEXW);
+    families.push(FontFamily {
+        attr: FontAttr::from_logfont(&elf.elfLogFont),
+        full_name: from_c_u16_buf(&elf.elfFullName),
+        style: from_c_u16_buf(&elf.elfStyle),
+        script: from_c_u16_buf(&elf.elfScript),
+        raster: font_type & RASTER_FONTTYPE != 0,
+        device: font_type & DEVICE_FONTTYPE != 0,
+        true_type: font_type & TRUETYPE_FONTTYPE != 0,
+    });
+    1
+}
+
+/// Enumerates the fonts available on `dc` via `EnumFontFamiliesExW`.
+///
+/// With `face` set to `None` one entry is returned per face name and character set
+/// (narrow `char_set` to avoid duplicates); otherwise every style of that face is listed.
+pub fn enum_font_families(dc: &Dc, face: Option<&str>, char_set: CharSet) -> Vec<FontFamily> {
+    let attr = FontAttr {
+        char_set: char_set,
+        face: face.map(|f| f.to_string()),
+        ..Default::default()
+    };
+    let mut lf = attr.to_logfont();
+    let mut families = Vec::new();
+    unsafe {
+        gdi32::EnumFontFamiliesExW(
+            dc.raw, &mut lf, Some(enum_font_families_proc),
+            &mut families as *mut Vec<FontFamily> as LPARAM, 0
+        );
+    }
+    families
+}
+
+/// Typed counterpart of `TEXTMETRICW`, as returned by `Dc::text_metrics`.
+#[derive(Clone,Copy)]
+pub struct TextMetrics {
+    pub height: isize,
+    pub ascent: isize,
+    pub descent: isize,
+    pub internal_leading: isize,
+    pub external_leading: isize,
+    pub ave_char_width: isize,
+    pub max_char_width: isize,
+    pub weight: isize,
+    pub overhang: isize,
+    pub digitized_aspect_x: isize,
+    pub digitized_aspect_y: isize,
+    pub first_char: u16,
+    pub last_char: u16,
+    pub default_char: u16,
+    pub break_char: u16,
+    pub italic: bool,
+    pub underlined: bool,
+    pub struck_out: bool,
+    pub fixed_pitch: bool,
+    pub true_type: bool,
+    pub family: Family,
+    pub char_set: CharSet,
+}
+
+impl TextMetrics {
+    pub fn from_raw(tm: &TEXTMETRICW) -> TextMetrics {
+        TextMetrics {
+            height: tm.tmHeight as isize,
+            ascent: tm.tmAscent as isize,
+            descent: tm.tmDescent as isize,
+            internal_leading: tm.tmInternalLeading as isize,
+            external_leading: tm.tmExternalLeading as isize,
+            ave_char_width: tm.tmAveCharWidth as isize,
+            max_char_width: tm.tmMaxCharWidth as isize,
+            weight: tm.tmWeight as isize,
+            overhang: tm.tmOverhang as isize,
+            digitized_aspect_x: tm.tmDigitizedAspectX as isize,
+            digitized_aspect_y: tm.tmDigitizedAspectY as isize,
+            first_char: tm.tmFirstChar,
+            last_char: tm.tmLastChar,
+            default_char: tm.tmDefaultChar,
+            break_char: tm.tmBreakChar,
+            italic: tm.tmItalic != 0,
+            underlined: tm.tmUnderlined != 0,
+            struck_out: tm.tmStruckOut != 0,
+            // TMPF_FIXED_PITCH is set for *variable* pitch fonts.
+            fixed_pitch: tm.tmPitchAndFamily & 0x01 == 0,
+            true_type: tm.tmPitchAndFamily & 0x04 != 0, // TMPF_TRUETYPE
+            family: Family::from_raw(tm.tmPitchAndFamily & 0xF0),
+            char_set: CharSet::from_raw(tm.tmCharSet),
+        }
+    }
+}
+
+/// The TrueType-specific part of `OUTLINETEXTMETRICW`, as returned by
+/// `Dc::outline_text_metrics`.
+pub struct OutlineTextMetrics {
+    pub text_metrics: TextMetrics,
+    pub em_square: usize,
+    pub ascent: isize,
+    pub descent: isize,
+    pub line_gap: usize,
+    pub cap_em_height: usize,
+    pub x_height: usize,
+    /// In tenths of a degree, counterclockwise from vertical.
+    pub italic_angle: isize,
+    pub font_box: RECT,
+    pub strikeout_size: usize,
+    pub strikeout_position: isize,
+    pub underscore_size: isize,
+    pub underscore_position: isize,
+    pub family_name: String,
+    pub face_name: String,
+    pub style_name: String,
+    pub full_name: String,
+}
+
+/// Width of a character as returned by `Dc::char_abc_widths`:
+/// `a` is the spacing before the glyph, `b` the glyph itself and `c` the spacing after it.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct AbcWidth {
+    pub a: isize,
+    pub b: usize,
+    pub c: isize,
+}
+
+impl AbcWidth {
+    pub fn advance(&self) -> isize {
+        self.a + self.b as isize + self.c
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Weight, height_from_points, points_from_height};
+
+    #[test]
+    fn test_height_from_points() {
+        assert_eq!(height_from_points(9.0, 96), -12);
+        assert_eq!(height_from_points(9.0, 144), -18);
+        assert_eq!(height_from_points(10.5, 96), -14);
+        assert_eq!(points_from_height(-12, 96), 9.0);
+        assert_eq!(points_from_height(-18, 144), 9.0);
+    }
+
+    #[test]
+    fn test_weight_from_raw() {
+        assert_eq!(Weight::from_raw(0), Weight::DontCare);
+        assert_eq!(Weight::from_raw(400), Weight::Normal);
+        assert_eq!(Weight::from_raw(900), Weight::Black);
+        // Weights between the named ones are kept.
+        assert_eq!(Weight::from_raw(350), Weight::Other(350));
+        assert_eq!(Weight::from_raw(1000), Weight::Other(1000));
+        for &v in &[0, 100, 350, 400, 649, 700, 950, 1000] {
+            assert_eq!(Weight::from_raw(v).to_raw(), v);
+        }
+    }
 }
@@ -8,18 +8,139 @@
 // except according to those terms.
 
 use std::ptr;
+use std;
+use std::mem;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
 
 use gdi32;
 use user32;
 use winapi::{
-    BOOL, BYTE, COLORREF, DWORD, HANDLE, HBITMAP, HBRUSH, HDC, HFONT, HGDIOBJ, HWND, LONG,
-    PAINTSTRUCT, RECT, c_int,
+    ABC, BOOL, BYTE, COLORREF, DWORD, HANDLE, HBITMAP, HBRUSH, HDC, HFONT, HGDIOBJ, HWND, LONG,
+    OUTLINETEXTMETRICW, PAINTSTRUCT, RECT, SIZE, TEXTMETRICW, UINT, c_int,
+    DRAWTEXTPARAMS, DT_BOTTOM, DT_CALCRECT, DT_CENTER, DT_END_ELLIPSIS, DT_EXPANDTABS,
+    DT_LEFT, DT_NOCLIP, DT_NOPREFIX, DT_PATH_ELLIPSIS, DT_RIGHT, DT_SINGLELINE, DT_TABSTOP,
+    DT_TOP, DT_VCENTER, DT_WORDBREAK, DT_WORD_ELLIPSIS, OPAQUE, TA_BASELINE, TA_BOTTOM,
+    TA_CENTER, TA_LEFT, TA_NOUPDATECP, TA_RIGHT, TA_TOP, TA_UPDATECP, TRANSPARENT,
+    LOGPIXELSX, LOGPIXELSY,
 };
 
-use font::Font;
-use window::WindowImpl;
+use wchar::from_c_u16_buf;
+use font::{AbcWidth, Font, OutlineTextMetrics, TextMetrics};
+use window::{Window, WindowImpl};
+
+const GDI_ERROR: UINT = 0xFFFFFFFF;
+
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum HAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum VAlign {
+    Top,
+    Center,
+    Bottom,
+}
+
+/// Vertical reference point used by `text_out`, see `Dc::set_text_align`.
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum VAnchor {
+    Top,
+    Baseline,
+    Bottom,
+}
+
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum Ellipsis {
+    None,
+    /// Replace the end of the text with "...".
+    End,
+    /// Replace the middle of the text, keeping as much as possible after the last backslash.
+    Path,
+    /// Truncate any word that does not fit with "...".
+    Word,
+}
+
+#[derive(Clone,Copy,PartialEq,Eq)]
+pub enum BackgroundMode {
+    Transparent = 1,
+    Opaque = 2,
+}
+
+/// Formatting options for `Dc::draw_text`.
+#[derive(Clone,Copy)]
+pub struct TextFormat {
+    pub h_align: HAlign,
+    /// `Center` and `Bottom` only apply to `single_line` text.
+    pub v_align: VAlign,
+    pub single_line: bool,
+    pub word_break: bool,
+    pub ellipsis: Ellipsis,
+    pub expand_tabs: bool,
+    /// Tab stop interval in average character widths; 0 means the default of 8.
+    pub tab_length: usize,
+    pub left_margin: isize,
+    pub right_margin: isize,
+    /// Draw `&&` as `&` and do not underline mnemonics.
+    pub no_prefix: bool,
+    pub no_clip: bool,
+    /// Size the rectangle to the text instead of drawing; see `Dc::draw_text`.
+    pub calc_rect: bool,
+}
+
+impl Default for TextFormat {
+    fn default() -> TextFormat {
+        TextFormat {
+            h_align: HAlign::Left,
+            v_align: VAlign::Top,
+            single_line: false,
+            word_break: false,
+            ellipsis: Ellipsis::None,
+            expand_tabs: false,
+            tab_length: 0,
+            left_margin: 0,
+            right_margin: 0,
+            no_prefix: false,
+            no_clip: false,
+            calc_rect: false,
+        }
+    }
+}
+
+impl TextFormat {
+    /// `DT_*` flags for `DrawTextExW`.
+    pub fn to_raw(&self) -> UINT {
+        let mut flags = match self.h_align {
+            HAlign::Left => DT_LEFT,
+            HAlign::Center => DT_CENTER,
+            HAlign::Right => DT_RIGHT,
+        };
+        flags |= match self.v_align {
+            VAlign::Top => DT_TOP,
+            VAlign::Center => DT_VCENTER,
+            VAlign::Bottom => DT_BOTTOM,
+        };
+        flags |= match self.ellipsis {
+            Ellipsis::None => 0,
+            Ellipsis::End => DT_END_ELLIPSIS,
+            Ellipsis::Path => DT_PATH_ELLIPSIS,
+            Ellipsis::Word => DT_WORD_ELLIPSIS,
+        };
+        if self.single_line { flags |= DT_SINGLELINE; }
+        if self.word_break { flags |= DT_WORDBREAK; }
+        if self.expand_tabs {
+            flags |= DT_EXPANDTABS;
+            if self.tab_length != 0 { flags |= DT_TABSTOP; }
+        }
+        if self.no_prefix { flags |= DT_NOPREFIX; }
+        if self.no_clip { flags |= DT_NOCLIP; }
+        if self.calc_rect { flags |= DT_CALCRECT; }
+        flags
+    }
+}
 
 #[derive(Clone,Copy)]
 pub struct Dc {
@@ -32,19 +153,103 @@ impl Dc {
     }
 
     pub fn text_out(&self, x: isize, y: isize, s: &str) -> bool {
-        let mut s16 : Vec<u16> = OsStr::new( s )
-			.encode_wide()
-			.chain(Some(0).into_iter())
-			.collect::<Vec<_>>();
-        let len = s16.len();
-
-        s16.push(0u16);
+        let s16: Vec<u16> = OsStr::new(s).encode_wide().collect();
         let ret = unsafe {
-            gdi32::TextOutW(self.raw, x as c_int, y as c_int, s16.as_mut_ptr(), len as i32)
+            gdi32::TextOutW(self.raw, x as c_int, y as c_int, s16.as_ptr(), s16.len() as c_int)
         };
         ret != 0
     }
 
+    /// Draws `s` formatted within `rect` and returns the rectangle as `DrawTextExW`
+    /// leaves it. If `format.calc_rect` is set, nothing is drawn and the rectangle
+    /// is sized to fit the text, keeping `rect`'s top-left corner; otherwise it is
+    /// `rect`. `measure_text` also moves it to where the text would be drawn.
+    pub fn draw_text(&self, s: &str, rect: RECT, format: &TextFormat) -> Option<RECT> {
+        let mut rect = rect;
+        if self.draw_text_ex(s, &mut rect, format.to_raw(), format) == 0 {
+            return None;
+        }
+        Some(rect)
+    }
+
+    /// Computes the rectangle `s` would occupy when drawn with `format` inside `rect`.
+    pub fn measure_text(&self, s: &str, rect: RECT, format: &TextFormat) -> Option<RECT> {
+        let mut calc = rect;
+        if self.draw_text_ex(s, &mut calc, format.to_raw() | DT_CALCRECT, format) == 0 {
+            return None;
+        }
+
+        // DT_CALCRECT keeps the top-left corner; move the result to where the text is drawn.
+        let (w, h) = (calc.right - calc.left, calc.bottom - calc.top);
+        let dx = match format.h_align {
+            HAlign::Left => 0,
+            HAlign::Center => (rect.right - rect.left - w) / 2,
+            HAlign::Right => rect.right - rect.left - w,
+        };
+        let dy = match (format.single_line, format.v_align) {
+            (false, _) | (_, VAlign::Top) => 0,
+            (true, VAlign::Center) => (rect.bottom - rect.top - h) / 2,
+            (true, VAlign::Bottom) => rect.bottom - rect.top - h,
+        };
+        calc.left += dx;
+        calc.right += dx;
+        calc.top += dy;
+        calc.bottom += dy;
+        Some(calc)
+    }
+
+    fn draw_text_ex(&self, s: &str, rect: &mut RECT, flags: UINT, format: &TextFormat) -> c_int {
+        let s16: Vec<u16> = OsStr::new(s).encode_wide().collect();
+        let mut params = DRAWTEXTPARAMS {
+            cbSize: mem::size_of::<DRAWTEXTPARAMS>() as UINT,
+            iTabLength: format.tab_length as c_int,
+            iLeftMargin: format.left_margin as c_int,
+            iRightMargin: format.right_margin as c_int,
+            uiLengthDrawn: 0,
+        };
+        unsafe {
+            user32::DrawTextExW(self.raw, s16.as_ptr(), s16.len() as c_int, rect, flags,
+                                &mut params)
+        }
+    }
+
+    /// Sets how `text_out` positions text relative to its reference point.
+    pub fn set_text_align(&self, h: HAlign, v: VAnchor, update_cp: bool) -> bool {
+        let h = match h {
+            HAlign::Left => TA_LEFT,
+            HAlign::Center => TA_CENTER,
+            HAlign::Right => TA_RIGHT,
+        };
+        let v = match v {
+            VAnchor::Top => TA_TOP,
+            VAnchor::Baseline => TA_BASELINE,
+            VAnchor::Bottom => TA_BOTTOM,
+        };
+        let cp = if update_cp { TA_UPDATECP } else { TA_NOUPDATECP };
+        let res = unsafe { gdi32::SetTextAlign(self.raw, h | v | cp) };
+        res != GDI_ERROR
+    }
+
+    /// Returns the previous background mode.
+    pub fn set_background_mode(&self, mode: BackgroundMode) -> Option<BackgroundMode> {
+        match unsafe { gdi32::SetBkMode(self.raw, mode as c_int) } {
+            TRANSPARENT => Some(BackgroundMode::Transparent),
+            OPAQUE => Some(BackgroundMode::Opaque),
+            _ => None,
+        }
+    }
+
+    /// Sets the extra space, in logical units, added between characters.
+    /// Returns the previous value.
+    pub fn set_char_spacing(&self, extra: isize) -> Option<isize> {
+        let res = unsafe { gdi32::SetTextCharacterExtra(self.raw, extra as c_int) };
+        if res == 0x80000000u32 as c_int {
+            None
+        } else {
+            Some(res as isize)
+        }
+    }
+
     pub fn select_object(&self, handle: HANDLE) -> HANDLE {
         unsafe { gdi32::SelectObject(self.raw, handle as HGDIOBJ) }
     }
@@ -58,6 +263,114 @@ impl Dc {
         }
     }
 
+    /// Pixels per logical inch, horizontally and vertically. Use with
+    /// `Font::from_points` to size fonts for this device.
+    pub fn logical_dpi(&self) -> (u32, u32) {
+        unsafe {
+            (gdi32::GetDeviceCaps(self.raw, LOGPIXELSX) as u32,
+             gdi32::GetDeviceCaps(self.raw, LOGPIXELSY) as u32)
+        }
+    }
+
+    /// Metrics of the currently selected font.
+    pub fn text_metrics(&self) -> Option<TextMetrics> {
+        let mut tm: TEXTMETRICW = unsafe { mem::zeroed() };
+        let res = unsafe { gdi32::GetTextMetricsW(self.raw, &mut tm) };
+        if res == 0 {
+            None
+        } else {
+            Some(TextMetrics::from_raw(&tm))
+        }
+    }
+
+    /// Outline metrics of the currently selected font.
+    /// Returns `None` if the font is not a TrueType/OpenType font.
+    pub fn outline_text_metrics(&self) -> Option<OutlineTextMetrics> {
+        let size = unsafe { gdi32::GetOutlineTextMetricsW(self.raw, 0, ptr::null_mut()) };
+        if (size as usize) < mem::size_of::<OUTLINETEXTMETRICW>() {
+            return None;
+        }
+
+        // u64 keeps the buffer suitably aligned for OUTLINETEXTMETRICW.
+        let mut buf = vec![0u64; (size as usize + 7) / 8];
+        let res = unsafe {
+            gdi32::GetOutlineTextMetricsW(self.raw, size, buf.as_mut_ptr() as *mut OUTLINETEXTMETRICW)
+        };
+        if res == 0 {
+            return None;
+        }
+
+        let otm = unsafe { &*(buf.as_ptr() as *const OUTLINETEXTMETRICW) };
+        let words = unsafe {
+            std::slice::from_raw_parts(buf.as_ptr() as *const u16, size as usize / 2)
+        };
+        // The otmp*Name members are byte offsets from the start of the structure.
+        let name = |offset: *mut i8| {
+            let start = offset as usize / 2;
+            if start == 0 || start >= words.len() {
+                String::new()
+            } else {
+                from_c_u16_buf(&words[start..])
+            }
+        };
+
+        Some(OutlineTextMetrics {
+            text_metrics: TextMetrics::from_raw(&otm.otmTextMetrics),
+            em_square: otm.otmEMSquare as usize,
+            ascent: otm.otmAscent as isize,
+            descent: otm.otmDescent as isize,
+            line_gap: otm.otmLineGap as usize,
+            cap_em_height: otm.otmsCapEmHeight as usize,
+            x_height: otm.otmsXHeight as usize,
+            italic_angle: otm.otmItalicAngle as isize,
+            font_box: otm.otmrcFontBox,
+            strikeout_size: otm.otmsStrikeoutSize as usize,
+            strikeout_position: otm.otmsStrikeoutPosition as isize,
+            underscore_size: otm.otmsUnderscoreSize as isize,
+            underscore_position: otm.otmsUnderscorePosition as isize,
+            family_name: name(otm.otmpFamilyName),
+            face_name: name(otm.otmpFaceName),
+            style_name: name(otm.otmpStyleName),
+            full_name: name(otm.otmpFullName),
+        })
+    }
+
+    /// Size of `s` drawn in a single line with the currently selected font.
+    pub fn text_extent(&self, s: &str) -> Option<(isize, isize)> {
+        let s16: Vec<u16> = OsStr::new(s).encode_wide().collect();
+        let mut size = SIZE { cx: 0, cy: 0 };
+        let res = unsafe {
+            gdi32::GetTextExtentPoint32W(self.raw, s16.as_ptr(), s16.len() as c_int, &mut size)
+        };
+        if res == 0 {
+            None
+        } else {
+            Some((size.cx as isize, size.cy as isize))
+        }
+    }
+
+    /// ABC widths of the characters `first..=last` in the currently selected TrueType font.
+    /// Only characters in the Basic Multilingual Plane are supported.
+    pub fn char_abc_widths(&self, first: char, last: char) -> Option<Vec<AbcWidth>> {
+        let (first, last) = (first as u32, last as u32);
+        if first > last || last > 0xFFFF {
+            return None;
+        }
+
+        let mut abcs = vec![ABC { abcA: 0, abcB: 0, abcC: 0 }; (last - first + 1) as usize];
+        let res = unsafe {
+            gdi32::GetCharABCWidthsW(self.raw, first as UINT, last as UINT, abcs.as_mut_ptr())
+        };
+        if res == 0 {
+            return None;
+        }
+        Some(abcs.iter().map(|abc| AbcWidth {
+            a: abc.abcA as isize,
+            b: abc.abcB as usize,
+            c: abc.abcC as isize,
+        }).collect())
+    }
+
     pub fn set_text_color(&self, color: COLORREF) -> COLORREF {
         unsafe { gdi32::SetTextColor(self.raw, color) }
     }
@@ -150,6 +463,33 @@ impl Drop for PaintDc {
     }
 }
 
+/// The client area DC of a window, from `GetDC`.
+pub struct WindowDc {
+    pub dc: Dc,
+    pub wnd: HWND,
+}
+
+impl WindowDc {
+    pub fn new(wnd: &Window) -> Option<WindowDc> {
+        let dc = unsafe { user32::GetDC(wnd.wnd) };
+        if dc.is_null() {
+            return None;
+        }
+        Some(WindowDc { dc: Dc { raw: dc }, wnd: wnd.wnd })
+    }
+
+    /// The DC of the entire screen.
+    pub fn screen() -> Option<WindowDc> {
+        WindowDc::new(&Window::null())
+    }
+}
+
+impl Drop for WindowDc {
+    fn drop(&mut self) {
+        unsafe { user32::ReleaseDC(self.wnd, self.dc.raw) };
+    }
+}
+
 pub struct MemoryDc {
     pub dc: Dc,
 }
@@ -0,0 +1,752 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Mouse and keyboard input state.
+
+use std::cell::Cell;
+
+#[cfg(windows)] use std::mem;
+
+#[cfg(windows)] use user32;
+#[cfg(windows)] use winapi::{DWORD, HOVER_DEFAULT, TME_CANCEL, TME_HOVER, TME_LEAVE, TRACKMOUSEEVENT, WPARAM};
+
+#[cfg(windows)] use window::Window;
+
+macro_rules! virtual_keys {
+    ($($(#[$attr:meta])* $name:ident = $code:tt,)*) => (
+        /// A virtual-key code (`VK_*`).
+        ///
+        /// Codes without a variant are kept in `Other`; `from_raw` never returns
+        /// `Other` for a code that has a variant.
+        #[derive(Clone,Copy,PartialEq,Eq,Hash,Debug)]
+        pub enum VirtualKey {
+            $($(#[$attr])* $name,)*
+            Other(u8),
+        }
+
+        impl VirtualKey {
+            pub fn from_raw(code: u8) -> VirtualKey {
+                match code {
+                    $($code => VirtualKey::$name,)*
+                    c => VirtualKey::Other(c),
+                }
+            }
+
+            pub fn to_raw(&self) -> u8 {
+                match *self {
+                    $(VirtualKey::$name => $code,)*
+                    VirtualKey::Other(c) => c,
+                }
+            }
+
+            /// The variant name, e.g. `"PageUp"`; `None` for `Other`.
+            pub fn name(&self) -> Option<&'static str> {
+                match *self {
+                    $(VirtualKey::$name => Some(stringify!($name)),)*
+                    VirtualKey::Other(_) => None,
+                }
+            }
+
+            /// Looks up a key by variant name, ignoring case, or by one of the
+            /// usual abbreviations such as `"Del"`, `"PgUp"`, `"Enter"` and `"5"`.
+            pub fn from_name(name: &str) -> Option<VirtualKey> {
+                $(if name.eq_ignore_ascii_case(stringify!($name)) {
+                    return Some(VirtualKey::$name);
+                })*
+                VirtualKey::from_alias(name)
+            }
+        }
+    )
+}
+
+virtual_keys! {
+    LeftButton = 0x01,
+    RightButton = 0x02,
+    /// Ctrl+Break.
+    Cancel = 0x03,
+    MiddleButton = 0x04,
+    XButton1 = 0x05,
+    XButton2 = 0x06,
+    Back = 0x08,
+    Tab = 0x09,
+    Clear = 0x0C,
+    /// Enter (`VK_RETURN`).
+    Return = 0x0D,
+    Shift = 0x10,
+    Control = 0x11,
+    /// Alt (`VK_MENU`).
+    Alt = 0x12,
+    Pause = 0x13,
+    /// `VK_CAPITAL`.
+    CapsLock = 0x14,
+    Escape = 0x1B,
+    Space = 0x20,
+    /// `VK_PRIOR`.
+    PageUp = 0x21,
+    /// `VK_NEXT`.
+    PageDown = 0x22,
+    End = 0x23,
+    Home = 0x24,
+    Left = 0x25,
+    Up = 0x26,
+    Right = 0x27,
+    Down = 0x28,
+    Select = 0x29,
+    Print = 0x2A,
+    Execute = 0x2B,
+    /// `VK_SNAPSHOT`.
+    PrintScreen = 0x2C,
+    Insert = 0x2D,
+    Delete = 0x2E,
+    Help = 0x2F,
+    Key0 = 0x30,
+    Key1 = 0x31,
+    Key2 = 0x32,
+    Key3 = 0x33,
+    Key4 = 0x34,
+    Key5 = 0x35,
+    Key6 = 0x36,
+    Key7 = 0x37,
+    Key8 = 0x38,
+    Key9 = 0x39,
+    A = 0x41,
+    B = 0x42,
+    C = 0x43,
+    D = 0x44,
+    E = 0x45,
+    F = 0x46,
+    G = 0x47,
+    H = 0x48,
+    I = 0x49,
+    J = 0x4A,
+    K = 0x4B,
+    L = 0x4C,
+    M = 0x4D,
+    N = 0x4E,
+    O = 0x4F,
+    P = 0x50,
+    Q = 0x51,
+    R = 0x52,
+    S = 0x53,
+    T = 0x54,
+    U = 0x55,
+    V = 0x56,
+    W = 0x57,
+    X = 0x58,
+    Y = 0x59,
+    Z = 0x5A,
+    LeftWin = 0x5B,
+    RightWin = 0x5C,
+    /// The context menu key.
+    Apps = 0x5D,
+    Sleep = 0x5F,
+    Numpad0 = 0x60,
+    Numpad1 = 0x61,
+    Numpad2 = 0x62,
+    Numpad3 = 0x63,
+    Numpad4 = 0x64,
+    Numpad5 = 0x65,
+    Numpad6 = 0x66,
+    Numpad7 = 0x67,
+    Numpad8 = 0x68,
+    Numpad9 = 0x69,
+    Multiply = 0x6A,
+    Add = 0x6B,
+    Separator = 0x6C,
+    Subtract = 0x6D,
+    Decimal = 0x6E,
+    Divide = 0x6F,
+    F1 = 0x70,
+    F2 = 0x71,
+    F3 = 0x72,
+    F4 = 0x73,
+    F5 = 0x74,
+    F6 = 0x75,
+    F7 = 0x76,
+    F8 = 0x77,
+    F9 = 0x78,
+    F10 = 0x79,
+    F11 = 0x7A,
+    F12 = 0x7B,
+    F13 = 0x7C,
+    F14 = 0x7D,
+    F15 = 0x7E,
+    F16 = 0x7F,
+    F17 = 0x80,
+    F18 = 0x81,
+    F19 = 0x82,
+    F20 = 0x83,
+    F21 = 0x84,
+    F22 = 0x85,
+    F23 = 0x86,
+    F24 = 0x87,
+    NumLock = 0x90,
+    ScrollLock = 0x91,
+    LeftShift = 0xA0,
+    RightShift = 0xA1,
+    LeftControl = 0xA2,
+    RightControl = 0xA3,
+    LeftAlt = 0xA4,
+    RightAlt = 0xA5,
+    BrowserBack = 0xA6,
+    BrowserForward = 0xA7,
+    BrowserRefresh = 0xA8,
+    BrowserStop = 0xA9,
+    BrowserSearch = 0xAA,
+    BrowserFavorites = 0xAB,
+    BrowserHome = 0xAC,
+    VolumeMute = 0xAD,
+    VolumeDown = 0xAE,
+    VolumeUp = 0xAF,
+    MediaNextTrack = 0xB0,
+    MediaPrevTrack = 0xB1,
+    MediaStop = 0xB2,
+    MediaPlayPause = 0xB3,
+    /// `;:` on US keyboards.
+    Oem1 = 0xBA,
+    OemPlus = 0xBB,
+    OemComma = 0xBC,
+    OemMinus = 0xBD,
+    OemPeriod = 0xBE,
+    /// `/?` on US keyboards.
+    Oem2 = 0xBF,
+    /// `` `~ `` on US keyboards.
+    Oem3 = 0xC0,
+    /// `[{` on US keyboards.
+    Oem4 = 0xDB,
+    /// `\|` on US keyboards.
+    Oem5 = 0xDC,
+    /// `]}` on US keyboards.
+    Oem6 = 0xDD,
+    /// `'"` on US keyboards.
+    Oem7 = 0xDE,
+    Oem8 = 0xDF,
+    /// `<>` or `\|` on non-US 102-key keyboards.
+    Oem102 = 0xE2,
+    /// Sent while an IME processes the key.
+    ProcessKey = 0xE5,
+    /// A Unicode character injected with `SendInput`.
+    Packet = 0xE7,
+}
+
+impl VirtualKey {
+    fn from_alias(name: &str) -> Option<VirtualKey> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_ascii_digit() {
+                return Some(VirtualKey::from_raw(c as u8));
+            }
+        }
+        let aliases = [
+            ("Enter", VirtualKey::Return),
+            ("Esc", VirtualKey::Escape),
+            ("Backspace", VirtualKey::Back),
+            ("Del", VirtualKey::Delete),
+            ("Ins", VirtualKey::Insert),
+            ("PgUp", VirtualKey::PageUp),
+            ("PgDn", VirtualKey::PageDown),
+            ("Break", VirtualKey::Pause),
+            ("Plus", VirtualKey::OemPlus),
+            ("+", VirtualKey::OemPlus),
+            ("Minus", VirtualKey::OemMinus),
+            ("-", VirtualKey::OemMinus),
+            ("Comma", VirtualKey::OemComma),
+            (",", VirtualKey::OemComma),
+            ("Period", VirtualKey::OemPeriod),
+            (".", VirtualKey::OemPeriod),
+        ];
+        aliases.iter()
+            .find(|&&(alias, _)| name.eq_ignore_ascii_case(alias))
+            .map(|&(_, key)| key)
+    }
+}
+
+#[cfg(windows)]
+impl VirtualKey {
+    /// Whether the key was down when the current message was generated.
+    pub fn is_down(&self) -> bool {
+        unsafe { user32::GetKeyState(self.to_raw() as i32) < 0 }
+    }
+
+    /// Whether a toggle key such as `CapsLock` is on.
+    pub fn is_toggled(&self) -> bool {
+        unsafe { user32::GetKeyState(self.to_raw() as i32) & 1 != 0 }
+    }
+}
+
+/// The LPARAM of keyboard messages.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct KeyFlags {
+    /// Auto-repeats folded into this message; always 1 for key-up messages.
+    pub repeat_count: u16,
+    pub scan_code: u8,
+    /// Right-hand Alt/Ctrl, the navigation block, numpad Enter and so on.
+    pub extended: bool,
+    /// Alt was down; only meaningful for `WM_SYS*` messages.
+    pub alt_down: bool,
+    /// The key was down before this message.
+    pub was_down: bool,
+    /// The key is being released.
+    pub released: bool,
+}
+
+impl KeyFlags {
+    pub fn from_raw(l: u32) -> KeyFlags {
+        KeyFlags {
+            repeat_count: (l & 0xFFFF) as u16,
+            scan_code: ((l >> 16) & 0xFF) as u8,
+            extended: l & (1 << 24) != 0,
+            alt_down: l & (1 << 29) != 0,
+            was_down: l & (1 << 30) != 0,
+            released: l & (1 << 31) != 0,
+        }
+    }
+
+    /// Whether this is an auto-repeated key-down.
+    pub fn is_repeat(&self) -> bool {
+        self.was_down && !self.released
+    }
+}
+
+/// Assembles UTF-16 code units from `WM_CHAR` into `char`s.
+///
+/// Characters outside the BMP arrive as two `WM_CHAR` messages, one per
+/// surrogate. Unpaired surrogates are dropped.
+#[derive(Default)]
+pub struct SurrogateDecoder {
+    high: Cell<Option<u16>>,
+}
+
+impl SurrogateDecoder {
+    pub fn new() -> SurrogateDecoder {
+        Default::default()
+    }
+
+    /// Feeds one code unit; returns a character once it is complete.
+    pub fn push(&self, unit: u16) -> Option<char> {
+        match unit {
+            0xD800..=0xDBFF => {
+                self.high.set(Some(unit));
+                None
+            }
+            0xDC00..=0xDFFF => {
+                let high = self.high.get()?;
+                self.high.set(None);
+                let c = 0x10000 + (((high as u32) - 0xD800) << 10) + ((unit as u32) - 0xDC00);
+                ::std::char::from_u32(c)
+            }
+            _ => {
+                self.high.set(None);
+                ::std::char::from_u32(unit as u32)
+            }
+        }
+    }
+
+    pub fn reset(&self) {
+        self.high.set(None);
+    }
+}
+
+// Keyboard focus is per thread, so one pending surrogate per thread suffices.
+thread_local!(static CHAR_DECODER: SurrogateDecoder = SurrogateDecoder::new());
+
+/// Decodes the WPARAM of `WM_CHAR`, which may be half of a surrogate pair.
+pub fn char_from_wm_char(w: usize) -> Option<char> {
+    CHAR_DECODER.with(|d| d.push(w as u16))
+}
+
+// MK_* flags of mouse message WPARAMs.
+const MK_LBUTTON: u32 = 0x0001;
+const MK_RBUTTON: u32 = 0x0002;
+const MK_SHIFT: u32 = 0x0004;
+const MK_CONTROL: u32 = 0x0008;
+const MK_MBUTTON: u32 = 0x0010;
+const MK_XBUTTON1: u32 = 0x0020;
+const MK_XBUTTON2: u32 = 0x0040;
+
+/// Wheel movement of one notch of a standard mouse wheel.
+pub const WHEEL_DELTA: isize = 120;
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+/// The mouse buttons held down during a mouse message.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct MouseButtons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+    pub x1: bool,
+    pub x2: bool,
+}
+
+impl MouseButtons {
+    /// Decodes the `MK_*` button flags.
+    pub fn from_raw(mk: u32) -> MouseButtons {
+        MouseButtons {
+            left: mk & MK_LBUTTON != 0,
+            right: mk & MK_RBUTTON != 0,
+            middle: mk & MK_MBUTTON != 0,
+            x1: mk & MK_XBUTTON1 != 0,
+            x2: mk & MK_XBUTTON2 != 0,
+        }
+    }
+
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        match button {
+            MouseButton::Left => self.left,
+            MouseButton::Right => self.right,
+            MouseButton::Middle => self.middle,
+            MouseButton::X1 => self.x1,
+            MouseButton::X2 => self.x2,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.left || self.right || self.middle || self.x1 || self.x2
+    }
+}
+
+/// The modifier keys held down during an input message.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct ModifierKeys {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+impl ModifierKeys {
+    pub fn none() -> ModifierKeys {
+        Default::default()
+    }
+
+    /// The modifiers that were down when the current message was generated.
+    #[cfg(windows)]
+    pub fn current() -> ModifierKeys {
+        ModifierKeys {
+            shift: VirtualKey::Shift.is_down(),
+            control: VirtualKey::Control.is_down(),
+            alt: VirtualKey::Alt.is_down(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.control || self.alt)
+    }
+}
+
+/// Buttons and modifiers of a mouse message, replacing the raw `MK_*` WPARAM.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct MouseState {
+    pub buttons: MouseButtons,
+    pub modifiers: ModifierKeys,
+}
+
+impl MouseState {
+    /// Decodes the `MK_*` flags. Mouse messages do not report the Alt key, so it is
+    /// passed separately.
+    pub fn from_raw(mk: u32, alt: bool) -> MouseState {
+        MouseState {
+            buttons: MouseButtons::from_raw(mk),
+            modifiers: ModifierKeys {
+                shift: mk & MK_SHIFT != 0,
+                control: mk & MK_CONTROL != 0,
+                alt,
+            },
+        }
+    }
+
+    /// Decodes the WPARAM of a mouse message, reading the Alt key state with
+    /// `GetKeyState`.
+    #[cfg(windows)]
+    pub fn from_wparam(w: WPARAM) -> MouseState {
+        MouseState::from_raw(w as u32 & 0xFFFF, VirtualKey::Alt.is_down())
+    }
+}
+
+/// Signed client coordinates packed into the LPARAM of a mouse message
+/// (`GET_X_LPARAM`/`GET_Y_LPARAM`).
+pub fn point_from_lparam(l: isize) -> (isize, isize) {
+    let l = l as u32;
+    ((l & 0xFFFF) as u16 as i16 as isize, (l >> 16) as u16 as i16 as isize)
+}
+
+/// Accumulates wheel deltas into whole notches.
+///
+/// High-resolution wheels and touchpads send deltas smaller than `WHEEL_DELTA`;
+/// keep one of these in the window and feed it every `on_mouse_wheel` delta.
+#[derive(Default)]
+pub struct WheelAccumulator {
+    remainder: Cell<isize>,
+}
+
+impl WheelAccumulator {
+    pub fn new() -> WheelAccumulator {
+        Default::default()
+    }
+
+    /// Adds `delta` and returns the number of whole notches (positive away from
+    /// the user, or to the right for horizontal wheels) to act on now.
+    /// Changing direction discards any partial notch.
+    pub fn add(&self, delta: isize) -> isize {
+        let mut total = self.remainder.get();
+        if (total > 0 && delta < 0) || (total < 0 && delta > 0) {
+            total = 0;
+        }
+        total += delta;
+        let notches = total / WHEEL_DELTA;
+        self.remainder.set(total - notches * WHEEL_DELTA);
+        notches
+    }
+
+    /// The partial delta carried over to the next `add`.
+    pub fn remainder(&self) -> isize {
+        self.remainder.get()
+    }
+
+    pub fn reset(&self) {
+        self.remainder.set(0);
+    }
+}
+
+/// Requests `WM_MOUSEHOVER`/`WM_MOUSELEAVE` via `TrackMouseEvent`.
+///
+/// Windows only reports these once per request, so call `on_mouse_move` from the
+/// window's `OnMouseMove` handler and `on_mouse_leave` from `OnMouseLeave`.
+#[derive(Default)]
+pub struct MouseTracker {
+    tracking: Cell<bool>,
+    hover: bool,
+    hover_time: Option<u32>,
+}
+
+impl MouseTracker {
+    /// Tracks leaving only.
+    pub fn new() -> MouseTracker {
+        Default::default()
+    }
+
+    /// Tracks hovering as well. `hover_time` is in milliseconds; `None` uses the
+    /// system default.
+    pub fn with_hover(hover_time: Option<u32>) -> MouseTracker {
+        MouseTracker {
+            tracking: Cell::new(false),
+            hover: true,
+            hover_time,
+        }
+    }
+
+    pub fn is_inside(&self) -> bool {
+        self.tracking.get()
+    }
+
+    pub fn tracks_hover(&self) -> bool {
+        self.hover
+    }
+
+    pub fn hover_time(&self) -> Option<u32> {
+        self.hover_time
+    }
+
+    /// Starts tracking if not already doing so. Returns `true` if the mouse just
+    /// entered the window.
+    #[cfg(windows)]
+    pub fn on_mouse_move(&self, wnd: &Window) -> bool {
+        if self.tracking.get() {
+            return false;
+        }
+        let started = wnd.track_mouse_event(true, self.hover, self.hover_time);
+        self.tracking.set(started);
+        started
+    }
+
+    /// Re-arms hover tracking, which is cancelled after each `WM_MOUSEHOVER`.
+    #[cfg(windows)]
+    pub fn on_mouse_hover(&self, wnd: &Window) {
+        if self.hover {
+            wnd.track_mouse_event(true, true, self.hover_time);
+        }
+    }
+
+    pub fn on_mouse_leave(&self) {
+        self.tracking.set(false);
+    }
+}
+
+/// Releases the mouse capture when dropped; see `Window::capture_mouse`.
+#[cfg(windows)]
+pub struct CaptureGuard {
+    wnd: Window,
+}
+
+#[cfg(windows)]
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if user32::GetCapture() == self.wnd.wnd {
+                user32::ReleaseCapture();
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Window {
+    /// Directs all mouse input to this window until the returned guard is dropped,
+    /// e.g. while dragging. Keep the guard in the window between the button-down
+    /// and button-up handlers.
+    pub fn capture_mouse(&self) -> CaptureGuard {
+        unsafe { user32::SetCapture(self.wnd) };
+        CaptureGuard { wnd: *self }
+    }
+
+    pub fn has_capture(&self) -> bool {
+        unsafe { user32::GetCapture() == self.wnd }
+    }
+
+    /// Requests a `WM_MOUSELEAVE` and/or `WM_MOUSEHOVER` for this window.
+    /// See `MouseTracker` for a wrapper that keeps track of pending requests.
+    pub fn track_mouse_event(&self, leave: bool, hover: bool, hover_time: Option<u32>) -> bool {
+        let mut flags: DWORD = 0;
+        if leave { flags |= TME_LEAVE; }
+        if hover { flags |= TME_HOVER; }
+        if flags == 0 {
+            return self.cancel_track_mouse_event();
+        }
+        let mut tme = TRACKMOUSEEVENT {
+            cbSize: mem::size_of::<TRACKMOUSEEVENT>() as DWORD,
+            dwFlags: flags,
+            hwndTrack: self.wnd,
+            dwHoverTime: hover_time.unwrap_or(HOVER_DEFAULT),
+        };
+        unsafe { user32::TrackMouseEvent(&mut tme) != 0 }
+    }
+
+    pub fn cancel_track_mouse_event(&self) -> bool {
+        let mut tme = TRACKMOUSEEVENT {
+            cbSize: mem::size_of::<TRACKMOUSEEVENT>() as DWORD,
+            dwFlags: TME_CANCEL | TME_LEAVE | TME_HOVER,
+            hwndTrack: self.wnd,
+            dwHoverTime: 0,
+        };
+        unsafe { user32::TrackMouseEvent(&mut tme) != 0 }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KeyFlags, MouseButtons, MouseState, SurrogateDecoder, VirtualKey, WheelAccumulator};
+    use super::point_from_lparam;
+
+    #[test]
+    fn test_mouse_state_from_raw() {
+        let state = MouseState::from_raw(0x0001 | 0x0008 | 0x0040, false);
+        assert_eq!(state.buttons, MouseButtons {
+            left: true, right: false, middle: false, x1: false, x2: true,
+        });
+        assert!(state.modifiers.control);
+        assert!(!state.modifiers.shift);
+        assert!(!state.modifiers.alt);
+        assert!(MouseState::from_raw(0, true).modifiers.alt);
+        assert!(!MouseState::from_raw(0, false).buttons.any());
+    }
+
+    #[test]
+    fn test_point_from_lparam() {
+        assert_eq!(point_from_lparam(0x0020_0010), (0x10, 0x20));
+        // Negative coordinates occur while the mouse is captured.
+        assert_eq!(point_from_lparam(0xFFFE_FFFFu32 as isize), (-1, -2));
+    }
+
+    #[test]
+    fn test_wheel_accumulator() {
+        let acc = WheelAccumulator::new();
+        assert_eq!(acc.add(120), 1);
+        assert_eq!(acc.add(40), 0);
+        assert_eq!(acc.add(40), 0);
+        assert_eq!(acc.add(40), 1);
+        assert_eq!(acc.remainder(), 0);
+        assert_eq!(acc.add(-360), -3);
+        assert_eq!(acc.add(100), 0);
+        // Reversing direction drops the partial notch.
+        assert_eq!(acc.add(-30), 0);
+        assert_eq!(acc.remainder(), -30);
+        assert_eq!(acc.add(-90), -1);
+        acc.add(60);
+        acc.reset();
+        assert_eq!(acc.remainder(), 0);
+    }
+
+    #[test]
+    fn test_virtual_key() {
+        assert_eq!(VirtualKey::from_raw(0x41), VirtualKey::A);
+        assert_eq!(VirtualKey::from_raw(0x0D), VirtualKey::Return);
+        assert_eq!(VirtualKey::from_raw(0x87), VirtualKey::F24);
+        assert_eq!(VirtualKey::from_raw(0x07), VirtualKey::Other(0x07));
+        for code in 0..256 {
+            assert_eq!(VirtualKey::from_raw(code as u8).to_raw(), code as u8);
+        }
+    }
+
+    #[test]
+    fn test_virtual_key_name() {
+        assert_eq!(VirtualKey::PageUp.name(), Some("PageUp"));
+        assert_eq!(VirtualKey::Other(7).name(), None);
+        assert_eq!(VirtualKey::from_name("pageup"), Some(VirtualKey::PageUp));
+        assert_eq!(VirtualKey::from_name("PgUp"), Some(VirtualKey::PageUp));
+        assert_eq!(VirtualKey::from_name("s"), Some(VirtualKey::S));
+        assert_eq!(VirtualKey::from_name("f12"), Some(VirtualKey::F12));
+        assert_eq!(VirtualKey::from_name("5"), Some(VirtualKey::Key5));
+        assert_eq!(VirtualKey::from_name("+"), Some(VirtualKey::OemPlus));
+        assert_eq!(VirtualKey::from_name("Enter"), Some(VirtualKey::Return));
+        assert_eq!(VirtualKey::from_name(""), None);
+        assert_eq!(VirtualKey::from_name("Hyper"), None);
+    }
+
+    #[test]
+    fn test_key_flags() {
+        // First press of the right Ctrl key.
+        let flags = KeyFlags::from_raw(0x011D_0001);
+        assert_eq!(flags.repeat_count, 1);
+        assert_eq!(flags.scan_code, 0x1D);
+        assert!(flags.extended);
+        assert!(!flags.was_down);
+        assert!(!flags.released);
+        assert!(!flags.is_repeat());
+
+        // Alt+F4 released.
+        let flags = KeyFlags::from_raw(0xE03E_0001);
+        assert!(flags.alt_down);
+        assert!(flags.was_down);
+        assert!(flags.released);
+        assert!(!flags.is_repeat());
+
+        assert!(KeyFlags::from_raw(0x4000_0003).is_repeat());
+    }
+
+    #[test]
+    fn test_surrogate_decoder() {
+        let d = SurrogateDecoder::new();
+        assert_eq!(d.push('a' as u16), Some('a'));
+        // U+1F600
+        assert_eq!(d.push(0xD83D), None);
+        assert_eq!(d.push(0xDE00), Some('\u{1F600}'));
+        // Unpaired surrogates are dropped.
+        assert_eq!(d.push(0xDE00), None);
+        assert_eq!(d.push(0xD83D), None);
+        assert_eq!(d.push('b' as u16), Some('b'));
+        assert_eq!(d.push(0xDE00), None);
+    }
+}
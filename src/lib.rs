@@ -11,35 +11,96 @@
 #![crate_type = "dylib"]
 #![crate_name = "rust_windows"]
 
-#[macro_use]
 extern crate log;
 extern crate gdi32;
 extern crate kernel32;
 extern crate user32;
 extern crate winapi;
 
-use std::ptr;
-use winapi::{DWORD, HWND, LONG, LPARAM, LPMSG, LRESULT, MSG, POINT, UINT, WPARAM};
+#[cfg(windows)] use std::ptr;
+#[cfg(windows)] use std::ffi::CString;
+#[cfg(windows)]
+use winapi::{DWORD, FARPROC, HWND, LONG, LPARAM, LPMSG, LRESULT, MSG, POINT, UINT, WPARAM};
+
+#[cfg(windows)] use wchar::WideCString;
 
+// Modules without `#[cfg(windows)]` are portable, mainly so that they can be used
+// from build scripts and unit-tested on any host.
 #[macro_use]
 pub mod macros;
-pub mod instance;
-pub mod resource;
-pub mod font;
+#[cfg(windows)] pub mod instance;
+#[cfg(windows)] pub mod resource;
+#[cfg(windows)] pub mod font;
+#[cfg(windows)] pub mod window;
+#[cfg(windows)] pub mod gdi;
+#[cfg(windows)] pub mod dialog;
+#[cfg(windows)] pub mod timer;
+#[cfg(windows)] pub mod dispatch;
+#[cfg(windows)] pub mod monitor;
+#[cfg(windows)] pub mod clipboard;
+#[cfg(windows)] pub mod dragdrop;
+#[cfg(windows)] pub mod ole;
+#[cfg(windows)] pub mod tray;
+pub mod accel;
+pub mod com;
+pub mod dpi;
+pub mod error;
+pub mod executor;
+pub mod input;
+pub mod manifest;
+pub mod message;
+pub mod placement;
+pub mod taskbar;
 pub mod wchar;
-pub mod window;
-pub mod gdi;
-pub mod dialog;
 
+#[cfg(windows)]
 pub fn get_last_error() -> DWORD {
     unsafe { kernel32::GetLastError() }
 }
 
+/// Looks up `name` in `module`, loading the module if needed.
+/// Used for APIs missing from older Windows versions.
+#[cfg(windows)]
+fn get_proc_address(module: &str, name: &str) -> Option<FARPROC> {
+    let module_u = match WideCString::new(module) {
+        Ok(m) => m,
+        Err(_) => return None,
+    };
+    let name_c = match CString::new(name) {
+        Ok(n) => n,
+        Err(_) => return None,
+    };
+    unsafe {
+        let mut hmodule = kernel32::GetModuleHandleW(module_u.as_ptr());
+        if hmodule.is_null() {
+            hmodule = kernel32::LoadLibraryW(module_u.as_ptr());
+        }
+        if hmodule.is_null() {
+            return None;
+        }
+        let f = kernel32::GetProcAddress(hmodule, name_c.as_ptr());
+        if f.is_null() { None } else { Some(f) }
+    }
+}
+
+#[cfg(windows)]
 pub fn def_window_proc(hwnd: HWND, msg: UINT, w: WPARAM, l: LPARAM) -> LRESULT {
     unsafe { user32::DefWindowProcW(hwnd, msg, w, l) }
 }
 
+#[cfg(windows)]
 pub fn main_window_loop() -> usize {
+    main_window_loop_filtered(|_| false)
+}
+
+/// Like `main_window_loop`, but first offers every message to `pre_translate`,
+/// which returns `true` if it consumed the message, e.g. through
+/// `accel::Accelerators::translate`.
+///
+/// Tasks spawned with `executor::spawn_local` are run between messages.
+#[cfg(windows)]
+pub fn main_window_loop_filtered<F>(mut pre_translate: F) -> usize
+    where F: FnMut(&MSG) -> bool {
     let mut msg = MSG {
         hwnd: ptr::null_mut(),
         message: 0 as UINT,
@@ -49,19 +110,27 @@ pub fn main_window_loop() -> usize {
         pt: POINT { x: 0 as LONG, y: 0 as LONG },
     };
     loop {
-        let ret = unsafe {
-            user32::GetMessageW(&mut msg as LPMSG, ptr::null_mut(),
-                    0 as UINT, 0 as UINT)
-        };
+        executor::run_local_tasks();
 
-        if ret == 0 {
-            let exit_code = msg.wParam;
-            return exit_code as usize;
+        // Wake up for input or for a task becoming ready.
+        let event = executor::local_wake_event();
+        let count = if event.is_null() { 0 } else { 1 };
+        unsafe {
+            // INFINITE, QS_ALLINPUT, MWMO_INPUTAVAILABLE
+            user32::MsgWaitForMultipleObjectsEx(count, &event, 0xFFFFFFFF, 0x04FF, 0x0004);
         }
-        else {
-            unsafe {
-                user32::TranslateMessage(&msg as *const MSG);
-                user32::DispatchMessageW(&msg as *const MSG);
+
+        // PM_REMOVE
+        while unsafe { user32::PeekMessageW(&mut msg as LPMSG, ptr::null_mut(), 0, 0, 0x0001) } != 0 {
+            if msg.message == 0x0012 { // WM_QUIT
+                let exit_code = msg.wParam;
+                return exit_code as usize;
+            }
+            else if !pre_translate(&msg) {
+                unsafe {
+                    user32::TranslateMessage(&msg as *const MSG);
+                    user32::DispatchMessageW(&msg as *const MSG);
+                }
             }
         }
     }
@@ -41,7 +41,7 @@ macro_rules! wnd_proc_thunk(
     );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_SETFOCUS) => (
         if $msg == 0x0007 { // WM_SETFOCUS
-            let w = ::windows::window::Window { wnd: $w as ::winapi::HWND };
+            let w = $crate::window::Window { wnd: $w as ::winapi::HWND };
             $self_.on_focus(w);
             return 0 as ::winapi::LRESULT;
         }
@@ -52,34 +52,117 @@ macro_rules! wnd_proc_thunk(
             return 0 as ::winapi::LRESULT;
         }
     );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MOUSEMOVE) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0200, on_mouse_move); // WM_MOUSEMOVE
+    );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_LBUTTONDOWN) => (
-        if $msg == 0x0201 { // WM_LBUTTONDOWN
-            let l = $l as u32;
-            let x = (l & 0xFFFF) as isize;
-            let y = (l >> 16) as isize;
-            let flags = $w as u32;
-            $self_.on_left_button_down(x, y, flags);
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0201, on_left_button_down); // WM_LBUTTONDOWN
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_LBUTTONUP) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0202, on_left_button_up); // WM_LBUTTONUP
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_LBUTTONDBLCLK) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0203, on_left_button_double_click); // WM_LBUTTONDBLCLK
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_RBUTTONDOWN) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0204, on_right_button_down); // WM_RBUTTONDOWN
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_RBUTTONUP) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0205, on_right_button_up); // WM_RBUTTONUP
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_RBUTTONDBLCLK) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0206, on_right_button_double_click); // WM_RBUTTONDBLCLK
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MBUTTONDOWN) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0207, on_middle_button_down); // WM_MBUTTONDOWN
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MBUTTONUP) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0208, on_middle_button_up); // WM_MBUTTONUP
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MBUTTONDBLCLK) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x0209, on_middle_button_double_click); // WM_MBUTTONDBLCLK
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_XBUTTONDOWN) => (
+        wnd_proc_thunk!(@xbutton $self_, $msg, $w, $l, 0x020B, on_x_button_down); // WM_XBUTTONDOWN
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_XBUTTONUP) => (
+        wnd_proc_thunk!(@xbutton $self_, $msg, $w, $l, 0x020C, on_x_button_up); // WM_XBUTTONUP
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_XBUTTONDBLCLK) => (
+        wnd_proc_thunk!(@xbutton $self_, $msg, $w, $l, 0x020D, on_x_button_double_click); // WM_XBUTTONDBLCLK
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MOUSEWHEEL) => (
+        wnd_proc_thunk!(@wheel $self_, $msg, $w, $l, 0x020A, on_mouse_wheel); // WM_MOUSEWHEEL
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MOUSEHWHEEL) => (
+        wnd_proc_thunk!(@wheel $self_, $msg, $w, $l, 0x020E, on_mouse_hwheel); // WM_MOUSEHWHEEL
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MOUSEHOVER) => (
+        wnd_proc_thunk!(@mouse $self_, $msg, $w, $l, 0x02A1, on_mouse_hover); // WM_MOUSEHOVER
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_MOUSELEAVE) => (
+        if $msg == 0x02A3 { // WM_MOUSELEAVE
+            $self_.on_mouse_leave();
             return 0 as ::winapi::LRESULT;
         }
     );
-    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_LBUTTONUP) => (
-        if $msg == 0x0202 { // WM_LBUTTONUP
-            let l = $l as u32;
-            let x = (l & 0xFFFF) as isize;
-            let y = (l >> 16) as isize;
-            let flags = $w as u32;
-            $self_.on_left_button_up(x, y, flags);
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_CAPTURECHANGED) => (
+        if $msg == 0x0215 { // WM_CAPTURECHANGED
+            let w = $crate::window::Window { wnd: $l as ::winapi::HWND };
+            $self_.on_capture_changed(w);
             return 0 as ::winapi::LRESULT;
         }
     );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_KEYDOWN) => (
         if $msg == 0x0100 { // WM_KEYDOWN
-            return $self_.on_key_down($w as u8, $l as u32) as ::winapi::LRESULT;
+            let key = $crate::input::VirtualKey::from_raw($w as u8);
+            let flags = $crate::input::KeyFlags::from_raw($l as u32);
+            return $self_.on_key_down(key, flags) as ::winapi::LRESULT;
         }
     );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_KEYUP) => (
         if $msg == 0x0101 { // WM_KEYUP
-            return $self_.on_key_up($w as u8, $l as u32) as ::winapi::LRESULT;
+            let key = $crate::input::VirtualKey::from_raw($w as u8);
+            let flags = $crate::input::KeyFlags::from_raw($l as u32);
+            return $self_.on_key_up(key, flags) as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_SYSKEYDOWN) => (
+        if $msg == 0x0104 { // WM_SYSKEYDOWN
+            let key = $crate::input::VirtualKey::from_raw($w as u8);
+            let flags = $crate::input::KeyFlags::from_raw($l as u32);
+            if $self_.on_sys_key_down(key, flags) {
+                return 0 as ::winapi::LRESULT;
+            }
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_SYSKEYUP) => (
+        if $msg == 0x0105 { // WM_SYSKEYUP
+            let key = $crate::input::VirtualKey::from_raw($w as u8);
+            let flags = $crate::input::KeyFlags::from_raw($l as u32);
+            if $self_.on_sys_key_up(key, flags) {
+                return 0 as ::winapi::LRESULT;
+            }
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_CHAR) => (
+        if $msg == 0x0102 { // WM_CHAR
+            if let Some(c) = $crate::input::char_from_wm_char($w as usize) {
+                $self_.on_char(c, $crate::input::KeyFlags::from_raw($l as u32));
+            }
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_UNICHAR) => (
+        if $msg == 0x0109 { // WM_UNICHAR
+            // UNICODE_NOCHAR asks whether WM_UNICHAR is supported.
+            if $w as u32 == 0xFFFF {
+                return 1 as ::winapi::LRESULT;
+            }
+            if let Some(c) = ::std::char::from_u32($w as u32) {
+                $self_.on_char(c, $crate::input::KeyFlags::from_raw($l as u32));
+            }
+            return 0 as ::winapi::LRESULT;
         }
     );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_ERASEBKGND) => (
@@ -88,34 +171,186 @@ macro_rules! wnd_proc_thunk(
             return $self_.on_erase_background() as ::winapi::LRESULT;
         }
     );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_DPICHANGED) => (
+        if $msg == 0x02E0 { // WM_DPICHANGED
+            let w = $w as u32;
+            let dpi_x = w & 0xFFFF;
+            let dpi_y = w >> 16;
+            let suggested = unsafe { &*($l as *const ::winapi::RECT) };
+            $self_.on_dpi_changed(dpi_x, dpi_y, suggested);
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_CLIPBOARDUPDATE) => (
+        if $msg == 0x031D { // WM_CLIPBOARDUPDATE
+            $self_.on_clipboard_update();
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, WM_DROPFILES) => (
+        if $msg == 0x0233 { // WM_DROPFILES
+            let drop = unsafe { $crate::dragdrop::DropHandle::new($w as ::winapi::HDROP) };
+            $self_.on_drop_files(drop.files(), drop.point());
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, DROP_TARGET) => (
+        // Registered messages are 0 if registration failed, which is WM_NULL.
+        if $msg != 0 && $msg == $crate::ole::drop_target_message() {
+            $crate::ole::dispatch_drop_event($self_);
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, TRAY_ICON) => (
+        if $msg != 0 && $msg == $crate::tray::tray_message() {
+            if let Some((id, event)) = $crate::tray::TrayEvent::from_raw($w as usize, $l as isize) {
+                $self_.on_tray_icon(id, event);
+            }
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, TASKBAR) => (
+        if $msg != 0 && $msg == $crate::taskbar::taskbar_button_created_message() {
+            $self_.on_taskbar_button_created();
+            return 0 as ::winapi::LRESULT;
+        }
+        if $msg == 0x0111 { // WM_COMMAND
+            if let Some(id) = $crate::taskbar::thumb_button_clicked($w as usize) {
+                $self_.on_thumb_button(id);
+                return 0 as ::winapi::LRESULT;
+            }
+        }
+    );
+    ($self_:ident, $msg:ident, $w:ident, $l:ident, MESSAGE($t:ty)) => (
+        // Registered messages are 0 if registration failed, which is WM_NULL.
+        if $msg != 0 && $msg == <$t as $crate::message::Message>::id() {
+            let m = <$t as $crate::message::Message>::decode($w as usize, $l as isize);
+            return $crate::message::OnCustomMessage::<$t>::on_custom_message($self_, m)
+                as ::winapi::LRESULT;
+        }
+    );
     ($self_:ident, $msg:ident, $w:ident, $l:ident, ANY) => (
         if let Some(result) = $self_.on_message($msg, $w, $l) {
             return result;
         }
     );
+
+    // Shared decoding of mouse messages.
+    (@mouse $self_:ident, $msg:ident, $w:ident, $l:ident, $code:expr, $method:ident) => (
+        if $msg == $code {
+            let (x, y) = $crate::input::point_from_lparam($l as isize);
+            let state = $crate::input::MouseState::from_wparam($w);
+            $self_.$method(x, y, state);
+            return 0 as ::winapi::LRESULT;
+        }
+    );
+    (@xbutton $self_:ident, $msg:ident, $w:ident, $l:ident, $code:expr, $method:ident) => (
+        if $msg == $code {
+            let (x, y) = $crate::input::point_from_lparam($l as isize);
+            let state = $crate::input::MouseState::from_wparam($w);
+            let button = match ($w as u32) >> 16 {
+                1 => $crate::input::MouseButton::X1, // XBUTTON1
+                _ => $crate::input::MouseButton::X2,
+            };
+            $self_.$method(button, x, y, state);
+            // XBUTTON messages must return TRUE.
+            return 1 as ::winapi::LRESULT;
+        }
+    );
+    (@wheel $self_:ident, $msg:ident, $w:ident, $l:ident, $code:expr, $method:ident) => (
+        if $msg == $code {
+            let (x, y) = $crate::input::point_from_lparam($l as isize);
+            let state = $crate::input::MouseState::from_wparam($w);
+            let delta = (($w as u32) >> 16) as u16 as i16 as isize;
+            $self_.$method(x, y, delta, state);
+            return 0 as ::winapi::LRESULT;
+        }
+    );
 );
 
 #[macro_export]
 macro_rules! wnd_proc(
-    ($wnd:ident, $win:ident, $($msg:ident),+) => (
+    ($wnd:ident, $win:ident, $($msg:ident $(($arg:ty))*),+) => (
 
-    impl ::windows::window::WindowImpl for $wnd {
-        fn wnd<'a>(&'a self) -> &'a ::windows::window::Window {
+    impl $crate::window::WindowImpl for $wnd {
+        fn wnd<'a>(&'a self) -> &'a $crate::window::Window {
             &self.$win
         }
 
-        fn wnd_mut<'a>(&'a mut self) -> &'a mut ::windows::window::Window {
+        fn wnd_mut<'a>(&'a mut self) -> &'a mut $crate::window::Window {
             &mut self.$win
         }
 
         fn wnd_proc(&self, msg: ::winapi::UINT, w: ::winapi::WPARAM,
                     l: ::winapi::LPARAM) -> ::winapi::LRESULT {
             $(
-                wnd_proc_thunk!(self, msg, w, l, $msg);
+                wnd_proc_thunk!(self, msg, w, l, $msg $(($arg))*);
             )+
-            ::windows::def_window_proc(self.wnd().wnd, msg, w, l)
+            $crate::def_window_proc(self.wnd().wnd, msg, w, l)
         }
     }
 
     )
 );
+
+/// Defines a struct implementing `Message`. The id is one of `user(n)`
+/// (`WM_USER + n`), `app(n)` (`WM_APP + n`) or `registered("name")`, which is
+/// unique system-wide per name and suited to messages between applications.
+#[macro_export]
+macro_rules! define_message {
+    (@id user($n:expr)) => ($crate::message::WM_USER + $n);
+    (@id app($n:expr)) => ($crate::message::WM_APP + $n);
+    (@id registered($name:expr)) => ({
+        static ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        $crate::message::register_message_cached(&ID, $name)
+    });
+
+    ($(#[$attr:meta])* pub struct $name:ident: $kind:ident($id:expr) {}) => (
+        $(#[$attr])*
+        pub struct $name;
+
+        impl $crate::message::Message for $name {
+            fn id() -> u32 { define_message!(@id $kind($id)) }
+            fn encode(&self) -> (usize, isize) { (0, 0) }
+            fn decode(_w: usize, _l: isize) -> $name { $name }
+        }
+    );
+    ($(#[$attr:meta])* pub struct $name:ident: $kind:ident($id:expr) { $w:ident: $wt:ty }) => (
+        $(#[$attr])*
+        pub struct $name {
+            pub $w: $wt,
+        }
+
+        impl $crate::message::Message for $name {
+            fn id() -> u32 { define_message!(@id $kind($id)) }
+            fn encode(&self) -> (usize, isize) {
+                ($crate::message::MessageParam::to_param(&self.$w), 0)
+            }
+            fn decode(w: usize, _l: isize) -> $name {
+                $name { $w: $crate::message::MessageParam::from_param(w) }
+            }
+        }
+    );
+    ($(#[$attr:meta])* pub struct $name:ident: $kind:ident($id:expr)
+     { $w:ident: $wt:ty, $l:ident: $lt:ty }) => (
+        $(#[$attr])*
+        pub struct $name {
+            pub $w: $wt,
+            pub $l: $lt,
+        }
+
+        impl $crate::message::Message for $name {
+            fn id() -> u32 { define_message!(@id $kind($id)) }
+            fn encode(&self) -> (usize, isize) {
+                ($crate::message::MessageParam::to_param(&self.$w),
+                 $crate::message::MessageParam::to_param(&self.$l) as isize)
+            }
+            fn decode(w: usize, l: isize) -> $name {
+                $name {
+                    $w: $crate::message::MessageParam::from_param(w),
+                    $l: $crate::message::MessageParam::from_param(l as usize),
+                }
+            }
+        }
+    );
+}
@@ -0,0 +1,362 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Application manifest generation.
+//!
+//! This module does not call any Windows API, so it can be used from build scripts:
+//!
+//! ```ignore
+//! Manifest::new()
+//!     .common_controls_v6(true)
+//!     .dpi_awareness(DpiAwareness::PerMonitorV2)
+//!     .embed(Path::new(&out_dir), "hello")
+//!     .unwrap();
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use dpi::DpiAwareness;
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum ExecutionLevel {
+    AsInvoker,
+    HighestAvailable,
+    RequireAdministrator,
+}
+
+impl ExecutionLevel {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            ExecutionLevel::AsInvoker => "asInvoker",
+            ExecutionLevel::HighestAvailable => "highestAvailable",
+            ExecutionLevel::RequireAdministrator => "requireAdministrator",
+        }
+    }
+}
+
+/// Windows versions the application declares itself compatible with.
+/// Without these, newer versions apply compatibility shims (e.g. `GetVersionEx` lies).
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum SupportedOs {
+    Vista,
+    Windows7,
+    Windows8,
+    Windows81,
+    Windows10,
+}
+
+impl SupportedOs {
+    pub fn all() -> Vec<SupportedOs> {
+        vec![SupportedOs::Vista, SupportedOs::Windows7, SupportedOs::Windows8,
+             SupportedOs::Windows81, SupportedOs::Windows10]
+    }
+
+    pub fn guid(&self) -> &'static str {
+        match *self {
+            SupportedOs::Vista => "{e2011457-1546-43c5-a5fe-008deee3d3f0}",
+            SupportedOs::Windows7 => "{35138b9a-5d96-4fbd-8e2d-a2440225f93a}",
+            SupportedOs::Windows8 => "{4a2f28e3-53b9-4441-ba9c-d69d4a4a6e38}",
+            SupportedOs::Windows81 => "{1f676c76-80e1-4239-95bb-83d0f6d0da78}",
+            SupportedOs::Windows10 => "{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}",
+        }
+    }
+}
+
+const NS_ASM_V1: &str = "urn:schemas-microsoft-com:asm.v1";
+const NS_ASM_V3: &str = "urn:schemas-microsoft-com:asm.v3";
+const NS_COMPATIBILITY: &str = "urn:schemas-microsoft-com:compatibility.v1";
+const NS_SETTINGS_2005: &str = "http://schemas.microsoft.com/SMI/2005/WindowsSettings";
+const NS_SETTINGS_2016: &str = "http://schemas.microsoft.com/SMI/2016/WindowsSettings";
+const NS_SETTINGS_2019: &str = "http://schemas.microsoft.com/SMI/2019/WindowsSettings";
+
+/// Builder for an application manifest (`RT_MANIFEST` resource).
+#[derive(Clone,Debug)]
+pub struct Manifest {
+    identity: Option<(String, String)>,
+    execution_level: ExecutionLevel,
+    ui_access: bool,
+    common_controls_v6: bool,
+    dpi_awareness: Option<DpiAwareness>,
+    long_path_aware: bool,
+    utf8_code_page: bool,
+    supported_os: Vec<SupportedOs>,
+}
+
+impl Default for Manifest {
+    fn default() -> Manifest {
+        Manifest::new()
+    }
+}
+
+impl Manifest {
+    /// A manifest that only requests `asInvoker` execution.
+    pub fn new() -> Manifest {
+        Manifest {
+            identity: None,
+            execution_level: ExecutionLevel::AsInvoker,
+            ui_access: false,
+            common_controls_v6: false,
+            dpi_awareness: None,
+            long_path_aware: false,
+            utf8_code_page: false,
+            supported_os: Vec::new(),
+        }
+    }
+
+    /// Sets the `assemblyIdentity`. `version` must have the form `a.b.c.d`.
+    pub fn identity(mut self, name: &str, version: &str) -> Manifest {
+        self.identity = Some((name.to_string(), version.to_string()));
+        self
+    }
+
+    pub fn execution_level(mut self, level: ExecutionLevel) -> Manifest {
+        self.execution_level = level;
+        self
+    }
+
+    pub fn ui_access(mut self, ui_access: bool) -> Manifest {
+        self.ui_access = ui_access;
+        self
+    }
+
+    /// Depends on Common Controls version 6, which enables visual styles.
+    pub fn common_controls_v6(mut self, enable: bool) -> Manifest {
+        self.common_controls_v6 = enable;
+        self
+    }
+
+    pub fn dpi_awareness(mut self, awareness: DpiAwareness) -> Manifest {
+        self.dpi_awareness = Some(awareness);
+        self
+    }
+
+    /// Opts into paths longer than `MAX_PATH` (Windows 10 1607 and later,
+    /// if enabled system-wide).
+    pub fn long_path_aware(mut self, enable: bool) -> Manifest {
+        self.long_path_aware = enable;
+        self
+    }
+
+    /// Makes UTF-8 the active code page for `-A` APIs (Windows 10 1903 and later).
+    pub fn utf8_code_page(mut self, enable: bool) -> Manifest {
+        self.utf8_code_page = enable;
+        self
+    }
+
+    pub fn supported_os(mut self, os: SupportedOs) -> Manifest {
+        if !self.supported_os.contains(&os) {
+            self.supported_os.push(os);
+        }
+        self
+    }
+
+    pub fn supported_os_all(mut self) -> Manifest {
+        for os in SupportedOs::all() {
+            self = self.supported_os(os);
+        }
+        self
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut x = String::new();
+        x.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+        x.push_str(&format!("<assembly xmlns=\"{}\" manifestVersion=\"1.0\">\n", NS_ASM_V1));
+
+        if let Some((ref name, ref version)) = self.identity {
+            x.push_str(&format!(
+                "    <assemblyIdentity type=\"win32\" name=\"{}\" version=\"{}\" />\n",
+                escape(name), escape(version)));
+        }
+
+        x.push_str(&format!("    <trustInfo xmlns=\"{}\">\n", NS_ASM_V3));
+        x.push_str("        <security>\n");
+        x.push_str("            <requestedPrivileges>\n");
+        x.push_str(&format!(
+            "                <requestedExecutionLevel level=\"{}\" uiAccess=\"{}\" />\n",
+            self.execution_level.as_str(), self.ui_access));
+        x.push_str("            </requestedPrivileges>\n");
+        x.push_str("        </security>\n");
+        x.push_str("    </trustInfo>\n");
+
+        if self.common_controls_v6 {
+            x.push_str("    <dependency>\n");
+            x.push_str("        <dependentAssembly>\n");
+            x.push_str("            <assemblyIdentity type=\"win32\" \
+                        name=\"Microsoft.Windows.Common-Controls\" version=\"6.0.0.0\" \
+                        processorArchitecture=\"*\" publicKeyToken=\"6595b64144ccf1df\" \
+                        language=\"*\" />\n");
+            x.push_str("        </dependentAssembly>\n");
+            x.push_str("    </dependency>\n");
+        }
+
+        if !self.supported_os.is_empty() {
+            x.push_str(&format!("    <compatibility xmlns=\"{}\">\n", NS_COMPATIBILITY));
+            x.push_str("        <application>\n");
+            for os in &self.supported_os {
+                x.push_str(&format!("            <supportedOS Id=\"{}\" />\n", os.guid()));
+            }
+            x.push_str("        </application>\n");
+            x.push_str("    </compatibility>\n");
+        }
+
+        let mut settings = Vec::new();
+        if let Some(awareness) = self.dpi_awareness {
+            let (aware, awareness) = match awareness {
+                DpiAwareness::Unaware => ("false", "unaware"),
+                DpiAwareness::System => ("true", "system"),
+                DpiAwareness::PerMonitor => ("true/pm", "PerMonitor"),
+                DpiAwareness::PerMonitorV2 => ("true/pm", "PerMonitorV2, PerMonitor"),
+            };
+            settings.push(format!("<dpiAware xmlns=\"{}\">{}</dpiAware>",
+                                  NS_SETTINGS_2005, aware));
+            settings.push(format!("<dpiAwareness xmlns=\"{}\">{}</dpiAwareness>",
+                                  NS_SETTINGS_2016, awareness));
+        }
+        if self.long_path_aware {
+            settings.push(format!("<longPathAware xmlns=\"{}\">true</longPathAware>",
+                                  NS_SETTINGS_2016));
+        }
+        if self.utf8_code_page {
+            settings.push(format!("<activeCodePage xmlns=\"{}\">UTF-8</activeCodePage>",
+                                  NS_SETTINGS_2019));
+        }
+        if !settings.is_empty() {
+            x.push_str(&format!("    <application xmlns=\"{}\">\n", NS_ASM_V3));
+            x.push_str("        <windowsSettings>\n");
+            for s in &settings {
+                x.push_str(&format!("            {}\n", s));
+            }
+            x.push_str("        </windowsSettings>\n");
+            x.push_str("    </application>\n");
+        }
+
+        x.push_str("</assembly>\n");
+        x
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut f = File::create(path)?;
+        f.write_all(self.to_xml().as_bytes())
+    }
+
+    /// For use in build scripts: writes `<name>.manifest` to `out_dir`, compiles it
+    /// into a resource object as the process manifest with `windres`, and tells
+    /// cargo to link it.
+    ///
+    /// The object is passed to the linker directly: nothing references a resource,
+    /// so from a static library the linker would leave it out.
+    pub fn embed(&self, out_dir: &Path, name: &str) -> io::Result<()> {
+        let manifest_name = format!("{}.manifest", name);
+        let rc_name = format!("{}_manifest.rc", name);
+        let obj_name = format!("{}_manifest.rc.o", name);
+
+        self.write(&out_dir.join(&manifest_name))?;
+        {
+            // 1 = CREATEPROCESS_MANIFEST_RESOURCE_ID, 24 = RT_MANIFEST
+            let mut rc = File::create(out_dir.join(&rc_name))?;
+            writeln!(rc, "1 24 \"{}\"", manifest_name)?;
+        }
+
+        run(Command::new("windres").arg(&rc_name).arg("-o").arg(&obj_name)
+                                   .current_dir(out_dir))?;
+
+        println!("cargo:rustc-link-arg={}", out_dir.join(&obj_name).display());
+        Ok(())
+    }
+}
+
+fn run(cmd: &mut Command) -> io::Result<()> {
+    let status = cmd.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{:?} failed: {}", cmd, status)))
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExecutionLevel, Manifest, SupportedOs};
+    use dpi::DpiAwareness;
+
+    #[test]
+    fn test_default_manifest() {
+        let expected = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<assembly xmlns=\"urn:schemas-microsoft-com:asm.v1\" manifestVersion=\"1.0\">
+    <trustInfo xmlns=\"urn:schemas-microsoft-com:asm.v3\">
+        <security>
+            <requestedPrivileges>
+                <requestedExecutionLevel level=\"asInvoker\" uiAccess=\"false\" />
+            </requestedPrivileges>
+        </security>
+    </trustInfo>
+</assembly>
+";
+        assert_eq!(Manifest::new().to_xml(), expected);
+    }
+
+    #[test]
+    fn test_full_manifest() {
+        let xml = Manifest::new()
+            .identity("Rust.Hello", "1.0.0.0")
+            .execution_level(ExecutionLevel::RequireAdministrator)
+            .common_controls_v6(true)
+            .dpi_awareness(DpiAwareness::PerMonitorV2)
+            .long_path_aware(true)
+            .utf8_code_page(true)
+            .supported_os(SupportedOs::Windows10)
+            .supported_os(SupportedOs::Windows10)
+            .to_xml();
+
+        assert!(xml.contains("<assemblyIdentity type=\"win32\" name=\"Rust.Hello\" \
+                              version=\"1.0.0.0\" />"));
+        assert!(xml.contains("level=\"requireAdministrator\""));
+        assert!(xml.contains("name=\"Microsoft.Windows.Common-Controls\" version=\"6.0.0.0\""));
+        assert!(xml.contains("<dpiAware xmlns=\"http://schemas.microsoft.com/SMI/2005/\
+                              WindowsSettings\">true/pm</dpiAware>"));
+        assert!(xml.contains(">PerMonitorV2, PerMonitor</dpiAwareness>"));
+        assert!(xml.contains(">true</longPathAware>"));
+        assert!(xml.contains(">UTF-8</activeCodePage>"));
+        assert_eq!(xml.matches("<supportedOS ").count(), 1);
+        assert!(xml.contains("{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"));
+        assert!(xml.ends_with("</assembly>\n"));
+    }
+
+    #[test]
+    fn test_supported_os_all() {
+        let xml = Manifest::new().supported_os_all().to_xml();
+        assert_eq!(xml.matches("<supportedOS ").count(), 5);
+    }
+
+    #[test]
+    fn test_escape() {
+        let xml = Manifest::new().identity("a&b<\"c\">", "1.0.0.0").to_xml();
+        assert!(xml.contains("name=\"a&amp;b&lt;&quot;c&quot;&gt;\""));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Typed application-defined messages.
+//!
+//! ```ignore
+//! define_message!(pub struct Progress: app(1) { done: u32, total: u32 });
+//! define_message!(pub struct Refresh: registered("MyApp.Refresh") {});
+//!
+//! wnd_proc!(MainFrame, win, WM_CREATE, MESSAGE(Progress), ANY);
+//!
+//! impl OnCustomMessage<Progress> for MainFrame {
+//!     fn on_custom_message(&self, p: Progress) -> isize { ...; 0 }
+//! }
+//!
+//! main.post(Progress { done: 1, total: 10 });
+//! ```
+//!
+//! The first field travels in WPARAM and the second in LPARAM, so a message has
+//! at most two fields, each a `MessageParam`.
+
+#[cfg(windows)] use std::mem;
+#[cfg(windows)] use std::ptr;
+#[cfg(windows)] use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(windows)] use user32;
+#[cfg(windows)] use winapi::{BOOL, DWORD, HWND, LPARAM, LRESULT, UINT, WPARAM};
+
+#[cfg(windows)] use wchar::WideCString;
+#[cfg(windows)] use window::Window;
+
+/// The first message id of `user(n)` messages, which are private to a window class.
+pub const WM_USER: u32 = 0x0400;
+/// The first message id of `app(n)` messages, which are private to an application.
+pub const WM_APP: u32 = 0x8000;
+
+/// A value that fits in a WPARAM or LPARAM.
+pub trait MessageParam: Sized {
+    fn to_param(&self) -> usize;
+    fn from_param(p: usize) -> Self;
+}
+
+macro_rules! unsigned_param {
+    ($($t:ty),*) => ($(
+        impl MessageParam for $t {
+            fn to_param(&self) -> usize { *self as usize }
+            fn from_param(p: usize) -> $t { p as $t }
+        }
+    )*)
+}
+
+// Signed values are sign-extended, so that they survive LPARAM.
+macro_rules! signed_param {
+    ($($t:ty),*) => ($(
+        impl MessageParam for $t {
+            fn to_param(&self) -> usize { *self as isize as usize }
+            fn from_param(p: usize) -> $t { p as isize as $t }
+        }
+    )*)
+}
+
+unsigned_param!(u8, u16, u32, usize);
+signed_param!(i8, i16, i32, isize);
+
+impl MessageParam for bool {
+    fn to_param(&self) -> usize { *self as usize }
+    fn from_param(p: usize) -> bool { p != 0 }
+}
+
+/// Invalid code points decode as U+FFFD.
+impl MessageParam for char {
+    fn to_param(&self) -> usize { *self as usize }
+    fn from_param(p: usize) -> char {
+        ::std::char::from_u32(p as u32).unwrap_or('\u{FFFD}')
+    }
+}
+
+#[cfg(windows)]
+impl MessageParam for Window {
+    fn to_param(&self) -> usize { self.wnd as usize }
+    fn from_param(p: usize) -> Window { Window { wnd: p as ::winapi::HWND } }
+}
+
+/// A message defined with `define_message!`.
+pub trait Message: Sized {
+    /// The message id, or 0 if registration failed.
+    fn id() -> u32;
+    fn encode(&self) -> (usize, isize);
+    fn decode(w: usize, l: isize) -> Self;
+}
+
+/// Handles a typed message listed as `MESSAGE(Type)` in `wnd_proc!`. The result is
+/// returned from the window procedure.
+pub trait OnCustomMessage<M: Message> {
+    fn on_custom_message(&self, _msg: M) -> isize {
+        0
+    }
+}
+
+/// Registers a message name with `RegisterWindowMessageW`. Every process gets the
+/// same id for the same name.
+#[cfg(windows)]
+pub fn register_message(name: &str) -> Option<u32> {
+    let name_u = match WideCString::new(name) {
+        Ok(n) => n,
+        Err(_) => return None,
+    };
+    match unsafe { user32::RegisterWindowMessageW(name_u.as_ptr()) } {
+        0 => None,
+        id => Some(id),
+    }
+}
+
+/// `register_message`, remembering the id in `cache`. Used by `define_message!`.
+/// Returns 0, which is `WM_NULL`, if registration failed, so callers comparing
+/// a message against the id must rule out 0 first.
+#[cfg(windows)]
+pub fn register_message_cached(cache: &AtomicUsize, name: &str) -> u32 {
+    let id = cache.load(Ordering::Relaxed);
+    if id != 0 {
+        return id as u32;
+    }
+    let id = register_message(name).unwrap_or(0);
+    cache.store(id as usize, Ordering::Relaxed);
+    id
+}
+
+#[cfg(windows)]
+type ChangeWindowMessageFilterExFn = unsafe extern "system" fn(HWND, UINT, DWORD, *mut u8) -> BOOL;
+
+#[cfg(windows)] const MSGFLT_ALLOW: DWORD = 1;
+
+#[cfg(windows)]
+impl Window {
+    /// Posts a typed message. Returns `false` if posting failed or the message is
+    /// registered and its registration failed.
+    pub fn post<M: Message>(&self, msg: M) -> bool {
+        let id = M::id();
+        let (w, l) = msg.encode();
+        id != 0 && self.post_message(id, w as WPARAM, l as LPARAM)
+    }
+
+    /// Sends a typed message and returns the handler's result. Returns 0 without
+    /// sending if the message is registered and its registration failed.
+    pub fn send<M: Message>(&self, msg: M) -> LRESULT {
+        let id = M::id();
+        if id == 0 {
+            return 0;
+        }
+        let (w, l) = msg.encode();
+        self.send_message(id, w as WPARAM, l as LPARAM)
+    }
+
+    /// Lets less privileged processes send `msg` to the window, which they cannot
+    /// by default when this process is elevated (Windows 7 and later).
+    pub fn allow_message(&self, msg: UINT) -> bool {
+        match ::get_proc_address("user32.dll", "ChangeWindowMessageFilterEx") {
+            Some(f) => unsafe {
+                let f: ChangeWindowMessageFilterExFn = mem::transmute(f);
+                f(self.wnd, msg, MSGFLT_ALLOW, ptr::null_mut()) != 0
+            },
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Message, MessageParam, WM_APP, WM_USER};
+
+    define_message!(
+        #[derive(Debug, PartialEq)]
+        pub struct Progress: app(1) { done: i32, total: u32 }
+    );
+    define_message!(
+        #[derive(Debug, PartialEq)]
+        pub struct Key: user(7) { c: char }
+    );
+    define_message!(
+        #[derive(Debug, PartialEq)]
+        pub struct Ping: app(2) {}
+    );
+
+    #[test]
+    fn test_params() {
+        assert_eq!(i32::from_param((-5i32).to_param()), -5);
+        assert_eq!((-1i16).to_param() as isize, -1);
+        assert_eq!(u8::from_param(0x1FF), 0xFF);
+        assert!(bool::from_param(2));
+        assert_eq!(char::from_param('\u{1F600}'.to_param()), '\u{1F600}');
+        assert_eq!(char::from_param(0xD800), '\u{FFFD}');
+    }
+
+    #[test]
+    fn test_define_message() {
+        assert_eq!(Progress::id(), WM_APP + 1);
+        assert_eq!(Key::id(), WM_USER + 7);
+        assert_eq!(Ping::id(), WM_APP + 2);
+
+        let p = Progress { done: -3, total: 10 };
+        let (w, l) = p.encode();
+        assert_eq!(l, 10);
+        assert_eq!(Progress::decode(w, l), p);
+
+        let k = Key { c: 'x' };
+        assert_eq!(k.encode(), ('x' as usize, 0));
+        assert_eq!(Key::decode('x' as usize, 0), k);
+
+        assert_eq!(Ping.encode(), (0, 0));
+        assert_eq!(Ping::decode(1, 2), Ping);
+    }
+}
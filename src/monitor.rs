@@ -0,0 +1,199 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Display monitors.
+//!
+//! Rectangles are in virtual-screen coordinates: the primary monitor's top-left
+//! corner is the origin, and other monitors may lie at negative coordinates.
+
+use std::mem;
+use std::ptr;
+
+use user32;
+use winapi::{BOOL, DWORD, HDC, HMONITOR, HRESULT, LONG, LPARAM, LPRECT, MONITORINFOEXW, POINT, UINT};
+
+use dpi;
+use placement::{MonitorLayout, Rect};
+use window::Window;
+
+/// What `Monitor::from_window` and friends return when nothing is on a monitor.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum MonitorFallback {
+    /// No monitor.
+    Null,
+    Primary,
+    Nearest,
+}
+
+impl MonitorFallback {
+    pub fn to_raw(&self) -> DWORD {
+        match *self {
+            MonitorFallback::Null => 0, // MONITOR_DEFAULTTONULL
+            MonitorFallback::Primary => 1, // MONITOR_DEFAULTTOPRIMARY
+            MonitorFallback::Nearest => 2, // MONITOR_DEFAULTTONEAREST
+        }
+    }
+}
+
+const MONITORINFOF_PRIMARY: DWORD = 0x0000_0001;
+
+// MDT_EFFECTIVE_DPI
+const EFFECTIVE_DPI: UINT = 0;
+
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct MonitorInfo {
+    /// The display device name, e.g. `\\.\DISPLAY1`.
+    pub device_name: String,
+    pub rect: Rect,
+    /// `rect` less the taskbar and docked toolbars.
+    pub work: Rect,
+    pub primary: bool,
+    /// The effective DPI; see `Monitor::dpi`.
+    pub dpi: u32,
+}
+
+impl MonitorInfo {
+    pub fn layout(&self) -> MonitorLayout {
+        MonitorLayout {
+            device_name: self.device_name.clone(),
+            rect: self.rect,
+            work: self.work,
+            primary: self.primary,
+        }
+    }
+}
+
+/// A display monitor. Handles become invalid when the display configuration
+/// changes (`WM_DISPLAYCHANGE`), so they should not be kept around.
+#[derive(Clone,Copy,PartialEq,Eq,Hash,Debug)]
+pub struct Monitor {
+    pub monitor: HMONITOR,
+}
+
+impl Monitor {
+    fn from_raw(monitor: HMONITOR) -> Option<Monitor> {
+        if monitor.is_null() { None } else { Some(Monitor { monitor }) }
+    }
+
+    /// All monitors, in the order the system enumerates them.
+    pub fn all() -> Vec<Monitor> {
+        unsafe extern "system" fn enum_proc(monitor: HMONITOR, _dc: HDC, _rect: LPRECT,
+                                            data: LPARAM) -> BOOL {
+            let monitors = &mut *(data as *mut Vec<Monitor>);
+            monitors.push(Monitor { monitor });
+            1
+        }
+
+        let mut monitors: Vec<Monitor> = Vec::new();
+        unsafe {
+            user32::EnumDisplayMonitors(ptr::null_mut(), ptr::null(), Some(enum_proc),
+                                        &mut monitors as *mut Vec<Monitor> as LPARAM);
+        }
+        monitors
+    }
+
+    pub fn primary() -> Option<Monitor> {
+        Monitor::from_point(0, 0, MonitorFallback::Primary)
+    }
+
+    /// The monitor showing the largest part of `wnd`.
+    pub fn from_window(wnd: &Window, fallback: MonitorFallback) -> Option<Monitor> {
+        Monitor::from_raw(unsafe { user32::MonitorFromWindow(wnd.wnd, fallback.to_raw()) })
+    }
+
+    pub fn from_point(x: isize, y: isize, fallback: MonitorFallback) -> Option<Monitor> {
+        let pt = POINT { x: x as LONG, y: y as LONG };
+        Monitor::from_raw(unsafe { user32::MonitorFromPoint(pt, fallback.to_raw()) })
+    }
+
+    /// The monitor showing the largest part of `rect`.
+    pub fn from_rect(rect: &Rect, fallback: MonitorFallback) -> Option<Monitor> {
+        let rect = rect.to_raw();
+        Monitor::from_raw(unsafe { user32::MonitorFromRect(&rect, fallback.to_raw()) })
+    }
+
+    pub fn info(&self) -> Option<MonitorInfo> {
+        let mut info: MONITORINFOEXW = unsafe { mem::zeroed() };
+        info.cbSize = mem::size_of::<MONITORINFOEXW>() as DWORD;
+        let ok = unsafe {
+            user32::GetMonitorInfoW(self.monitor, &mut info as *mut MONITORINFOEXW as *mut _)
+        };
+        if ok == 0 {
+            return None;
+        }
+        let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+        Some(MonitorInfo {
+            device_name: String::from_utf16_lossy(&info.szDevice[..len]),
+            rect: Rect::from_raw(&info.rcMonitor),
+            work: Rect::from_raw(&info.rcWork),
+            primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+            dpi: self.dpi(),
+        })
+    }
+
+    /// The DPI windows on this monitor should be laid out for. Without
+    /// per-monitor awareness (or before Windows 8.1) this is the system DPI.
+    pub fn dpi(&self) -> u32 {
+        if let Some(f) = ::get_proc_address("shcore.dll", "GetDpiForMonitor") {
+            let f: unsafe extern "system" fn(HMONITOR, UINT, *mut UINT, *mut UINT) -> HRESULT =
+                unsafe { mem::transmute(f) };
+            let (mut x, mut y) = (0, 0);
+            if unsafe { f(self.monitor, EFFECTIVE_DPI, &mut x, &mut y) } >= 0 && x != 0 {
+                return x;
+            }
+        }
+        dpi::system_dpi()
+    }
+}
+
+/// The top-left corner that centers a `width` by `height` rectangle in `area`.
+fn center_in(width: isize, height: isize, area: &Rect) -> (isize, isize) {
+    (area.left + (area.width() - width) / 2, area.top + (area.height() - height) / 2)
+}
+
+impl Window {
+    /// The monitor showing the largest part of the window, or the nearest one.
+    pub fn monitor(&self) -> Option<Monitor> {
+        Monitor::from_window(self, MonitorFallback::Nearest)
+    }
+
+    /// Centers a top-level window in the work area of its monitor.
+    pub fn center_on_monitor(&self) -> bool {
+        match self.monitor() {
+            Some(monitor) => self.center_on(&monitor),
+            None => false,
+        }
+    }
+
+    /// Centers a top-level window in the work area of `monitor`. A window larger
+    /// than the work area is aligned with its top-left corner instead.
+    pub fn center_on(&self, monitor: &Monitor) -> bool {
+        let (rect, info) = match (self.window_rect(), monitor.info()) {
+            (Some(rect), Some(info)) => (Rect::from_raw(&rect), info),
+            _ => return false,
+        };
+        let (x, y) = center_in(rect.width(), rect.height(), &info.work);
+        self.move_to(x.max(info.work.left), y.max(info.work.top))
+    }
+
+    /// Centers a top-level window over `owner`, keeping it within the work area
+    /// of `owner`'s monitor. Useful for dialogs and popups.
+    pub fn center_over(&self, owner: &Window) -> bool {
+        let (rect, owner_rect, info) =
+            match (self.window_rect(), owner.window_rect(), owner.monitor().and_then(|m| m.info())) {
+                (Some(r), Some(o), Some(info)) => (Rect::from_raw(&r), Rect::from_raw(&o), info),
+                _ => return false,
+            };
+        let (x, y) = center_in(rect.width(), rect.height(), &owner_rect);
+        let work = &info.work;
+        let x = x.min(work.right - rect.width()).max(work.left);
+        let y = y.min(work.bottom - rect.height()).max(work.top);
+        self.move_to(x, y)
+    }
+}
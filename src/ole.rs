@@ -0,0 +1,735 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! OLE drag and drop.
+//!
+//! A window accepts drops by implementing `DropTarget`, listing `DROP_TARGET` in
+//! `wnd_proc!` and calling `register_drop_target`:
+//!
+//! ```ignore
+//! wnd_proc!(MainFrame, win, WM_CREATE, DROP_TARGET, ANY);
+//!
+//! impl DropTarget for MainFrame {
+//!     fn on_drag_enter(&self, data: &DropData, state: MouseState, _x: isize, _y: isize,
+//!                      allowed: DropEffects) -> DropEffect {
+//!         if data.has_format(CF_UNICODETEXT) { allowed.choose(&state) } else { DropEffect::None }
+//!     }
+//!     ...
+//! }
+//!
+//! let _ole = OleGuard::initialize().unwrap();
+//! main.register_drop_target();
+//! ```
+//!
+//! and starts drags with `do_drag_drop`:
+//!
+//! ```ignore
+//! let data = DataObject::new().text("Hello").files(&[path]);
+//! if do_drag_drop(&data, DropEffects::new(&[DropEffect::Copy, DropEffect::Move])) ==
+//!    DropEffect::Move {
+//!     // remove the dragged items
+//! }
+//! ```
+//!
+//! Drops are delivered to the window impl through a registered message, so the
+//! handlers run like any other message handler.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::AtomicUsize;
+
+use winapi::{BOOL, DWORD, HGLOBAL, HRESULT, HWND, LONG, POINT, UINT, c_void};
+
+use clipboard;
+use clipboard::{CF_HDROP, CF_UNICODETEXT};
+use com;
+use com::{AddRefFn, ComObject, ComPtr, Guid, Interface, QueryInterfaceFn, add_ref, query_interface,
+          release};
+use error::{Result, E_NOTIMPL, E_OUTOFMEMORY, S_OK};
+use dragdrop;
+use input::MouseState;
+use message;
+use window::Window;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct FORMATETC {
+    pub cfFormat: u16,
+    pub ptd: *mut c_void,
+    pub dwAspect: DWORD,
+    pub lindex: LONG,
+    pub tymed: DWORD,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct STGMEDIUM {
+    pub tymed: DWORD,
+    /// The union of medium handles; only `hGlobal` is used here.
+    pub hGlobal: HGLOBAL,
+    pub pUnkForRelease: *mut c_void,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IDropTargetVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    DragEnter: unsafe extern "system" fn(*mut c_void, *mut c_void, DWORD, POINT, *mut DWORD) -> HRESULT,
+    DragOver: unsafe extern "system" fn(*mut c_void, DWORD, POINT, *mut DWORD) -> HRESULT,
+    DragLeave: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+    Drop: unsafe extern "system" fn(*mut c_void, *mut c_void, DWORD, POINT, *mut DWORD) -> HRESULT,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IDropSourceVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    QueryContinueDrag: unsafe extern "system" fn(*mut c_void, BOOL, DWORD) -> HRESULT,
+    GiveFeedback: unsafe extern "system" fn(*mut c_void, DWORD) -> HRESULT,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IDataObjectVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    GetData: unsafe extern "system" fn(*mut c_void, *const FORMATETC, *mut STGMEDIUM) -> HRESULT,
+    GetDataHere: unsafe extern "system" fn(*mut c_void, *const FORMATETC, *mut STGMEDIUM) -> HRESULT,
+    QueryGetData: unsafe extern "system" fn(*mut c_void, *const FORMATETC) -> HRESULT,
+    GetCanonicalFormatEtc: unsafe extern "system" fn(*mut c_void, *const FORMATETC, *mut FORMATETC)
+                                                     -> HRESULT,
+    SetData: unsafe extern "system" fn(*mut c_void, *const FORMATETC, *mut STGMEDIUM, BOOL) -> HRESULT,
+    EnumFormatEtc: unsafe extern "system" fn(*mut c_void, DWORD, *mut *mut c_void) -> HRESULT,
+    DAdvise: unsafe extern "system" fn(*mut c_void, *const FORMATETC, DWORD, *mut c_void, *mut DWORD)
+                                       -> HRESULT,
+    DUnadvise: unsafe extern "system" fn(*mut c_void, DWORD) -> HRESULT,
+    EnumDAdvise: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> HRESULT,
+}
+
+// Missing from the sys crates.
+#[link(name = "ole32")]
+extern "system" {
+    fn OleInitialize(pvReserved: *mut c_void) -> HRESULT;
+    fn OleUninitialize();
+    fn RegisterDragDrop(hwnd: HWND, pDropTarget: *mut c_void) -> HRESULT;
+    fn RevokeDragDrop(hwnd: HWND) -> HRESULT;
+    fn DoDragDrop(pDataObj: *mut c_void, pDropSource: *mut c_void, dwOKEffects: DWORD,
+                  pdwEffect: *mut DWORD) -> HRESULT;
+    fn ReleaseStgMedium(pmedium: *mut STGMEDIUM);
+}
+
+#[link(name = "shell32")]
+extern "system" {
+    fn SHCreateStdEnumFmtEtc(cfmt: UINT, afmt: *const FORMATETC, ppenumFormatEtc: *mut *mut c_void)
+                             -> HRESULT;
+}
+
+const IID_IDATAOBJECT: Guid = Guid {
+    data1: 0x0000_010E, data2: 0x0000, data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+const IID_IDROPSOURCE: Guid = Guid {
+    data1: 0x0000_0121, data2: 0x0000, data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+const IID_IDROPTARGET: Guid = Guid {
+    data1: 0x0000_0122, data2: 0x0000, data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+const OLE_E_ADVISENOTSUPPORTED: HRESULT = 0x8004_0003u32 as HRESULT;
+const DV_E_FORMATETC: HRESULT = 0x8004_0064u32 as HRESULT;
+const DV_E_TYMED: HRESULT = 0x8004_0069u32 as HRESULT;
+const DATA_S_SAMEFORMATETC: HRESULT = 0x0004_0130;
+const DRAGDROP_S_DROP: HRESULT = 0x0004_0100;
+const DRAGDROP_S_CANCEL: HRESULT = 0x0004_0101;
+const DRAGDROP_S_USEDEFAULTCURSORS: HRESULT = 0x0004_0102;
+
+const TYMED_HGLOBAL: DWORD = 1;
+const DVASPECT_CONTENT: DWORD = 1;
+const DATADIR_GET: DWORD = 1;
+
+// The mouse buttons in an OLE key state, and the Alt key, which shares its bit
+// with MK_XBUTTON1 in window messages.
+const MK_BUTTONS: DWORD = 0x0001 | 0x0002 | 0x0010;
+const MK_ALT: DWORD = 0x0020;
+
+/// Initializes OLE on the current thread, which must be done before using drag
+/// and drop, and uninitializes it when dropped.
+pub struct OleGuard {
+    _private: (),
+}
+
+impl OleGuard {
+    /// Fails with `RPC_E_CHANGED_MODE` if the thread has joined the multithreaded
+    /// COM apartment.
+    pub fn initialize() -> Result<OleGuard> {
+        // As with COM, an S_FALSE success still needs its OleUninitialize.
+        com::check(unsafe { OleInitialize(ptr::null_mut()) })?;
+        Ok(OleGuard { _private: () })
+    }
+}
+
+impl Drop for OleGuard {
+    fn drop(&mut self) {
+        unsafe { OleUninitialize() };
+    }
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum DropEffect {
+    None,
+    Copy,
+    Move,
+    Link,
+}
+
+impl DropEffect {
+    /// Decodes a `DROPEFFECT_*` value. Of several effects, the first of copy, move
+    /// and link is taken.
+    pub fn from_raw(effect: u32) -> DropEffect {
+        if effect & 1 != 0 {
+            DropEffect::Copy
+        } else if effect & 2 != 0 {
+            DropEffect::Move
+        } else if effect & 4 != 0 {
+            DropEffect::Link
+        } else {
+            DropEffect::None
+        }
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            DropEffect::None => 0, // DROPEFFECT_NONE
+            DropEffect::Copy => 1, // DROPEFFECT_COPY
+            DropEffect::Move => 2, // DROPEFFECT_MOVE
+            DropEffect::Link => 4, // DROPEFFECT_LINK
+        }
+    }
+}
+
+/// A set of drop effects, such as those a drag source allows.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct DropEffects(u32);
+
+impl DropEffects {
+    pub fn new(effects: &[DropEffect]) -> DropEffects {
+        DropEffects(effects.iter().fold(0, |raw, e| raw | e.to_raw()))
+    }
+
+    pub fn all() -> DropEffects {
+        DropEffects::new(&[DropEffect::Copy, DropEffect::Move, DropEffect::Link])
+    }
+
+    pub fn from_raw(effects: u32) -> DropEffects {
+        DropEffects(effects & DropEffects::all().0)
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, effect: DropEffect) -> bool {
+        self.0 & effect.to_raw() == effect.to_raw()
+    }
+
+    /// The effect Explorer would pick for the keys held: Ctrl+Shift links, Ctrl
+    /// copies, Shift moves, and otherwise moving is preferred over copying over
+    /// linking. `None` if the wanted effect is not allowed.
+    pub fn choose(&self, state: &MouseState) -> DropEffect {
+        let keys = &state.modifiers;
+        let wanted = match (keys.control, keys.shift) {
+            (true, true) => DropEffect::Link,
+            (true, false) => DropEffect::Copy,
+            (false, true) => DropEffect::Move,
+            (false, false) => {
+                return [DropEffect::Move, DropEffect::Copy, DropEffect::Link].iter()
+                    .cloned()
+                    .find(|e| self.contains(*e))
+                    .unwrap_or(DropEffect::None);
+            }
+        };
+        if self.contains(wanted) { wanted } else { DropEffect::None }
+    }
+}
+
+enum IDataObject {}
+
+unsafe impl Interface for IDataObject {
+    type Vtbl = IDataObjectVtbl;
+    const IID: Guid = IID_IDATAOBJECT;
+}
+
+enum IDropSource {}
+
+unsafe impl Interface for IDropSource {
+    type Vtbl = IDropSourceVtbl;
+    const IID: Guid = IID_IDROPSOURCE;
+}
+
+enum IDropTarget {}
+
+unsafe impl Interface for IDropTarget {
+    type Vtbl = IDropTargetVtbl;
+    const IID: Guid = IID_IDROPTARGET;
+}
+
+fn format_etc(format: u32) -> FORMATETC {
+    FORMATETC {
+        cfFormat: format as u16,
+        ptd: ptr::null_mut(),
+        dwAspect: DVASPECT_CONTENT,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL,
+    }
+}
+
+/// The data being dragged over or dropped on a `DropTarget`.
+#[derive(Clone)]
+pub struct DropData {
+    obj: ComPtr<IDataObject>,
+}
+
+impl DropData {
+    unsafe fn from_raw(obj: *mut c_void) -> Option<DropData> {
+        ComPtr::from_raw_add_ref(obj).map(|obj| DropData { obj })
+    }
+
+    pub fn has_format(&self, format: u32) -> bool {
+        let fmt = format_etc(format);
+        unsafe { (self.obj.vtbl().QueryGetData)(self.obj.as_raw(), &fmt) == S_OK }
+    }
+
+    /// The data of a memory-based format.
+    pub fn data(&self, format: u32) -> Option<Vec<u8>> {
+        self.with_global(format, |h| unsafe { clipboard::read_global(h) })
+    }
+
+    pub fn text(&self) -> Option<String> {
+        self.data(CF_UNICODETEXT).map(|data| clipboard::decode_text(&data))
+    }
+
+    /// Files dragged from e.g. Explorer.
+    pub fn files(&self) -> Option<Vec<PathBuf>> {
+        self.with_global(CF_HDROP, |h| Some(unsafe { dragdrop::hdrop_files(h as _) }))
+    }
+
+    fn with_global<F, R>(&self, format: u32, f: F) -> Option<R>
+        where F: FnOnce(HGLOBAL) -> Option<R> {
+        let fmt = format_etc(format);
+        let mut medium = STGMEDIUM {
+            tymed: 0,
+            hGlobal: ptr::null_mut(),
+            pUnkForRelease: ptr::null_mut(),
+        };
+        unsafe {
+            if (self.obj.vtbl().GetData)(self.obj.as_raw(), &fmt, &mut medium) != S_OK {
+                return None;
+            }
+            let result = if medium.tymed == TYMED_HGLOBAL { f(medium.hGlobal) } else { None };
+            ReleaseStgMedium(&mut medium);
+            result
+        }
+    }
+}
+
+
+/// Handles OLE drops on a window; see the module documentation. Coordinates are
+/// client coordinates, and effects outside `allowed` are treated as `None`.
+pub trait DropTarget {
+    fn on_drag_enter(&self, _data: &DropData, _state: MouseState, _x: isize, _y: isize,
+                     _allowed: DropEffects) -> DropEffect {
+        DropEffect::None
+    }
+
+    /// Called as the drag moves over the window, and when the keys change.
+    fn on_drag_over(&self, _data: &DropData, _state: MouseState, _x: isize, _y: isize,
+                    _allowed: DropEffects) -> DropEffect {
+        DropEffect::None
+    }
+
+    fn on_drag_leave(&self) {
+    }
+
+    /// Returns the effect performed.
+    fn on_drop(&self, _data: &DropData, _state: MouseState, _x: isize, _y: isize,
+               _allowed: DropEffects) -> DropEffect {
+        DropEffect::None
+    }
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+enum DropEventKind {
+    Enter,
+    Over,
+    Leave,
+    Drop,
+}
+
+/// A drop target call on its way to the window impl.
+struct DropEvent {
+    kind: DropEventKind,
+    data: Option<DropData>,
+    state: MouseState,
+    point: (isize, isize),
+    allowed: DropEffects,
+    effect: DropEffect,
+}
+
+thread_local!(static PENDING_EVENT: RefCell<Option<DropEvent>> = RefCell::new(None));
+thread_local!(static DROP_TARGETS: RefCell<HashSet<Window>> = RefCell::new(HashSet::new()));
+
+static DROP_TARGET_MESSAGE: AtomicUsize = AtomicUsize::new(0);
+
+/// The registered message that delivers drop target calls to a window impl.
+pub fn drop_target_message() -> UINT {
+    message::register_message_cached(&DROP_TARGET_MESSAGE, "RustWindowsDropTarget")
+}
+
+/// Runs the pending drop target call on `target`. Called by `wnd_proc!` for
+/// `DROP_TARGET`.
+pub fn dispatch_drop_event<T: DropTarget + ?Sized>(target: &T) {
+    // Taken out while the handler runs, which may start a nested drag.
+    let mut event = match PENDING_EVENT.with(|e| e.borrow_mut().take()) {
+        Some(event) => event,
+        None => return,
+    };
+    let (x, y) = event.point;
+    let effect = match (event.kind, event.data.as_ref()) {
+        (DropEventKind::Enter, Some(data)) =>
+            target.on_drag_enter(data, event.state, x, y, event.allowed),
+        (DropEventKind::Over, Some(data)) =>
+            target.on_drag_over(data, event.state, x, y, event.allowed),
+        (DropEventKind::Drop, Some(data)) =>
+            target.on_drop(data, event.state, x, y, event.allowed),
+        (DropEventKind::Leave, _) => {
+            target.on_drag_leave();
+            DropEffect::None
+        }
+        (_, None) => DropEffect::None,
+    };
+    event.effect = if event.allowed.contains(effect) { effect } else { DropEffect::None };
+    PENDING_EVENT.with(|e| *e.borrow_mut() = Some(event));
+}
+
+struct DropTargetState {
+    wnd: Window,
+    data: RefCell<Option<DropData>>,
+}
+
+type DropTargetObject = ComObject<IDropTargetVtbl, DropTargetState>;
+
+static DROP_TARGET_VTBL: IDropTargetVtbl = IDropTargetVtbl {
+    QueryInterface: query_interface::<IDropTargetVtbl, DropTargetState>,
+    AddRef: add_ref::<IDropTargetVtbl, DropTargetState>,
+    Release: release::<IDropTargetVtbl, DropTargetState>,
+    DragEnter: drag_enter,
+    DragOver: drag_over,
+    DragLeave: drag_leave,
+    Drop: drag_drop,
+};
+
+impl DropTargetState {
+    // Sends the call to the window impl and returns the raw effect.
+    fn send(&self, kind: DropEventKind, keys: DWORD, pt: POINT, allowed: DWORD) -> DWORD {
+        let point = self.wnd.screen_to_client(pt.x as isize, pt.y as isize).unwrap_or((0, 0));
+        let event = DropEvent {
+            kind,
+            data: self.data.borrow().clone(),
+            state: MouseState::from_raw(keys & !MK_ALT, keys & MK_ALT != 0),
+            point,
+            allowed: DropEffects::from_raw(allowed),
+            effect: DropEffect::None,
+        };
+        PENDING_EVENT.with(|e| *e.borrow_mut() = Some(event));
+        self.wnd.send_message(drop_target_message(), 0, 0);
+        PENDING_EVENT.with(|e| e.borrow_mut().take()).map_or(0, |e| e.effect.to_raw())
+    }
+}
+
+unsafe extern "system" fn drag_enter(this: *mut c_void, data: *mut c_void, keys: DWORD, pt: POINT,
+                                     effect: *mut DWORD) -> HRESULT {
+    let target = DropTargetObject::state(this);
+    *target.data.borrow_mut() = DropData::from_raw(data);
+    *effect = target.send(DropEventKind::Enter, keys, pt, *effect);
+    S_OK
+}
+
+unsafe extern "system" fn drag_over(this: *mut c_void, keys: DWORD, pt: POINT,
+                                    effect: *mut DWORD) -> HRESULT {
+    let target = DropTargetObject::state(this);
+    *effect = target.send(DropEventKind::Over, keys, pt, *effect);
+    S_OK
+}
+
+unsafe extern "system" fn drag_leave(this: *mut c_void) -> HRESULT {
+    let target = DropTargetObject::state(this);
+    target.send(DropEventKind::Leave, 0, POINT { x: 0, y: 0 }, 0);
+    target.data.borrow_mut().take();
+    S_OK
+}
+
+unsafe extern "system" fn drag_drop(this: *mut c_void, data: *mut c_void, keys: DWORD, pt: POINT,
+                                    effect: *mut DWORD) -> HRESULT {
+    let target = DropTargetObject::state(this);
+    *target.data.borrow_mut() = DropData::from_raw(data);
+    *effect = target.send(DropEventKind::Drop, keys, pt, *effect);
+    target.data.borrow_mut().take();
+    S_OK
+}
+
+/// Forgets the drop target of a window that is being destroyed. Called from
+/// `main_wnd_proc` on `WM_NCDESTROY`.
+pub fn revoke_window(wnd: Window) {
+    if DROP_TARGETS.with(|t| t.borrow_mut().remove(&wnd)) {
+        unsafe { RevokeDragDrop(wnd.wnd) };
+    }
+}
+
+impl Window {
+    /// Makes the window accept OLE drops, which are passed to its `DropTarget`
+    /// implementation. OLE must be initialized on the thread (see `OleGuard`).
+    pub fn register_drop_target(&self) -> bool {
+        let state = DropTargetState { wnd: *self, data: RefCell::new(None) };
+        let obj: ComPtr<IDropTarget> = ComObject::create(&DROP_TARGET_VTBL, state);
+        // RegisterDragDrop keeps its own reference.
+        if unsafe { RegisterDragDrop(self.wnd, obj.as_raw()) } < 0 {
+            return false;
+        }
+        DROP_TARGETS.with(|t| t.borrow_mut().insert(*self));
+        true
+    }
+
+    pub fn revoke_drop_target(&self) -> bool {
+        let registered = DROP_TARGETS.with(|t| t.borrow_mut().remove(self));
+        registered && unsafe { RevokeDragDrop(self.wnd) } >= 0
+    }
+}
+
+/// Data to be dragged with `do_drag_drop`, in one or more formats.
+#[derive(Clone,Default)]
+pub struct DataObject {
+    formats: Vec<(u32, Vec<u8>)>,
+}
+
+impl DataObject {
+    pub fn new() -> DataObject {
+        Default::default()
+    }
+
+    /// Adds `CF_UNICODETEXT` text.
+    pub fn text(self, text: &str) -> DataObject {
+        self.data(CF_UNICODETEXT, clipboard::encode_text(text))
+    }
+
+    /// Adds a `CF_HDROP` file list, which Explorer accepts.
+    pub fn files<P: AsRef<Path>>(self, files: &[P]) -> DataObject {
+        self.data(CF_HDROP, clipboard::encode_files(files))
+    }
+
+    /// Adds data in a memory-based format, such as one from
+    /// `clipboard::register_format`, replacing earlier data in that format.
+    pub fn data(mut self, format: u32, data: Vec<u8>) -> DataObject {
+        self.formats.retain(|f| f.0 != format);
+        self.formats.push((format, data));
+        self
+    }
+
+    pub fn formats(&self) -> Vec<u32> {
+        self.formats.iter().map(|f| f.0).collect()
+    }
+}
+
+struct DataObjectState {
+    formats: RefCell<Vec<(u32, Vec<u8>)>>,
+}
+
+type DataObjectObject = ComObject<IDataObjectVtbl, DataObjectState>;
+
+static DATA_OBJECT_VTBL: IDataObjectVtbl = IDataObjectVtbl {
+    QueryInterface: query_interface::<IDataObjectVtbl, DataObjectState>,
+    AddRef: add_ref::<IDataObjectVtbl, DataObjectState>,
+    Release: release::<IDataObjectVtbl, DataObjectState>,
+    GetData: get_data,
+    GetDataHere: get_data_here,
+    QueryGetData: query_get_data,
+    GetCanonicalFormatEtc: get_canonical_format_etc,
+    SetData: set_data,
+    EnumFormatEtc: enum_format_etc,
+    DAdvise: d_advise,
+    DUnadvise: d_unadvise,
+    EnumDAdvise: enum_d_advise,
+};
+
+impl DataObjectState {
+    fn check(&self, fmt: &FORMATETC) -> HRESULT {
+        if fmt.dwAspect != DVASPECT_CONTENT {
+            return DV_E_FORMATETC;
+        }
+        if fmt.tymed & TYMED_HGLOBAL == 0 {
+            return DV_E_TYMED;
+        }
+        let format = fmt.cfFormat as u32;
+        if self.formats.borrow().iter().any(|f| f.0 == format) { S_OK } else { DV_E_FORMATETC }
+    }
+}
+
+unsafe extern "system" fn get_data(this: *mut c_void, fmt: *const FORMATETC,
+                                   medium: *mut STGMEDIUM) -> HRESULT {
+    let state = DataObjectObject::state(this);
+    let hr = state.check(&*fmt);
+    if hr != S_OK {
+        return hr;
+    }
+    let format = (*fmt).cfFormat as u32;
+    let formats = state.formats.borrow();
+    let data = match formats.iter().find(|f| f.0 == format) {
+        Some(f) => &f.1,
+        None => return DV_E_FORMATETC,
+    };
+    match clipboard::alloc_global(data) {
+        Some(handle) => {
+            *medium = STGMEDIUM {
+                tymed: TYMED_HGLOBAL,
+                hGlobal: handle,
+                pUnkForRelease: ptr::null_mut(),
+            };
+            S_OK
+        }
+        None => E_OUTOFMEMORY,
+    }
+}
+
+unsafe extern "system" fn get_data_here(_this: *mut c_void, _fmt: *const FORMATETC,
+                                        _medium: *mut STGMEDIUM) -> HRESULT {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn query_get_data(this: *mut c_void, fmt: *const FORMATETC) -> HRESULT {
+    DataObjectObject::state(this).check(&*fmt)
+}
+
+unsafe extern "system" fn get_canonical_format_etc(_this: *mut c_void, _fmt: *const FORMATETC,
+                                                   out: *mut FORMATETC) -> HRESULT {
+    (*out).ptd = ptr::null_mut();
+    DATA_S_SAMEFORMATETC
+}
+
+// Drop targets such as Explorer store extra formats on the source's data object
+// (e.g. the drop description), so memory-based data is accepted.
+unsafe extern "system" fn set_data(this: *mut c_void, fmt: *const FORMATETC,
+                                   medium: *mut STGMEDIUM, release: BOOL) -> HRESULT {
+    if (*fmt).tymed & TYMED_HGLOBAL == 0 || (*medium).tymed != TYMED_HGLOBAL {
+        return E_NOTIMPL;
+    }
+    let data = clipboard::read_global((*medium).hGlobal);
+    if release != 0 {
+        ReleaseStgMedium(medium);
+    }
+    let data = match data {
+        Some(data) => data,
+        None => return E_OUTOFMEMORY,
+    };
+    let format = (*fmt).cfFormat as u32;
+    let mut formats = DataObjectObject::state(this).formats.borrow_mut();
+    formats.retain(|f| f.0 != format);
+    formats.push((format, data));
+    S_OK
+}
+
+unsafe extern "system" fn enum_format_etc(this: *mut c_void, direction: DWORD,
+                                          out: *mut *mut c_void) -> HRESULT {
+    if direction != DATADIR_GET {
+        *out = ptr::null_mut();
+        return E_NOTIMPL;
+    }
+    let fmts: Vec<FORMATETC> = DataObjectObject::state(this).formats.borrow().iter()
+        .map(|f| format_etc(f.0))
+        .collect();
+    SHCreateStdEnumFmtEtc(fmts.len() as UINT, fmts.as_ptr(), out)
+}
+
+unsafe extern "system" fn d_advise(_this: *mut c_void, _fmt: *const FORMATETC, _advf: DWORD,
+                                   _sink: *mut c_void, _connection: *mut DWORD) -> HRESULT {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+unsafe extern "system" fn d_unadvise(_this: *mut c_void, _connection: DWORD) -> HRESULT {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+unsafe extern "system" fn enum_d_advise(_this: *mut c_void, _out: *mut *mut c_void) -> HRESULT {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+struct DropSourceState {
+    // The mouse buttons that started the drag, seen on the first call.
+    buttons: Cell<Option<DWORD>>,
+}
+
+static DROP_SOURCE_VTBL: IDropSourceVtbl = IDropSourceVtbl {
+    QueryInterface: query_interface::<IDropSourceVtbl, DropSourceState>,
+    AddRef: add_ref::<IDropSourceVtbl, DropSourceState>,
+    Release: release::<IDropSourceVtbl, DropSourceState>,
+    QueryContinueDrag: query_continue_drag,
+    GiveFeedback: give_feedback,
+};
+
+unsafe extern "system" fn query_continue_drag(this: *mut c_void, escape: BOOL, keys: DWORD)
+                                              -> HRESULT {
+    let state = ComObject::<IDropSourceVtbl, DropSourceState>::state(this);
+    let down = keys & MK_BUTTONS;
+    let start = match state.buttons.get() {
+        Some(start) => start,
+        None => {
+            state.buttons.set(Some(down));
+            down
+        }
+    };
+    if escape != 0 || down & !start != 0 {
+        // Escape, or another button pressed.
+        DRAGDROP_S_CANCEL
+    } else if start & !down != 0 || start == 0 {
+        DRAGDROP_S_DROP
+    } else {
+        S_OK
+    }
+}
+
+unsafe extern "system" fn give_feedback(_this: *mut c_void, _effect: DWORD) -> HRESULT {
+    DRAGDROP_S_USEDEFAULTCURSORS
+}
+
+/// Drags `data` until the mouse button that started the drag is released, and
+/// returns the effect the target performed. Call it when a drag gesture starts,
+/// e.g. on `LVN_BEGINDRAG`, with OLE initialized on the thread.
+///
+/// `DropEffect::None` means the drag was cancelled or refused. After
+/// `DropEffect::Move` the source should remove the dragged items.
+pub fn do_drag_drop(data: &DataObject, allowed: DropEffects) -> DropEffect {
+    let state = DataObjectState { formats: RefCell::new(data.formats.clone()) };
+    let data_obj: ComPtr<IDataObject> = ComObject::create(&DATA_OBJECT_VTBL, state);
+    let state = DropSourceState { buttons: Cell::new(None) };
+    let source: ComPtr<IDropSource> = ComObject::create(&DROP_SOURCE_VTBL, state);
+
+    let mut effect = 0;
+    let hr = unsafe {
+        DoDragDrop(data_obj.as_raw(), source.as_raw(), allowed.to_raw(), &mut effect)
+    };
+    if hr == DRAGDROP_S_DROP { DropEffect::from_raw(effect) } else { DropEffect::None }
+}
+
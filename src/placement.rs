@@ -0,0 +1,602 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Window placement: the restored rectangle and show state of a top-level
+//! window, as used by `Window::placement` and `Window::set_placement`.
+//!
+//! Placements have a one-line text form, so that they can be saved and restored:
+//!
+//! ```text
+//! show=maximized normal=100,80,900,680 min=-1,-1 max=-1,-1 restore_maximized=0
+//! ```
+//!
+//! `WindowState` adds the monitor the window was on, and can move a saved window
+//! back onto the screen when the monitor layout has changed since:
+//!
+//! ```ignore
+//! // On startup.
+//! if let Ok(state) = WindowState::load(&path) {
+//!     main.restore_state(&state);
+//! }
+//! // On WM_CLOSE.
+//! if let Some(state) = main.state() {
+//!     let _ = state.save(&path);
+//! }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[cfg(windows)] use std::mem;
+
+#[cfg(windows)] use winapi::{LONG, POINT, RECT, UINT, WINDOWPLACEMENT};
+
+#[cfg(windows)] use monitor::Monitor;
+#[cfg(windows)] use window::Window;
+
+/// A rectangle in pixels, `right` and `bottom` exclusive.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct Rect {
+    pub left: isize,
+    pub top: isize,
+    pub right: isize,
+    pub bottom: isize,
+}
+
+impl Rect {
+    pub fn new(left: isize, top: isize, right: isize, bottom: isize) -> Rect {
+        Rect { left, top, right, bottom }
+    }
+
+    pub fn width(&self) -> isize {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> isize {
+        self.bottom - self.top
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0
+    }
+
+    pub fn offset(&self, dx: isize, dy: isize) -> Rect {
+        Rect::new(self.left + dx, self.top + dy, self.right + dx, self.bottom + dy)
+    }
+
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    /// The overlapping part, if any.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let r = Rect::new(self.left.max(other.left), self.top.max(other.top),
+                          self.right.min(other.right), self.bottom.min(other.bottom));
+        if r.is_empty() { None } else { Some(r) }
+    }
+
+    #[cfg(windows)]
+    pub fn from_raw(r: &RECT) -> Rect {
+        Rect::new(r.left as isize, r.top as isize, r.right as isize, r.bottom as isize)
+    }
+
+    #[cfg(windows)]
+    pub fn to_raw(&self) -> RECT {
+        RECT {
+            left: self.left as LONG,
+            top: self.top as LONG,
+            right: self.right as LONG,
+            bottom: self.bottom as LONG,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Rect> {
+        let v = parse_numbers(s, 4)?;
+        Some(Rect::new(v[0], v[1], v[2], v[3]))
+    }
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum ShowState {
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+impl ShowState {
+    /// Decodes a `SW_*` show command. Commands that show the window normally,
+    /// possibly without activating it, map to `Normal`.
+    pub fn from_raw(cmd: u32) -> ShowState {
+        match cmd {
+            2 | 6 | 7 | 11 => ShowState::Minimized, // SW_SHOWMINIMIZED, SW_MINIMIZE, ...
+            3 => ShowState::Maximized, // SW_SHOWMAXIMIZED
+            _ => ShowState::Normal,
+        }
+    }
+
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            ShowState::Normal => 1, // SW_SHOWNORMAL
+            ShowState::Minimized => 2, // SW_SHOWMINIMIZED
+            ShowState::Maximized => 3, // SW_SHOWMAXIMIZED
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            ShowState::Normal => "normal",
+            ShowState::Minimized => "minimized",
+            ShowState::Maximized => "maximized",
+        }
+    }
+
+    fn parse(s: &str) -> Option<ShowState> {
+        match s {
+            "normal" => Some(ShowState::Normal),
+            "minimized" => Some(ShowState::Minimized),
+            "maximized" => Some(ShowState::Maximized),
+            _ => None,
+        }
+    }
+}
+
+// WINDOWPLACEMENT flags.
+#[cfg(windows)] const WPF_SETMINPOSITION: u32 = 0x0001;
+#[cfg(windows)] const WPF_RESTORETOMAXIMIZED: u32 = 0x0002;
+
+/// The contents of `WINDOWPLACEMENT`.
+///
+/// `normal` is the restored rectangle in workspace coordinates: relative to the
+/// work area of the primary monitor, which differs from screen coordinates when
+/// the taskbar is at the top or left.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub struct WindowPlacement {
+    pub show: ShowState,
+    pub normal: Rect,
+    /// The position of the minimized window; `(-1, -1)` lets the system choose.
+    pub min_position: (isize, isize),
+    /// The position of the maximized window; `(-1, -1)` lets the system choose.
+    pub max_position: (isize, isize),
+    /// A minimized window restores to maximized.
+    pub restore_maximized: bool,
+}
+
+impl WindowPlacement {
+    pub fn new(show: ShowState, normal: Rect) -> WindowPlacement {
+        WindowPlacement {
+            show,
+            normal,
+            min_position: (-1, -1),
+            max_position: (-1, -1),
+            restore_maximized: false,
+        }
+    }
+
+    /// Parses the text form written by `Display`. `show` and `normal` are
+    /// required; other fields default as in `new`, and unknown keys are ignored.
+    pub fn parse(s: &str) -> Option<WindowPlacement> {
+        let mut show = None;
+        let mut normal = None;
+        let mut placement = WindowPlacement::new(ShowState::Normal, Rect::default());
+        for field in s.split_whitespace() {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "show" => show = Some(ShowState::parse(value)?),
+                "normal" => normal = Some(Rect::parse(value)?),
+                "min" => placement.min_position = parse_point(value)?,
+                "max" => placement.max_position = parse_point(value)?,
+                "restore_maximized" => placement.restore_maximized = value == "1",
+                _ => {}
+            }
+        }
+        placement.show = show?;
+        placement.normal = normal?;
+        Some(placement)
+    }
+
+    #[cfg(windows)]
+    pub fn from_raw(wp: &WINDOWPLACEMENT) -> WindowPlacement {
+        WindowPlacement {
+            show: ShowState::from_raw(wp.showCmd as u32),
+            normal: Rect::from_raw(&wp.rcNormalPosition),
+            min_position: (wp.ptMinPosition.x as isize, wp.ptMinPosition.y as isize),
+            max_position: (wp.ptMaxPosition.x as isize, wp.ptMaxPosition.y as isize),
+            restore_maximized: wp.flags as u32 & WPF_RESTORETOMAXIMIZED != 0,
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn to_raw(&self) -> WINDOWPLACEMENT {
+        let mut flags = 0;
+        if self.min_position != (-1, -1) { flags |= WPF_SETMINPOSITION; }
+        if self.restore_maximized { flags |= WPF_RESTORETOMAXIMIZED; }
+        let point = |p: (isize, isize)| POINT { x: p.0 as LONG, y: p.1 as LONG };
+        WINDOWPLACEMENT {
+            length: mem::size_of::<WINDOWPLACEMENT>() as UINT,
+            flags: flags as UINT,
+            showCmd: self.show.to_raw() as UINT,
+            ptMinPosition: point(self.min_position),
+            ptMaxPosition: point(self.max_position),
+            rcNormalPosition: self.normal.to_raw(),
+        }
+    }
+}
+
+impl fmt::Display for WindowPlacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let r = &self.normal;
+        write!(f, "show={} normal={},{},{},{} min={},{} max={},{} restore_maximized={}",
+               self.show.name(), r.left, r.top, r.right, r.bottom,
+               self.min_position.0, self.min_position.1,
+               self.max_position.0, self.max_position.1,
+               self.restore_maximized as u8)
+    }
+}
+
+/// A monitor as seen by `WindowState`: its device name, and its full and work
+/// rectangles in screen coordinates.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct MonitorLayout {
+    pub device_name: String,
+    pub rect: Rect,
+    pub work: Rect,
+    pub primary: bool,
+}
+
+impl MonitorLayout {
+    /// The monitors currently attached.
+    #[cfg(windows)]
+    pub fn current() -> Vec<MonitorLayout> {
+        Monitor::all().iter().filter_map(|m| m.info()).map(|info| info.layout()).collect()
+    }
+}
+
+/// Which monitor a saved window was on: its device name and where it was in the
+/// virtual screen.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct MonitorId {
+    pub device_name: String,
+    pub rect: Rect,
+}
+
+/// A window's placement and monitor, to be saved when the window closes and
+/// restored the next time it opens.
+///
+/// The text form is the placement on the first line, and the monitor rectangle
+/// and device name on the second:
+///
+/// ```text
+/// show=normal normal=100,80,900,680 min=-1,-1 max=-1,-1 restore_maximized=0
+/// monitor=1920,0,3840,1080 \\.\DISPLAY2
+/// ```
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct WindowState {
+    pub placement: WindowPlacement,
+    pub monitor: Option<MonitorId>,
+}
+
+impl WindowState {
+    pub fn new(placement: WindowPlacement, monitor: Option<MonitorId>) -> WindowState {
+        WindowState { placement, monitor }
+    }
+
+    /// Parses the text form written by `Display`. The monitor line is optional.
+    pub fn parse(s: &str) -> Option<WindowState> {
+        let mut lines = s.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+        let placement = WindowPlacement::parse(lines.next()?)?;
+        let monitor = match lines.next() {
+            Some(line) => {
+                let value = line.strip_prefix("monitor=")?;
+                let (rect, name) = match value.find(' ') {
+                    Some(i) => (&value[..i], value[i + 1..].trim()),
+                    None => (value, ""),
+                };
+                Some(MonitorId { device_name: name.to_string(), rect: Rect::parse(rect)? })
+            }
+            None => None,
+        };
+        Some(WindowState { placement, monitor })
+    }
+
+    /// Reads a state written by `save`. Text that does not parse is reported as
+    /// `InvalidData`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<WindowState> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        WindowState::parse(&text).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "invalid window state")
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", self)
+    }
+
+    /// Adjusts the state to the monitors now attached, so that the window comes
+    /// back where the user can see it.
+    ///
+    /// A window whose monitor is still there keeps its position, following the
+    /// monitor if it has moved in the virtual screen. A window whose monitor has
+    /// gone stays put if another monitor shows part of it, and otherwise moves to
+    /// the same spot on the primary monitor. Either way the restored rectangle is
+    /// then shrunk and shifted to fit the work area of its new monitor. Minimized
+    /// and maximized positions are reset when the monitor changes.
+    ///
+    /// `monitors` would normally be `MonitorLayout::current()`; with no monitors
+    /// the state is returned unchanged.
+    pub fn clamp_to(&self, monitors: &[MonitorLayout]) -> WindowState {
+        let primary = match monitors.iter().find(|m| m.primary).or_else(|| monitors.first()) {
+            Some(primary) => primary,
+            None => return self.clone(),
+        };
+
+        // `normal` is in workspace coordinates, relative to the primary work area.
+        let (dx, dy) = (primary.work.left - primary.rect.left, primary.work.top - primary.rect.top);
+        let screen = self.placement.normal.offset(dx, dy);
+
+        let saved = self.monitor.as_ref().and_then(|id| {
+            monitors.iter().find(|m| m.device_name == id.device_name).map(|m| (m, id))
+        });
+        let (target, shift) = match saved {
+            Some((m, id)) => (m, (m.rect.left - id.rect.left, m.rect.top - id.rect.top)),
+            None => match most_overlapping(&screen, monitors) {
+                Some(m) => (m, (0, 0)),
+                None => {
+                    let shift = match self.monitor {
+                        Some(ref id) => (primary.rect.left - id.rect.left,
+                                         primary.rect.top - id.rect.top),
+                        None => (0, 0),
+                    };
+                    (primary, shift)
+                }
+            },
+        };
+
+        let moved = match self.monitor {
+            Some(ref id) => id.device_name != target.device_name || id.rect != target.rect,
+            None => true,
+        };
+        let mut placement = self.placement;
+        placement.normal = fit_rect(&screen.offset(shift.0, shift.1), &target.work).offset(-dx, -dy);
+        if moved {
+            placement.min_position = (-1, -1);
+            placement.max_position = (-1, -1);
+        }
+        WindowState {
+            placement,
+            monitor: Some(MonitorId { device_name: target.device_name.clone(), rect: target.rect }),
+        }
+    }
+}
+
+impl fmt::Display for WindowState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.placement)?;
+        if let Some(ref id) = self.monitor {
+            let r = &id.rect;
+            write!(f, "\nmonitor={},{},{},{} {}", r.left, r.top, r.right, r.bottom, id.device_name)?;
+        }
+        Ok(())
+    }
+}
+
+fn most_overlapping<'a>(r: &Rect, monitors: &'a [MonitorLayout]) -> Option<&'a MonitorLayout> {
+    monitors.iter()
+        .filter_map(|m| r.intersect(&m.rect).map(|i| (i.width() * i.height(), m)))
+        .max_by_key(|e| e.0)
+        .map(|e| e.1)
+}
+
+/// Shrinks `r` to fit `area`, then moves it into `area`.
+fn fit_rect(r: &Rect, area: &Rect) -> Rect {
+    let width = r.width().min(area.width()).max(0);
+    let height = r.height().min(area.height()).max(0);
+    let left = r.left.min(area.right - width).max(area.left);
+    let top = r.top.min(area.bottom - height).max(area.top);
+    Rect::new(left, top, left + width, top + height)
+}
+
+#[cfg(windows)]
+impl Window {
+    /// The window's placement and the monitor it is on, for `WindowState::save`.
+    pub fn state(&self) -> Option<WindowState> {
+        let placement = self.placement()?;
+        let monitor = self.monitor().and_then(|m| m.info()).map(|info| {
+            MonitorId { device_name: info.device_name, rect: info.rect }
+        });
+        Some(WindowState { placement, monitor })
+    }
+
+    /// Places and shows the window as saved in `state`, clamped to the current
+    /// monitors. A window saved minimized comes back restored, or maximized if it
+    /// was maximized before being minimized.
+    pub fn restore_state(&self, state: &WindowState) -> bool {
+        let mut placement = state.clamp_to(&MonitorLayout::current()).placement;
+        if placement.show == ShowState::Minimized {
+            placement.show = if placement.restore_maximized {
+                ShowState::Maximized
+            } else {
+                ShowState::Normal
+            };
+        }
+        self.set_placement(&placement)
+    }
+}
+
+fn parse_numbers(s: &str, count: usize) -> Option<Vec<isize>> {
+    let v = s.split(',').map(|n| n.trim().parse().ok()).collect::<Option<Vec<isize>>>()?;
+    if v.len() == count { Some(v) } else { None }
+}
+
+fn parse_point(s: &str) -> Option<(isize, isize)> {
+    let v = parse_numbers(s, 2)?;
+    Some((v[0], v[1]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MonitorId, MonitorLayout, Rect, ShowState, WindowPlacement, WindowState};
+
+    fn monitor(name: &str, rect: Rect, work: Rect, primary: bool) -> MonitorLayout {
+        MonitorLayout { device_name: name.to_string(), rect, work, primary }
+    }
+
+    // A primary monitor with the taskbar on the left, and a second one to its right.
+    fn two_monitors() -> Vec<MonitorLayout> {
+        vec![monitor("\\\\.\\DISPLAY1", Rect::new(0, 0, 1920, 1080), Rect::new(40, 0, 1920, 1080), true),
+             monitor("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), Rect::new(1920, 0, 3200, 1000), false)]
+    }
+
+    fn state_on(name: &str, rect: Rect, show: ShowState, normal: Rect) -> WindowState {
+        let mut placement = WindowPlacement::new(show, normal);
+        placement.max_position = (-8, -8);
+        WindowState::new(placement, Some(MonitorId { device_name: name.to_string(), rect }))
+    }
+
+    #[test]
+    fn test_rect() {
+        let r = Rect::new(10, 20, 110, 70);
+        assert_eq!((r.width(), r.height()), (100, 50));
+        assert!(r.contains(10, 20));
+        assert!(!r.contains(110, 20));
+        assert_eq!(r.offset(-10, 5), Rect::new(0, 25, 100, 75));
+        assert_eq!(r.intersect(&Rect::new(100, 0, 200, 30)), Some(Rect::new(100, 20, 110, 30)));
+        assert_eq!(r.intersect(&Rect::new(110, 0, 200, 30)), None);
+        assert!(Rect::default().is_empty());
+    }
+
+    #[test]
+    fn test_show_state() {
+        assert_eq!(ShowState::from_raw(3), ShowState::Maximized);
+        assert_eq!(ShowState::from_raw(6), ShowState::Minimized);
+        assert_eq!(ShowState::from_raw(8), ShowState::Normal);
+        for s in [ShowState::Normal, ShowState::Minimized, ShowState::Maximized].iter() {
+            assert_eq!(ShowState::from_raw(s.to_raw()), *s);
+        }
+    }
+
+    #[test]
+    fn test_placement_text() {
+        let mut p = WindowPlacement::new(ShowState::Maximized, Rect::new(100, 80, 900, 680));
+        p.restore_maximized = true;
+        p.min_position = (-32000, -32000);
+        let s = p.to_string();
+        assert_eq!(s, "show=maximized normal=100,80,900,680 min=-32000,-32000 max=-1,-1 \
+                       restore_maximized=1");
+        assert_eq!(WindowPlacement::parse(&s), Some(p));
+
+        let p = WindowPlacement::parse("normal=-5,0,10,20 show=normal extra=1").unwrap();
+        assert_eq!(p, WindowPlacement::new(ShowState::Normal, Rect::new(-5, 0, 10, 20)));
+
+        assert!(WindowPlacement::parse("").is_none());
+        assert!(WindowPlacement::parse("show=normal").is_none());
+        assert!(WindowPlacement::parse("show=big normal=0,0,1,1").is_none());
+        assert!(WindowPlacement::parse("show=normal normal=0,0,1").is_none());
+        assert!(WindowPlacement::parse("show=normal normal=0,0,1,1 min").is_none());
+    }
+
+    #[test]
+    fn test_state_text() {
+        let state = state_on("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), ShowState::Normal,
+                             Rect::new(2000, 100, 2600, 500));
+        let s = state.to_string();
+        assert_eq!(s, "show=normal normal=2000,100,2600,500 min=-1,-1 max=-8,-8 restore_maximized=0\n\
+                       monitor=1920,0,3200,1024 \\\\.\\DISPLAY2");
+        assert_eq!(WindowState::parse(&s), Some(state.clone()));
+        assert_eq!(WindowState::parse(&format!("\n{}\n\n", s)), Some(state));
+
+        let named = state_on("My Display", Rect::new(0, 0, 10, 10), ShowState::Normal, Rect::new(0, 0, 5, 5));
+        assert_eq!(WindowState::parse(&named.to_string()), Some(named));
+
+        let p = WindowPlacement::new(ShowState::Maximized, Rect::new(0, 0, 5, 5));
+        assert_eq!(WindowState::parse(&p.to_string()), Some(WindowState::new(p, None)));
+
+        assert!(WindowState::parse("").is_none());
+        assert!(WindowState::parse(&format!("{}\nscreen=0,0,1,1 x", p)).is_none());
+        assert!(WindowState::parse(&format!("{}\nmonitor=0,0,1 x", p)).is_none());
+    }
+
+    #[test]
+    fn test_state_file() {
+        let path = ::std::env::temp_dir().join(format!("rust-windows-state-{}.txt", ::std::process::id()));
+        let state = state_on("\\\\.\\DISPLAY1", Rect::new(0, 0, 1920, 1080), ShowState::Maximized,
+                             Rect::new(10, 20, 30, 40));
+        state.save(&path).unwrap();
+        assert_eq!(WindowState::load(&path).unwrap(), state);
+
+        ::std::fs::write(&path, "garbage").unwrap();
+        assert_eq!(WindowState::load(&path).unwrap_err().kind(), ::std::io::ErrorKind::InvalidData);
+        ::std::fs::remove_file(&path).unwrap();
+        assert!(WindowState::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_clamp_unchanged_layout() {
+        let monitors = two_monitors();
+        // Workspace coordinates: 40 pixels left of the screen position.
+        let state = state_on("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), ShowState::Normal,
+                             Rect::new(2000, 100, 2600, 500));
+        assert_eq!(state.clamp_to(&monitors), state);
+        assert_eq!(state.clamp_to(&[]), state);
+    }
+
+    #[test]
+    fn test_clamp_monitor_moved() {
+        // DISPLAY2 is now left of the primary monitor.
+        let mut monitors = two_monitors();
+        monitors[1].rect = Rect::new(-1280, 0, 0, 1024);
+        monitors[1].work = Rect::new(-1280, 0, 0, 1000);
+        let state = state_on("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), ShowState::Normal,
+                             Rect::new(2000, 100, 2600, 500));
+        let clamped = state.clamp_to(&monitors);
+        assert_eq!(clamped.placement.normal, Rect::new(-1200, 100, -600, 500));
+        assert_eq!(clamped.placement.max_position, (-1, -1));
+        assert_eq!(clamped.monitor.unwrap().rect, Rect::new(-1280, 0, 0, 1024));
+    }
+
+    #[test]
+    fn test_clamp_monitor_gone() {
+        let monitors = vec![two_monitors().remove(0)];
+        let state = state_on("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), ShowState::Maximized,
+                             Rect::new(2000, 100, 2600, 500));
+        let clamped = state.clamp_to(&monitors);
+        // Same offset from the monitor's corner, now on the primary monitor.
+        assert_eq!(clamped.placement.normal, Rect::new(80, 100, 680, 500));
+        assert_eq!(clamped.placement.show, ShowState::Maximized);
+        assert_eq!(clamped.monitor, Some(MonitorId {
+            device_name: "\\\\.\\DISPLAY1".to_string(),
+            rect: Rect::new(0, 0, 1920, 1080),
+        }));
+
+        // Straddling both monitors: the part on the primary one keeps it there.
+        let state = state_on("\\\\.\\DISPLAY2", Rect::new(1920, 0, 3200, 1024), ShowState::Normal,
+                             Rect::new(1700, 100, 2300, 500));
+        assert_eq!(state.clamp_to(&monitors).placement.normal, Rect::new(1280, 100, 1880, 500));
+    }
+
+    #[test]
+    fn test_clamp_fits_work_area() {
+        let monitors = two_monitors();
+        // Larger than the work area, and partly above the screen.
+        let state = state_on("\\\\.\\DISPLAY1", Rect::new(0, 0, 1920, 1080), ShowState::Normal,
+                             Rect::new(-100, -50, 2000, 1000));
+        assert_eq!(state.clamp_to(&monitors).placement.normal, Rect::new(0, 0, 1880, 1050));
+
+        // No saved monitor, and off every screen.
+        let state = WindowState::new(WindowPlacement::new(ShowState::Normal, Rect::new(5000, 5000, 5400, 5300)),
+                                     None);
+        let clamped = state.clamp_to(&monitors);
+        assert_eq!(clamped.placement.normal, Rect::new(1480, 780, 1880, 1080));
+        assert_eq!(clamped.monitor.unwrap().device_name, "\\\\.\\DISPLAY1");
+    }
+}
@@ -13,7 +13,7 @@ use std;
 use user32;
 use winapi::{HANDLE, UINT, c_int};
 
-use wchar::ToCU16Str;
+use wchar::WideCString;
 use instance::Instance;
 
 pub trait ToHandle {
@@ -80,7 +80,7 @@ impl MenuResource {
         where F: FnOnce(*const u16) -> T {
         match *self {
             MenuResource::MenuName(ref s) => {
-                let u = &s.to_c_u16();
+                let u = WideCString::from_str_truncate(s);
                 f(u.as_ptr())
             }
             MenuResource::MenuId(id) => unsafe { f(std::mem::transmute(id)) },
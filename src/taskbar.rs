@@ -0,0 +1,979 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Taskbar buttons: progress, overlay icons, thumbnail toolbars and jump lists.
+//!
+//! A window's taskbar button can be changed once the taskbar has announced it,
+//! which `OnTaskbar::on_taskbar_button_created` reports:
+//!
+//! ```ignore
+//! wnd_proc!(MainFrame, win, WM_CREATE, TASKBAR, ANY);
+//!
+//! impl OnTaskbar for MainFrame {
+//!     fn on_taskbar_button_created(&self) {
+//!         let taskbar = Taskbar::new(&self.win).unwrap();
+//!         taskbar.add_thumb_buttons(&[ThumbButton::new(CMD_PAUSE, &pause_icon, "Pause")]);
+//!         *self.taskbar.borrow_mut() = Some(taskbar);
+//!     }
+//!
+//!     fn on_thumb_button(&self, id: u16) { ... }
+//! }
+//!
+//! // As the work goes on.
+//! taskbar.set_progress(done, total);
+//! ```
+//!
+//! Jump lists are built and committed once, e.g. on startup:
+//!
+//! ```ignore
+//! let exe = env::current_exe().unwrap();
+//! JumpList::new()
+//!     .known_category(KnownCategory::Recent)
+//!     .task(ShellLink::new("New window", &exe).args(&["--new-window"]))
+//!     .commit()?;
+//! ```
+//!
+//! COM must be initialized on the thread (see `com::ComGuard`).
+
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)] use std::mem;
+#[cfg(windows)] use std::ptr;
+#[cfg(windows)] use std::sync::atomic::AtomicUsize;
+
+#[cfg(windows)] use user32;
+#[cfg(windows)] use winapi::{DWORD, FLASHWINFO, HICON, HRESULT, HWND, UINT, c_int, c_void};
+
+#[cfg(windows)] use com;
+#[cfg(windows)] use com::{AddRefFn, ComPtr, Guid, Interface, QueryInterfaceFn};
+#[cfg(windows)] use error::{Error, Result, E_INVALIDARG};
+#[cfg(windows)] use message;
+#[cfg(windows)] use resource::Image;
+#[cfg(windows)] use wchar::{WideCString, to_c_u16_buf};
+#[cfg(windows)] use window::Window;
+
+// THBN_CLICKED, in the high word of a thumbnail button's WM_COMMAND.
+const THBN_CLICKED: usize = 0x1800;
+
+/// Decodes the WPARAM of a `WM_COMMAND` sent by a thumbnail toolbar button into
+/// the button id.
+pub fn thumb_button_clicked(w: usize) -> Option<u16> {
+    if (w >> 16) & 0xFFFF == THBN_CLICKED { Some(w as u16) } else { None }
+}
+
+/// Quotes arguments into a command line that `CommandLineToArgvW`, and so the
+/// Rust runtime, splits back into the same arguments.
+pub fn quote_arguments<S: AsRef<str>>(args: &[S]) -> String {
+    let mut line = String::new();
+    for arg in args {
+        let arg = arg.as_ref();
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        if !arg.is_empty() && !arg.contains(&[' ', '\t', '"'][..]) {
+            line.push_str(arg);
+            continue;
+        }
+        line.push('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    // Backslashes before a quote are escaped, as is the quote.
+                    line.extend((0..backslashes * 2 + 1).map(|_| '\\'));
+                    line.push('"');
+                    backslashes = 0;
+                }
+                _ => {
+                    line.extend((0..backslashes).map(|_| '\\'));
+                    line.push(c);
+                    backslashes = 0;
+                }
+            }
+        }
+        // Backslashes before the closing quote are escaped too.
+        line.extend((0..backslashes * 2).map(|_| '\\'));
+        line.push('"');
+    }
+    line
+}
+
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum ProgressState {
+    /// No progress shown.
+    None,
+    /// A pulsing bar, for work of unknown length.
+    Indeterminate,
+    Normal,
+    /// A red bar.
+    Error,
+    /// A yellow bar.
+    Paused,
+}
+
+impl ProgressState {
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            ProgressState::None => 0, // TBPF_NOPROGRESS
+            ProgressState::Indeterminate => 1, // TBPF_INDETERMINATE
+            ProgressState::Normal => 2, // TBPF_NORMAL
+            ProgressState::Error => 4, // TBPF_ERROR
+            ProgressState::Paused => 8, // TBPF_PAUSED
+        }
+    }
+}
+
+/// A shortcut in a jump list, which starts `path` when clicked.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct ShellLink {
+    pub title: String,
+    pub path: PathBuf,
+    /// The command line passed to `path`; see `args`.
+    pub arguments: String,
+    /// The tooltip.
+    pub description: String,
+    /// A file holding the icon and the icon's index, or `None` for the icon of
+    /// `path`.
+    pub icon: Option<(PathBuf, i32)>,
+    pub working_dir: Option<PathBuf>,
+}
+
+impl ShellLink {
+    pub fn new<P: AsRef<Path>>(title: &str, path: P) -> ShellLink {
+        ShellLink {
+            title: title.to_string(),
+            path: path.as_ref().to_path_buf(),
+            arguments: String::new(),
+            description: String::new(),
+            icon: None,
+            working_dir: None,
+        }
+    }
+
+    /// Sets the arguments, quoted as needed.
+    pub fn args<S: AsRef<str>>(mut self, args: &[S]) -> ShellLink {
+        self.arguments = quote_arguments(args);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> ShellLink {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn icon<P: AsRef<Path>>(mut self, path: P, index: i32) -> ShellLink {
+        self.icon = Some((path.as_ref().to_path_buf(), index));
+        self
+    }
+
+    pub fn working_dir<P: AsRef<Path>>(mut self, dir: P) -> ShellLink {
+        self.working_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// An entry of a jump list's task list.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub enum JumpItem {
+    Link(ShellLink),
+    /// A line between tasks.
+    Separator,
+}
+
+/// The jump list categories kept by the shell.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum KnownCategory {
+    /// Files recently opened, as passed to `add_to_recent_docs` or opened through
+    /// the common file dialogs.
+    Recent,
+    Frequent,
+}
+
+impl KnownCategory {
+    pub fn to_raw(&self) -> u32 {
+        match *self {
+            KnownCategory::Frequent => 1, // KDC_FREQUENT
+            KnownCategory::Recent => 2, // KDC_RECENT
+        }
+    }
+}
+
+/// The jump list of the application's taskbar button and Start menu entry.
+#[derive(Clone,Default,PartialEq,Eq,Debug)]
+pub struct JumpList {
+    app_id: Option<String>,
+    known: Vec<KnownCategory>,
+    categories: Vec<(String, Vec<ShellLink>)>,
+    tasks: Vec<JumpItem>,
+}
+
+impl JumpList {
+    pub fn new() -> JumpList {
+        Default::default()
+    }
+
+    /// The list of a specific application id (see `set_app_id`) rather than the
+    /// process's.
+    pub fn app_id(mut self, app_id: &str) -> JumpList {
+        self.app_id = Some(app_id.to_string());
+        self
+    }
+
+    pub fn known_category(mut self, category: KnownCategory) -> JumpList {
+        if !self.known.contains(&category) {
+            self.known.push(category);
+        }
+        self
+    }
+
+    /// Adds a category of links, after the known categories. Links the user has
+    /// removed from the list are left out when committing.
+    pub fn category(mut self, name: &str, links: Vec<ShellLink>) -> JumpList {
+        self.categories.push((name.to_string(), links));
+        self
+    }
+
+    /// Adds a link to the "Tasks" category, which lists the application's
+    /// common commands.
+    pub fn task(mut self, link: ShellLink) -> JumpList {
+        self.tasks.push(JumpItem::Link(link));
+        self
+    }
+
+    pub fn separator(mut self) -> JumpList {
+        self.tasks.push(JumpItem::Separator);
+        self
+    }
+
+    /// The tasks, without leading, trailing or repeated separators.
+    pub fn tasks(&self) -> Vec<JumpItem> {
+        let mut tasks: Vec<JumpItem> = Vec::new();
+        for item in &self.tasks {
+            let after_separator = match tasks.last() {
+                Some(last) => *last == JumpItem::Separator,
+                None => true,
+            };
+            if *item == JumpItem::Separator && after_separator {
+                continue;
+            }
+            tasks.push(item.clone());
+        }
+        if tasks.last() == Some(&JumpItem::Separator) {
+            tasks.pop();
+        }
+        tasks
+    }
+
+    /// The custom categories, without empty ones.
+    pub fn categories(&self) -> Vec<(String, Vec<ShellLink>)> {
+        self.categories.iter().filter(|c| !c.1.is_empty()).cloned().collect()
+    }
+}
+
+// Methods that are not called are left untyped.
+#[cfg(windows)]
+type Unused = *const c_void;
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct ITaskbarList3Vtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    HrInit: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+    AddTab: Unused,
+    DeleteTab: Unused,
+    ActivateTab: Unused,
+    SetActiveAlt: Unused,
+    MarkFullscreenWindow: Unused,
+    SetProgressValue: unsafe extern "system" fn(*mut c_void, HWND, u64, u64) -> HRESULT,
+    SetProgressState: unsafe extern "system" fn(*mut c_void, HWND, DWORD) -> HRESULT,
+    RegisterTab: Unused,
+    UnregisterTab: Unused,
+    SetTabOrder: Unused,
+    SetTabActive: Unused,
+    ThumbBarAddButtons: unsafe extern "system" fn(*mut c_void, HWND, UINT, *mut THUMBBUTTON) -> HRESULT,
+    ThumbBarUpdateButtons: unsafe extern "system" fn(*mut c_void, HWND, UINT, *mut THUMBBUTTON)
+                                                     -> HRESULT,
+    ThumbBarSetImageList: Unused,
+    SetOverlayIcon: unsafe extern "system" fn(*mut c_void, HWND, HICON, *const u16) -> HRESULT,
+    SetThumbnailTooltip: unsafe extern "system" fn(*mut c_void, HWND, *const u16) -> HRESULT,
+    SetThumbnailClip: Unused,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct ICustomDestinationListVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    SetAppID: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    BeginList: unsafe extern "system" fn(*mut c_void, *mut UINT, *const Guid, *mut *mut c_void)
+                                         -> HRESULT,
+    AppendCategory: unsafe extern "system" fn(*mut c_void, *const u16, *mut c_void) -> HRESULT,
+    AppendKnownCategory: unsafe extern "system" fn(*mut c_void, c_int) -> HRESULT,
+    AddUserTasks: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+    CommitList: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+    GetRemovedDestinations: Unused,
+    DeleteList: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    AbortList: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IObjectArrayVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    GetCount: unsafe extern "system" fn(*mut c_void, *mut UINT) -> HRESULT,
+    GetAt: unsafe extern "system" fn(*mut c_void, UINT, *const Guid, *mut *mut c_void) -> HRESULT,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IObjectCollectionVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    GetCount: Unused,
+    GetAt: Unused,
+    AddObject: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+    AddFromArray: Unused,
+    RemoveObjectAt: Unused,
+    Clear: Unused,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IShellLinkWVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    GetPath: unsafe extern "system" fn(*mut c_void, *mut u16, c_int, *mut c_void, DWORD) -> HRESULT,
+    GetIDList: Unused,
+    SetIDList: Unused,
+    GetDescription: Unused,
+    SetDescription: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    GetWorkingDirectory: Unused,
+    SetWorkingDirectory: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    GetArguments: unsafe extern "system" fn(*mut c_void, *mut u16, c_int) -> HRESULT,
+    SetArguments: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    GetHotkey: Unused,
+    SetHotkey: Unused,
+    GetShowCmd: Unused,
+    SetShowCmd: Unused,
+    GetIconLocation: Unused,
+    SetIconLocation: unsafe extern "system" fn(*mut c_void, *const u16, c_int) -> HRESULT,
+    SetRelativePath: Unused,
+    Resolve: Unused,
+    SetPath: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct IPropertyStoreVtbl {
+    QueryInterface: QueryInterfaceFn,
+    AddRef: AddRefFn,
+    Release: AddRefFn,
+    GetCount: Unused,
+    GetAt: Unused,
+    GetValue: Unused,
+    SetValue: unsafe extern "system" fn(*mut c_void, *const PROPERTYKEY, *const PROPVARIANT)
+                                        -> HRESULT,
+    Commit: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct THUMBBUTTON {
+    dwMask: DWORD,
+    iId: UINT,
+    iBitmap: UINT,
+    hIcon: HICON,
+    szTip: [u16; 260],
+    dwFlags: DWORD,
+}
+
+#[cfg(windows)]
+#[repr(C)]
+#[allow(non_snake_case)]
+struct PROPERTYKEY {
+    fmtid: Guid,
+    pid: DWORD,
+}
+
+// winapi's PROPVARIANT is not pointer-aligned.
+#[cfg(windows)]
+#[repr(C)]
+struct PROPVARIANT {
+    vt: u16,
+    reserved: [u16; 3],
+    data: [usize; 2],
+}
+
+#[cfg(windows)]
+#[link(name = "shell32")]
+extern "system" {
+    fn SetCurrentProcessExplicitAppUserModelID(AppID: *const u16) -> HRESULT;
+    fn SHAddToRecentDocs(uFlags: UINT, pv: *const c_void);
+}
+
+#[cfg(windows)]
+enum ITaskbarList3 {}
+
+#[cfg(windows)]
+unsafe impl Interface for ITaskbarList3 {
+    type Vtbl = ITaskbarList3Vtbl;
+    const IID: Guid = Guid {
+        data1: 0xEA1A_FB91, data2: 0x9E28, data3: 0x4B86,
+        data4: [0x90, 0xE9, 0x9E, 0x9F, 0x8A, 0x5E, 0xEF, 0xAF],
+    };
+}
+
+#[cfg(windows)]
+enum ICustomDestinationList {}
+
+#[cfg(windows)]
+unsafe impl Interface for ICustomDestinationList {
+    type Vtbl = ICustomDestinationListVtbl;
+    const IID: Guid = Guid {
+        data1: 0x6332_DEBF, data2: 0x87B5, data3: 0x4670,
+        data4: [0x90, 0xC0, 0x5E, 0x57, 0xB4, 0x08, 0xA4, 0x9E],
+    };
+}
+
+#[cfg(windows)]
+enum IObjectArray {}
+
+#[cfg(windows)]
+unsafe impl Interface for IObjectArray {
+    type Vtbl = IObjectArrayVtbl;
+    const IID: Guid = Guid {
+        data1: 0x92CA_9DCD, data2: 0x5622, data3: 0x4BBA,
+        data4: [0xA8, 0x05, 0x5E, 0x9F, 0x54, 0x1B, 0xD8, 0xC9],
+    };
+}
+
+#[cfg(windows)]
+enum IObjectCollection {}
+
+#[cfg(windows)]
+unsafe impl Interface for IObjectCollection {
+    type Vtbl = IObjectCollectionVtbl;
+    const IID: Guid = Guid {
+        data1: 0x5632_B1A4, data2: 0xE38A, data3: 0x400A,
+        data4: [0x92, 0x8A, 0xD4, 0xCD, 0x63, 0x23, 0x02, 0x95],
+    };
+}
+
+#[cfg(windows)]
+enum IShellLinkW {}
+
+#[cfg(windows)]
+unsafe impl Interface for IShellLinkW {
+    type Vtbl = IShellLinkWVtbl;
+    const IID: Guid = Guid {
+        data1: 0x0002_14F9, data2: 0x0000, data3: 0x0000,
+        data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+    };
+}
+
+#[cfg(windows)]
+enum IPropertyStore {}
+
+#[cfg(windows)]
+unsafe impl Interface for IPropertyStore {
+    type Vtbl = IPropertyStoreVtbl;
+    const IID: Guid = Guid {
+        data1: 0x886D_8EEB, data2: 0x8CF2, data3: 0x4446,
+        data4: [0x8D, 0x02, 0xCD, 0xBA, 0x1D, 0xBD, 0xCF, 0x99],
+    };
+}
+
+#[cfg(windows)]
+const CLSID_TASKBARLIST: Guid = Guid {
+    data1: 0x56FD_F344, data2: 0xFD6D, data3: 0x11D0,
+    data4: [0x95, 0x8A, 0x00, 0x60, 0x97, 0xC9, 0xA0, 0x90],
+};
+#[cfg(windows)]
+const CLSID_DESTINATIONLIST: Guid = Guid {
+    data1: 0x77F1_0CF0, data2: 0x3DB5, data3: 0x4966,
+    data4: [0xB5, 0x20, 0xB7, 0xC5, 0x4F, 0xD3, 0x5E, 0xD6],
+};
+#[cfg(windows)]
+const CLSID_ENUMERABLEOBJECTCOLLECTION: Guid = Guid {
+    data1: 0x2D34_68C1, data2: 0x36A7, data3: 0x43B6,
+    data4: [0xAC, 0x24, 0xD3, 0xF0, 0x2F, 0xD9, 0x60, 0x7A],
+};
+#[cfg(windows)]
+const CLSID_SHELLLINK: Guid = Guid {
+    data1: 0x0002_1401, data2: 0x0000, data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+#[cfg(windows)]
+const PKEY_TITLE: PROPERTYKEY = PROPERTYKEY {
+    fmtid: Guid {
+        data1: 0xF29F_85E0, data2: 0x4FF9, data3: 0x1068,
+        data4: [0xAB, 0x91, 0x08, 0x00, 0x2B, 0x27, 0xB3, 0xD9],
+    },
+    pid: 2,
+};
+#[cfg(windows)]
+const PKEY_APPUSERMODEL_ISDESTLISTSEPARATOR: PROPERTYKEY = PROPERTYKEY {
+    fmtid: Guid {
+        data1: 0x9F4C_2855, data2: 0x9F79, data3: 0x4B39,
+        data4: [0xA8, 0xD0, 0xE1, 0xD4, 0x2D, 0xE1, 0xD5, 0xF3],
+    },
+    pid: 6,
+};
+
+#[cfg(windows)] const VT_BOOL: u16 = 11;
+#[cfg(windows)] const VT_LPWSTR: u16 = 31;
+
+// THUMBBUTTON masks and flags.
+#[cfg(windows)] const THB_ICON: DWORD = 0x2;
+#[cfg(windows)] const THB_TOOLTIP: DWORD = 0x4;
+#[cfg(windows)] const THB_FLAGS: DWORD = 0x8;
+#[cfg(windows)] const THBF_DISABLED: DWORD = 0x1;
+#[cfg(windows)] const THBF_DISMISSONCLICK: DWORD = 0x2;
+#[cfg(windows)] const THBF_NOBACKGROUND: DWORD = 0x4;
+#[cfg(windows)] const THBF_HIDDEN: DWORD = 0x8;
+
+#[cfg(windows)] const SHARD_PATHW: UINT = 0x3;
+
+/// The most buttons a thumbnail toolbar can have.
+pub const MAX_THUMB_BUTTONS: usize = 7;
+
+#[cfg(windows)]
+static TASKBAR_BUTTON_CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// The message the taskbar sends a window when its button is created, or again
+/// after Explorer restarts. Handled by `TASKBAR` in `wnd_proc!`.
+///
+/// An elevated process must let the message through with `Window::allow_message`,
+/// e.g. in `WM_CREATE`.
+#[cfg(windows)]
+pub fn taskbar_button_created_message() -> UINT {
+    message::register_message_cached(&TASKBAR_BUTTON_CREATED, "TaskbarButtonCreated")
+}
+
+/// Sets the application id of the process, which groups its windows on the
+/// taskbar and names its jump list. Must be called before any window is shown.
+#[cfg(windows)]
+pub fn set_app_id(app_id: &str) -> Result<()> {
+    let app_id = WideCString::new(app_id)?;
+    com::check(unsafe { SetCurrentProcessExplicitAppUserModelID(app_id.as_ptr()) })
+}
+
+/// Adds a file to the recent documents, and so to `KnownCategory::Recent`. Only
+/// files of types registered to the application are shown in its jump list.
+#[cfg(windows)]
+pub fn add_to_recent_docs<P: AsRef<Path>>(path: P) {
+    let path = match WideCString::from_path(path) {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    unsafe { SHAddToRecentDocs(SHARD_PATHW, path.as_ptr() as *const c_void) };
+}
+
+/// A button of a window's thumbnail toolbar. Clicks are reported by
+/// `OnTaskbar::on_thumb_button`.
+#[cfg(windows)]
+#[derive(Clone,Debug)]
+pub struct ThumbButton {
+    pub id: u16,
+    pub icon: HICON,
+    pub tooltip: String,
+    pub enabled: bool,
+    pub hidden: bool,
+    /// Closes the thumbnail when clicked.
+    pub dismiss_on_click: bool,
+    /// Shows only the icon, without a button frame.
+    pub no_background: bool,
+}
+
+#[cfg(windows)]
+impl ThumbButton {
+    /// An enabled button. The icon must outlive the toolbar.
+    pub fn new(id: u16, icon: &Image, tooltip: &str) -> ThumbButton {
+        ThumbButton {
+            id,
+            icon: icon.image as HICON,
+            tooltip: tooltip.to_string(),
+            enabled: true,
+            hidden: false,
+            dismiss_on_click: false,
+            no_background: false,
+        }
+    }
+
+    fn to_raw(&self) -> THUMBBUTTON {
+        let mut flags = 0;
+        if !self.enabled {
+            flags |= THBF_DISABLED;
+        }
+        if self.hidden {
+            flags |= THBF_HIDDEN;
+        }
+        if self.dismiss_on_click {
+            flags |= THBF_DISMISSONCLICK;
+        }
+        if self.no_background {
+            flags |= THBF_NOBACKGROUND;
+        }
+        let mut raw = THUMBBUTTON {
+            dwMask: THB_ICON | THB_TOOLTIP | THB_FLAGS,
+            iId: self.id as UINT,
+            iBitmap: 0,
+            hIcon: self.icon,
+            szTip: [0; 260],
+            dwFlags: flags,
+        };
+        to_c_u16_buf(&mut raw.szTip, &self.tooltip);
+        raw
+    }
+}
+
+/// The taskbar button of a top-level window.
+#[cfg(windows)]
+pub struct Taskbar {
+    list: ComPtr<ITaskbarList3>,
+    wnd: Window,
+}
+
+#[cfg(windows)]
+impl Taskbar {
+    /// Fails if COM is not initialized or the shell has no taskbar. Changes made
+    /// before `on_taskbar_button_created` are lost.
+    pub fn new(wnd: &Window) -> Result<Taskbar> {
+        let list: ComPtr<ITaskbarList3> = com::create_instance(&CLSID_TASKBARLIST)?;
+        com::check(unsafe { (list.vtbl().HrInit)(list.as_raw()) })?;
+        Ok(Taskbar { list, wnd: *wnd })
+    }
+
+    pub fn set_progress_state(&self, state: ProgressState) -> Result<()> {
+        let list = &self.list;
+        com::check(unsafe { (list.vtbl().SetProgressState)(list.as_raw(), self.wnd.wnd, state.to_raw()) })
+    }
+
+    /// Shows `completed` out of `total` done, switching from `ProgressState::None`
+    /// or `Indeterminate` to `Normal`.
+    pub fn set_progress(&self, completed: u64, total: u64) -> Result<()> {
+        let list = &self.list;
+        let completed = completed.min(total);
+        com::check(unsafe {
+            (list.vtbl().SetProgressValue)(list.as_raw(), self.wnd.wnd, completed, total)
+        })
+    }
+
+    /// Shows a small icon over the button, e.g. for a status, or removes it with
+    /// `None`. `description` is read by screen readers.
+    pub fn set_overlay_icon(&self, icon: Option<&Image>, description: &str) -> Result<()> {
+        let list = &self.list;
+        let icon = icon.map_or(ptr::null_mut(), |i| i.image as HICON);
+        let description = WideCString::new(description)?;
+        com::check(unsafe {
+            (list.vtbl().SetOverlayIcon)(list.as_raw(), self.wnd.wnd, icon, description.as_ptr())
+        })
+    }
+
+    /// Sets the tooltip of the window's thumbnail, which is the window title by
+    /// default.
+    pub fn set_thumbnail_tooltip(&self, tooltip: &str) -> Result<()> {
+        let list = &self.list;
+        let tooltip = WideCString::new(tooltip)?;
+        com::check(unsafe {
+            (list.vtbl().SetThumbnailTooltip)(list.as_raw(), self.wnd.wnd, tooltip.as_ptr())
+        })
+    }
+
+    /// Adds a toolbar of up to `MAX_THUMB_BUTTONS` buttons to the window's
+    /// thumbnail. A window's toolbar can be added only once; later changes go
+    /// through `update_thumb_buttons`, with `hidden` standing in for removal.
+    pub fn add_thumb_buttons(&self, buttons: &[ThumbButton]) -> Result<()> {
+        if buttons.is_empty() || buttons.len() > MAX_THUMB_BUTTONS {
+            return Err(Error::from_hresult(E_INVALIDARG));
+        }
+        let list = &self.list;
+        let mut raw: Vec<THUMBBUTTON> = buttons.iter().map(|b| b.to_raw()).collect();
+        com::check(unsafe {
+            (list.vtbl().ThumbBarAddButtons)(list.as_raw(), self.wnd.wnd, raw.len() as UINT,
+                                             raw.as_mut_ptr())
+        })
+    }
+
+    /// Updates the buttons with the ids of `buttons`.
+    pub fn update_thumb_buttons(&self, buttons: &[ThumbButton]) -> Result<()> {
+        let list = &self.list;
+        let mut raw: Vec<THUMBBUTTON> = buttons.iter().map(|b| b.to_raw()).collect();
+        com::check(unsafe {
+            (list.vtbl().ThumbBarUpdateButtons)(list.as_raw(), self.wnd.wnd, raw.len() as UINT,
+                                                raw.as_mut_ptr())
+        })
+    }
+}
+
+#[cfg(windows)]
+impl Window {
+    /// Flashes the caption and taskbar button `count` times.
+    pub fn flash(&self, count: u32) {
+        self.flash_raw(0x3, count); // FLASHW_ALL
+    }
+
+    /// Flashes the caption and taskbar button until the window comes to the
+    /// foreground, e.g. to draw attention to a finished task.
+    pub fn flash_until_foreground(&self) {
+        self.flash_raw(0x3 | 0xC, 0); // FLASHW_ALL | FLASHW_TIMERNOFG
+    }
+
+    pub fn stop_flashing(&self) {
+        self.flash_raw(0, 0); // FLASHW_STOP
+    }
+
+    fn flash_raw(&self, flags: DWORD, count: u32) {
+        let mut info = FLASHWINFO {
+            cbSize: mem::size_of::<FLASHWINFO>() as UINT,
+            hwnd: self.wnd,
+            dwFlags: flags,
+            uCount: count,
+            dwTimeout: 0,
+        };
+        unsafe { user32::FlashWindowEx(&mut info) };
+    }
+}
+
+#[cfg(windows)]
+fn set_property(link: &ComPtr<IShellLinkW>, key: &PROPERTYKEY, value: &PROPVARIANT) -> Result<()> {
+    let store = link.query::<IPropertyStore>()?;
+    unsafe {
+        com::check((store.vtbl().SetValue)(store.as_raw(), key, value))?;
+        com::check((store.vtbl().Commit)(store.as_raw()))
+    }
+}
+
+#[cfg(windows)]
+fn create_link(link: &ShellLink) -> Result<ComPtr<IShellLinkW>> {
+    let obj: ComPtr<IShellLinkW> = com::create_instance(&CLSID_SHELLLINK)?;
+    unsafe {
+        let vtbl = obj.vtbl();
+        com::check((vtbl.SetPath)(obj.as_raw(), WideCString::from_path(&link.path)?.as_ptr()))?;
+        com::check((vtbl.SetArguments)(obj.as_raw(), WideCString::new(&link.arguments)?.as_ptr()))?;
+        com::check((vtbl.SetDescription)(obj.as_raw(), WideCString::new(&link.description)?.as_ptr()))?;
+        if let Some((ref path, index)) = link.icon {
+            com::check((vtbl.SetIconLocation)(obj.as_raw(), WideCString::from_path(path)?.as_ptr(), index))?;
+        }
+        if let Some(ref dir) = link.working_dir {
+            com::check((vtbl.SetWorkingDirectory)(obj.as_raw(), WideCString::from_path(dir)?.as_ptr()))?;
+        }
+    }
+    // The property store copies the string.
+    let title = WideCString::new(&link.title)?;
+    let value = PROPVARIANT { vt: VT_LPWSTR, reserved: [0; 3], data: [title.as_ptr() as usize, 0] };
+    set_property(&obj, &PKEY_TITLE, &value)?;
+    Ok(obj)
+}
+
+#[cfg(windows)]
+fn create_separator() -> Result<ComPtr<IShellLinkW>> {
+    let obj: ComPtr<IShellLinkW> = com::create_instance(&CLSID_SHELLLINK)?;
+    // VARIANT_TRUE
+    let value = PROPVARIANT { vt: VT_BOOL, reserved: [0; 3], data: [0xFFFF, 0] };
+    set_property(&obj, &PKEY_APPUSERMODEL_ISDESTLISTSEPARATOR, &value)?;
+    Ok(obj)
+}
+
+// Reads a string through a `Get*(buf, len)` method of IShellLinkW.
+#[cfg(windows)]
+fn link_string<F: Fn(*mut u16, c_int) -> HRESULT>(get: F) -> String {
+    let mut buf = [0u16; 1024];
+    if get(buf.as_mut_ptr(), buf.len() as c_int) < 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// The path and arguments of the links in an array.
+#[cfg(windows)]
+fn removed_links(array: &ComPtr<IObjectArray>) -> Vec<(String, String)> {
+    let mut links = Vec::new();
+    unsafe {
+        let mut count = 0;
+        if (array.vtbl().GetCount)(array.as_raw(), &mut count) < 0 {
+            return links;
+        }
+        for i in 0..count {
+            let mut obj = ptr::null_mut();
+            if (array.vtbl().GetAt)(array.as_raw(), i, &IShellLinkW::IID, &mut obj) < 0 {
+                continue;
+            }
+            let link: ComPtr<IShellLinkW> = match ComPtr::from_raw(obj) {
+                Some(link) => link,
+                None => continue,
+            };
+            let vtbl = link.vtbl();
+            let path = link_string(|buf, len| {
+                (vtbl.GetPath)(link.as_raw(), buf, len, ptr::null_mut(), 0)
+            });
+            let args = link_string(|buf, len| (vtbl.GetArguments)(link.as_raw(), buf, len));
+            links.push((path, args));
+        }
+    }
+    links
+}
+
+#[cfg(windows)]
+fn collection(items: Vec<ComPtr<IShellLinkW>>) -> Result<ComPtr<IObjectCollection>> {
+    let coll: ComPtr<IObjectCollection> = com::create_instance(&CLSID_ENUMERABLEOBJECTCOLLECTION)?;
+    for item in items {
+        com::check(unsafe { (coll.vtbl().AddObject)(coll.as_raw(), item.as_raw()) })?;
+    }
+    Ok(coll)
+}
+
+#[cfg(windows)]
+impl JumpList {
+    /// Replaces the application's jump list. Fails if COM is not initialized.
+    pub fn commit(&self) -> Result<()> {
+        let list: ComPtr<ICustomDestinationList> = com::create_instance(&CLSID_DESTINATIONLIST)?;
+        let vtbl = list.vtbl();
+        if let Some(ref app_id) = self.app_id {
+            let app_id = WideCString::new(app_id)?;
+            com::check(unsafe { (vtbl.SetAppID)(list.as_raw(), app_id.as_ptr()) })?;
+        }
+        let mut slots = 0;
+        let mut removed = ptr::null_mut();
+        com::check(unsafe {
+            (vtbl.BeginList)(list.as_raw(), &mut slots, &IObjectArray::IID, &mut removed)
+        })?;
+        let removed = match unsafe { ComPtr::from_raw(removed) } {
+            Some(removed) => removed_links(&removed),
+            None => Vec::new(),
+        };
+        let result = self.build(&list, &removed)
+            .and_then(|_| com::check(unsafe { (vtbl.CommitList)(list.as_raw()) }));
+        if result.is_err() {
+            unsafe { (vtbl.AbortList)(list.as_raw()) };
+        }
+        result
+    }
+
+    fn build(&self, list: &ComPtr<ICustomDestinationList>, removed: &[(String, String)])
+             -> Result<()> {
+        let vtbl = list.vtbl();
+        for category in &self.known {
+            com::check(unsafe {
+                (vtbl.AppendKnownCategory)(list.as_raw(), category.to_raw() as c_int)
+            })?;
+        }
+        for (name, links) in self.categories() {
+            // Adding a link the user removed fails the whole category.
+            let mut items = Vec::new();
+            for link in links.iter().filter(|l| {
+                let key = (l.path.to_string_lossy().into_owned(), l.arguments.clone());
+                !removed.contains(&key)
+            }) {
+                items.push(create_link(link)?);
+            }
+            if items.is_empty() {
+                continue;
+            }
+            let coll = collection(items)?;
+            let name = WideCString::new(&name)?;
+            com::check(unsafe {
+                (vtbl.AppendCategory)(list.as_raw(), name.as_ptr(), coll.as_raw())
+            })?;
+        }
+        let tasks = self.tasks();
+        if tasks.is_empty() {
+            return Ok(());
+        }
+        let mut items = Vec::new();
+        for task in &tasks {
+            items.push(match *task {
+                JumpItem::Link(ref link) => create_link(link)?,
+                JumpItem::Separator => create_separator()?,
+            });
+        }
+        let coll = collection(items)?;
+        com::check(unsafe { (vtbl.AddUserTasks)(list.as_raw(), coll.as_raw()) })
+    }
+
+    /// Removes the jump list of the process's application id, or of `app_id`.
+    pub fn clear(app_id: Option<&str>) -> Result<()> {
+        let list: ComPtr<ICustomDestinationList> = com::create_instance(&CLSID_DESTINATIONLIST)?;
+        let app_id = match app_id {
+            Some(id) => Some(WideCString::new(id)?),
+            None => None,
+        };
+        let app_id = app_id.as_ref().map_or(ptr::null(), |id| id.as_ptr());
+        com::check(unsafe { (list.vtbl().DeleteList)(list.as_raw(), app_id) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JumpItem, JumpList, KnownCategory, ShellLink, quote_arguments,
+                thumb_button_clicked};
+
+    #[test]
+    fn test_quote_arguments() {
+        assert_eq!(quote_arguments(&["--new-window"]), "--new-window");
+        assert_eq!(quote_arguments(&["a", "b c", ""]), r#"a "b c" """#);
+        assert_eq!(quote_arguments(&[r#"say "hi""#]), r#""say \"hi\"""#);
+        assert_eq!(quote_arguments(&[r"C:\dir\"]), r"C:\dir\");
+        assert_eq!(quote_arguments(&[r"C:\my dir\"]), r#""C:\my dir\\""#);
+        assert_eq!(quote_arguments(&[r#"a\"b"#]), r#""a\\\"b""#);
+        let empty: [&str; 0] = [];
+        assert_eq!(quote_arguments(&empty), "");
+    }
+
+    #[test]
+    fn test_thumb_button_clicked() {
+        assert_eq!(thumb_button_clicked(0x1800_0005), Some(5));
+        assert_eq!(thumb_button_clicked(0x0000_0005), None);
+        assert_eq!(thumb_button_clicked(0x0001_0005), None);
+    }
+
+    #[test]
+    fn test_tasks() {
+        let a = ShellLink::new("A", "app.exe").args(&["--a"]);
+        let b = ShellLink::new("B", "app.exe").description("Does b");
+        let list = JumpList::new()
+            .separator()
+            .task(a.clone())
+            .separator()
+            .separator()
+            .task(b.clone())
+            .separator();
+        assert_eq!(list.tasks(), vec![JumpItem::Link(a), JumpItem::Separator, JumpItem::Link(b)]);
+        assert_eq!(JumpList::new().separator().tasks(), vec![]);
+    }
+
+    #[test]
+    fn test_categories() {
+        let link = ShellLink::new("Project", "app.exe").args(&["C:\\Projects\\one.proj"]);
+        let list = JumpList::new()
+            .known_category(KnownCategory::Recent)
+            .known_category(KnownCategory::Recent)
+            .category("Empty", vec![])
+            .category("Pinned", vec![link.clone()]);
+        assert_eq!(list.known, vec![KnownCategory::Recent]);
+        assert_eq!(list.categories(), vec![("Pinned".to_string(), vec![link])]);
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Window timers with closure callbacks.
+//!
+//! Timers are created with a `TIMERPROC`, so `DispatchMessage` routes their
+//! `WM_TIMER` messages straight to the right closure; the window procedure never
+//! sees them. A timer lives until its `TimerHandle` is dropped or its window is
+//! destroyed, whichever comes first.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use user32;
+use winapi::{DWORD, HWND, UINT, UINT_PTR, ULONG};
+
+use error::{Error, Result};
+use window::Window;
+
+// USER_TIMER_MINIMUM and USER_TIMER_MAXIMUM.
+const TIMER_MINIMUM: u64 = 0x0000_000A;
+const TIMER_MAXIMUM: u64 = 0x7FFF_FFFF;
+
+// Timer ids handed out by this module, kept well clear of ids applications pick
+// for their own `SetTimer` calls on the same window.
+const FIRST_TIMER_ID: usize = 0x4000_0000;
+
+enum Callback {
+    Repeating(Box<dyn FnMut()>),
+    Once(Option<Box<dyn FnOnce()>>),
+}
+
+thread_local!(static NEXT_TIMER_ID: RefCell<usize> = RefCell::new(FIRST_TIMER_ID));
+thread_local!(static TIMERS: RefCell<HashMap<(Window, usize), Rc<RefCell<Callback>>>> =
+    RefCell::new(HashMap::new()));
+
+/// Kills its timer when dropped.
+pub struct TimerHandle {
+    wnd: Window,
+    id: usize,
+}
+
+impl TimerHandle {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Whether the timer is still pending. One-shot timers stop after firing, and
+    /// all timers stop when their window is destroyed.
+    pub fn is_active(&self) -> bool {
+        TIMERS.with(|t| t.borrow().contains_key(&(self.wnd, self.id)))
+    }
+
+    /// Lets the timer run until its window is destroyed.
+    pub fn detach(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for TimerHandle {
+    fn drop(&mut self) {
+        kill_timer(self.wnd, self.id);
+    }
+}
+
+fn interval_ms(interval: Duration) -> UINT {
+    let ms = interval.as_secs() * 1000 + (interval.subsec_nanos() / 1_000_000) as u64;
+    ms.max(TIMER_MINIMUM).min(TIMER_MAXIMUM) as UINT
+}
+
+fn kill_timer(wnd: Window, id: usize) {
+    let removed = TIMERS.with(|t| t.borrow_mut().remove(&(wnd, id)));
+    if removed.is_some() {
+        unsafe { user32::KillTimer(wnd.wnd, id as UINT_PTR) };
+    }
+}
+
+/// Forgets all timers of a window that is being destroyed. Called from
+/// `main_wnd_proc` on `WM_NCDESTROY`.
+pub fn remove_window_timers(wnd: Window) {
+    // Dropping callbacks may drop `TimerHandle`s, which borrow `TIMERS` again.
+    let removed: Vec<_> = TIMERS.with(|t| {
+        let mut timers = t.borrow_mut();
+        let keys: Vec<_> = timers.keys().filter(|k| k.0 == wnd).cloned().collect();
+        keys.iter().filter_map(|k| timers.remove(k)).collect()
+    });
+    drop(removed);
+}
+
+unsafe extern "system" fn timer_proc(wnd: HWND, _msg: UINT, id: UINT_PTR, _time: DWORD) {
+    let key = (Window { wnd }, id as usize);
+    let callback = match TIMERS.with(|t| t.borrow().get(&key).cloned()) {
+        Some(callback) => callback,
+        None => return,
+    };
+
+    let once = match *callback.borrow() {
+        Callback::Once(_) => true,
+        Callback::Repeating(_) => false,
+    };
+    if once {
+        kill_timer(key.0, key.1);
+    }
+
+    // A callback running a nested message loop (e.g. a message box) can see its
+    // own timer fire again; skip that tick rather than re-entering.
+    let mut callback = match callback.try_borrow_mut() {
+        Ok(callback) => callback,
+        Err(_) => return,
+    };
+    match *callback {
+        Callback::Repeating(ref mut f) => f(),
+        Callback::Once(ref mut f) => {
+            if let Some(f) = f.take() {
+                f();
+            }
+        }
+    }
+}
+
+type SetCoalescableTimerFn =
+    unsafe extern "system" fn(HWND, UINT_PTR, UINT, ::winapi::TimerProc, ULONG) -> UINT_PTR;
+
+impl Window {
+    fn start_timer(&self, interval: Duration, tolerance: Option<Duration>, callback: Callback)
+                   -> Option<TimerHandle> {
+        let id = NEXT_TIMER_ID.with(|next| {
+            let mut next = next.borrow_mut();
+            let id = *next;
+            *next += 1;
+            id
+        });
+        let elapse = interval_ms(interval);
+
+        let set = match (tolerance, ::get_proc_address("user32.dll", "SetCoalescableTimer")) {
+            (Some(tolerance), Some(f)) => unsafe {
+                let f: SetCoalescableTimerFn = mem::transmute(f);
+                f(self.wnd, id as UINT_PTR, elapse, Some(timer_proc), interval_ms(tolerance) as ULONG)
+            },
+            // Windows 7 and earlier coalesce timers on their own.
+            _ => unsafe { user32::SetTimer(self.wnd, id as UINT_PTR, elapse, Some(timer_proc)) },
+        };
+        if set == 0 {
+            return None;
+        }
+
+        TIMERS.with(|t| t.borrow_mut().insert((*self, id), Rc::new(RefCell::new(callback))));
+        Some(TimerHandle { wnd: *self, id })
+    }
+
+    /// Calls `callback` every `interval` (at least 10ms) until the returned handle
+    /// is dropped.
+    pub fn set_timer<F>(&self, interval: Duration, callback: F) -> Option<TimerHandle>
+        where F: FnMut() + 'static {
+        self.start_timer(interval, None, Callback::Repeating(Box::new(callback)))
+    }
+
+    /// Calls `callback` once after `interval`, unless the returned handle is dropped
+    /// first.
+    pub fn set_timeout<F>(&self, interval: Duration, callback: F) -> Option<TimerHandle>
+        where F: FnOnce() + 'static {
+        self.start_timer(interval, None, Callback::Once(Some(Box::new(callback))))
+    }
+
+    /// Like `set_timer`, but lets the system delay each tick by up to `tolerance`
+    /// to save power by coalescing it with other timers (Windows 8 and later).
+    pub fn set_coalescable_timer<F>(&self, interval: Duration, tolerance: Duration, callback: F)
+                                    -> Option<TimerHandle>
+        where F: FnMut() + 'static {
+        self.start_timer(interval, Some(tolerance), Callback::Repeating(Box::new(callback)))
+    }
+}
+
+struct SleepState {
+    fired: bool,
+    waker: Option<Waker>,
+}
+
+thread_local!(static SLEEPS: RefCell<HashMap<usize, SleepState>> = RefCell::new(HashMap::new()));
+
+unsafe extern "system" fn sleep_proc(_wnd: HWND, _msg: UINT, id: UINT_PTR, _time: DWORD) {
+    user32::KillTimer(ptr::null_mut(), id);
+    let waker = SLEEPS.with(|s| {
+        s.borrow_mut().get_mut(&(id as usize)).and_then(|state| {
+            state.fired = true;
+            state.waker.take()
+        })
+    });
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// A future completing after a delay; see `sleep`.
+pub struct Sleep {
+    interval: UINT,
+    id: Option<usize>,
+}
+
+/// Completes after `duration` (at least 10ms). Uses a thread timer, so it must be
+/// polled on a thread with a message loop, e.g. by `executor::spawn_local`.
+///
+/// Completes at once with the error if the timer cannot be created, rather than
+/// leaving the task waiting for a wake that never comes.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        interval: interval_ms(duration),
+        id: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let id = match self.id {
+            Some(id) => id,
+            None => {
+                let id = unsafe { user32::SetTimer(ptr::null_mut(), 0, self.interval, Some(sleep_proc)) };
+                if id == 0 {
+                    return Poll::Ready(Err(Error::last_error()));
+                }
+                self.id = Some(id as usize);
+                id as usize
+            }
+        };
+        let fired = SLEEPS.with(|s| {
+            let mut sleeps = s.borrow_mut();
+            let state = sleeps.entry(id).or_insert(SleepState { fired: false, waker: None });
+            if !state.fired {
+                state.waker = Some(cx.waker().clone());
+            }
+            state.fired
+        });
+        if fired {
+            SLEEPS.with(|s| s.borrow_mut().remove(&id));
+            self.id = None;
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            SLEEPS.with(|s| s.borrow_mut().remove(&id));
+            unsafe { user32::KillTimer(ptr::null_mut(), id as UINT_PTR) };
+        }
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2015 The Rust-Windows Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Notification area ("tray") icons.
+//!
+//! ```ignore
+//! wnd_proc!(MainFrame, win, WM_CREATE, TRAY_ICON, ANY);
+//!
+//! impl OnTrayIcon for MainFrame {
+//!     fn on_tray_icon(&self, _id: u32, event: TrayEvent) {
+//!         if let TrayEvent::ContextMenu(x, y) = event {
+//!             match self.tray.borrow().as_ref().unwrap().track_menu(self.menu, x, y) { ... }
+//!         }
+//!     }
+//! }
+//!
+//! let tray = TrayIcon::new(&main, 1, &icon, "My utility");
+//! ```
+//!
+//! Events arrive at the owner window through `OnTrayIcon`. Icons are added again
+//! when Explorer restarts, and removed when their `TrayIcon` is dropped.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::AtomicUsize;
+
+use user32;
+use winapi::{BOOL, DWORD, HICON, HMENU, HWND, LPARAM, NOTIFYICONDATAW, UINT, c_int, c_void};
+
+use input;
+use message;
+use resource::Image;
+use wchar::to_c_u16_buf;
+use window::Window;
+
+// Missing from the sys crates.
+#[link(name = "shell32")]
+extern "system" {
+    fn Shell_NotifyIconW(dwMessage: DWORD, lpData: *mut NOTIFYICONDATAW) -> BOOL;
+}
+
+#[link(name = "user32")]
+extern "system" {
+    fn TrackPopupMenuEx(hMenu: HMENU, uFlags: UINT, x: c_int, y: c_int, hwnd: HWND,
+                        lptpm: *mut c_void) -> BOOL;
+}
+
+const NIM_ADD: DWORD = 0x0000_0000;
+const NIM_MODIFY: DWORD = 0x0000_0001;
+const NIM_DELETE: DWORD = 0x0000_0002;
+const NIM_SETFOCUS: DWORD = 0x0000_0003;
+const NIM_SETVERSION: DWORD = 0x0000_0004;
+
+const NIF_MESSAGE: UINT = 0x0000_0001;
+const NIF_ICON: UINT = 0x0000_0002;
+const NIF_TIP: UINT = 0x0000_0004;
+const NIF_INFO: UINT = 0x0000_0010;
+const NIF_SHOWTIP: UINT = 0x0000_0080;
+
+const NOTIFYICON_VERSION_4: UINT = 4;
+
+const NIIF_NOSOUND: DWORD = 0x0000_0010;
+
+// TPM_RIGHTBUTTON | TPM_RETURNCMD | TPM_NONOTIFY
+const TPM_FLAGS: UINT = 0x0002 | 0x0100 | 0x0080;
+
+/// The icon shown next to a balloon notification's title.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum BalloonIcon {
+    None,
+    Info,
+    Warning,
+    Error,
+    /// The tray icon's own icon.
+    User,
+}
+
+impl BalloonIcon {
+    pub fn to_raw(&self) -> DWORD {
+        match *self {
+            BalloonIcon::None => 0, // NIIF_NONE
+            BalloonIcon::Info => 1, // NIIF_INFO
+            BalloonIcon::Warning => 2, // NIIF_WARNING
+            BalloonIcon::Error => 3, // NIIF_ERROR
+            BalloonIcon::User => 4, // NIIF_USER
+        }
+    }
+}
+
+/// What happened to a tray icon. Points are in screen coordinates.
+#[derive(Clone,Copy,PartialEq,Eq,Debug)]
+pub enum TrayEvent {
+    /// Clicked, or activated with Space or Enter.
+    Select(isize, isize),
+    DoubleClick(isize, isize),
+    /// Right-clicked, or Shift+F10 or the menu key pressed; show the icon's menu.
+    ContextMenu(isize, isize),
+    MouseMove(isize, isize),
+    BalloonShown,
+    /// The balloon was closed, e.g. because the icon was removed.
+    BalloonHidden,
+    BalloonTimeout,
+    BalloonClicked,
+    /// The pointer is resting on the icon; a rich tooltip could be shown.
+    PopupOpen,
+    PopupClose,
+}
+
+impl TrayEvent {
+    /// Decodes a version 4 callback message into the icon id and the event.
+    pub fn from_raw(w: usize, l: isize) -> Option<(u32, TrayEvent)> {
+        let (x, y) = input::point_from_lparam(w as isize);
+        let event = match l as u32 & 0xFFFF {
+            0x0400 | 0x0401 => TrayEvent::Select(x, y), // NIN_SELECT, NIN_KEYSELECT
+            0x0203 => TrayEvent::DoubleClick(x, y), // WM_LBUTTONDBLCLK
+            0x007B => TrayEvent::ContextMenu(x, y), // WM_CONTEXTMENU
+            0x0200 => TrayEvent::MouseMove(x, y), // WM_MOUSEMOVE
+            0x0402 => TrayEvent::BalloonShown, // NIN_BALLOONSHOW
+            0x0403 => TrayEvent::BalloonHidden, // NIN_BALLOONHIDE
+            0x0404 => TrayEvent::BalloonTimeout, // NIN_BALLOONTIMEOUT
+            0x0405 => TrayEvent::BalloonClicked, // NIN_BALLOONUSERCLICK
+            0x0406 => TrayEvent::PopupOpen, // NIN_POPUPOPEN
+            0x0407 => TrayEvent::PopupClose, // NIN_POPUPCLOSE
+            _ => return None,
+        };
+        Some(((l as u32 >> 16) & 0xFFFF, event))
+    }
+}
+
+// What is needed to add an icon again after Explorer restarts.
+struct IconState {
+    icon: HICON,
+    tip: String,
+}
+
+thread_local!(static ICONS: RefCell<HashMap<(Window, u32), IconState>> = RefCell::new(HashMap::new()));
+
+static TRAY_MESSAGE: AtomicUsize = AtomicUsize::new(0);
+static TASKBAR_CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// The callback message of tray icons, handled by `TRAY_ICON` in `wnd_proc!`.
+pub fn tray_message() -> UINT {
+    message::register_message_cached(&TRAY_MESSAGE, "RustWindowsTrayIcon")
+}
+
+/// The message Explorer broadcasts to top-level windows when the taskbar is
+/// (re)created.
+pub fn taskbar_created_message() -> UINT {
+    message::register_message_cached(&TASKBAR_CREATED, "TaskbarCreated")
+}
+
+fn icon_data(wnd: Window, id: u32, flags: UINT) -> NOTIFYICONDATAW {
+    let mut nid: NOTIFYICONDATAW = unsafe { mem::zeroed() };
+    nid.cbSize = mem::size_of::<NOTIFYICONDATAW>() as DWORD;
+    nid.hWnd = wnd.wnd;
+    nid.uID = id;
+    nid.uFlags = flags;
+    nid
+}
+
+fn add_icon(wnd: Window, id: u32, state: &IconState) -> bool {
+    let mut nid = icon_data(wnd, id, NIF_MESSAGE | NIF_ICON | NIF_TIP | NIF_SHOWTIP);
+    nid.uCallbackMessage = tray_message();
+    nid.hIcon = state.icon;
+    to_c_u16_buf(&mut nid.szTip, &state.tip);
+    unsafe {
+        if Shell_NotifyIconW(NIM_ADD, &mut nid) == 0 {
+            return false;
+        }
+        // uTimeout doubles as uVersion.
+        nid.uTimeout = NOTIFYICON_VERSION_4;
+        Shell_NotifyIconW(NIM_SETVERSION, &mut nid) != 0
+    }
+}
+
+/// Adds the icons of `wnd` again. Called from `main_wnd_proc` on
+/// `taskbar_created_message()`.
+pub fn readd_icons(wnd: Window) {
+    ICONS.with(|icons| {
+        for (key, state) in icons.borrow().iter().filter(|e| (e.0).0 == wnd) {
+            add_icon(wnd, key.1, state);
+        }
+    });
+}
+
+/// Forgets the icons of a destroyed window, whose `TrayIcon`s may outlive it.
+/// Called from `main_wnd_proc` on `WM_NCDESTROY`.
+pub fn forget_window(wnd: Window) {
+    ICONS.with(|icons| icons.borrow_mut().retain(|key, _| key.0 != wnd));
+}
+
+/// An icon in the notification area, removed when dropped. Its events are sent
+/// to the owner window, which must be a top-level window created by
+/// `Window::new` for the icon to come back after Explorer restarts.
+pub struct TrayIcon {
+    wnd: Window,
+    id: u32,
+}
+
+impl TrayIcon {
+    /// Adds an icon, with `id` distinguishing the icons of one owner. Icons are
+    /// shared resources (see `Image::load_resource`), so they must outlive the
+    /// tray icon.
+    pub fn new(owner: &Window, id: u32, icon: &Image, tip: &str) -> Option<TrayIcon> {
+        let state = IconState { icon: icon.image as HICON, tip: tip.to_string() };
+        if !add_icon(*owner, id, &state) {
+            // Clean up after a failed NIM_SETVERSION.
+            let mut nid = icon_data(*owner, id, 0);
+            unsafe { Shell_NotifyIconW(NIM_DELETE, &mut nid) };
+            return None;
+        }
+        owner.allow_message(taskbar_created_message());
+        ICONS.with(|icons| icons.borrow_mut().insert((*owner, id), state));
+        Some(TrayIcon { wnd: *owner, id })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn owner(&self) -> Window {
+        self.wnd
+    }
+
+    fn modify(&self, nid: &mut NOTIFYICONDATAW) -> bool {
+        unsafe { Shell_NotifyIconW(NIM_MODIFY, nid) != 0 }
+    }
+
+    pub fn set_icon(&self, icon: &Image) -> bool {
+        let mut nid = icon_data(self.wnd, self.id, NIF_ICON);
+        nid.hIcon = icon.image as HICON;
+        ICONS.with(|icons| {
+            if let Some(state) = icons.borrow_mut().get_mut(&(self.wnd, self.id)) {
+                state.icon = nid.hIcon;
+            }
+        });
+        self.modify(&mut nid)
+    }
+
+    /// Sets the tooltip, truncated to 127 UTF-16 units.
+    pub fn set_tooltip(&self, tip: &str) -> bool {
+        let mut nid = icon_data(self.wnd, self.id, NIF_TIP | NIF_SHOWTIP);
+        to_c_u16_buf(&mut nid.szTip, tip);
+        ICONS.with(|icons| {
+            if let Some(state) = icons.borrow_mut().get_mut(&(self.wnd, self.id)) {
+                state.tip = tip.to_string();
+            }
+        });
+        self.modify(&mut nid)
+    }
+
+    /// Shows a balloon notification, which Windows 10 and later show as a toast.
+    /// `text` is truncated to 255 UTF-16 units and `title` to 63.
+    pub fn show_balloon(&self, title: &str, text: &str, icon: BalloonIcon, sound: bool) -> bool {
+        let mut nid = icon_data(self.wnd, self.id, NIF_INFO);
+        to_c_u16_buf(&mut nid.szInfoTitle, title);
+        // An empty text would hide the balloon instead.
+        to_c_u16_buf(&mut nid.szInfo, if text.is_empty() { " " } else { text });
+        nid.dwInfoFlags = icon.to_raw() | if sound { 0 } else { NIIF_NOSOUND };
+        self.modify(&mut nid)
+    }
+
+    pub fn hide_balloon(&self) -> bool {
+        let mut nid = icon_data(self.wnd, self.id, NIF_INFO);
+        self.modify(&mut nid)
+    }
+
+    /// Gives the keyboard focus back to the icon, e.g. after its menu was closed
+    /// with Escape.
+    pub fn set_focus(&self) -> bool {
+        let mut nid = icon_data(self.wnd, self.id, 0);
+        unsafe { Shell_NotifyIconW(NIM_SETFOCUS, &mut nid) != 0 }
+    }
+
+    /// Shows a popup menu at `(x, y)`, as given by `TrayEvent::ContextMenu`, and
+    /// returns the chosen command id, or `None` if the menu was dismissed.
+    pub fn track_menu(&self, menu: HMENU, x: isize, y: isize) -> Option<u16> {
+        unsafe {
+            // Without this the menu does not close when clicking elsewhere.
+            user32::SetForegroundWindow(self.wnd.wnd);
+            let cmd = TrackPopupMenuEx(menu, TPM_FLAGS, x as c_int, y as c_int, self.wnd.wnd,
+                                       ptr::null_mut());
+            // WM_NULL, so that the next click on the icon opens the menu again.
+            self.wnd.post_message(0, 0, 0 as LPARAM);
+            if cmd == 0 { None } else { Some(cmd as u16) }
+        }
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        ICONS.with(|icons| icons.borrow_mut().remove(&(self.wnd, self.id)));
+        let mut nid = icon_data(self.wnd, self.id, 0);
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &mut nid) };
+    }
+}
@@ -7,171 +7,833 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::mem;
-use std::ffi::{OsStr,OsString};
+//! NUL-terminated UTF-16 strings, as taken and returned by the `W` APIs.
+//!
+//! `WideCString` and `WideCStr` are to UTF-16 what `CString` and `CStr` are to
+//! bytes: an owned string and a borrowed one, both ending with a single NUL and
+//! holding no other.
+//!
+//! ```ignore
+//! let text = WideCString::from_str_truncate(text);
+//! user32::SetWindowTextW(wnd, text.as_ptr());
+//!
+//! let path = WideCString::from_path(&path)?;
+//! ```
+//!
+//! Strings from the system are not always valid UTF-16, so they convert to
+//! `OsString` without loss, or to `String` lossily or strictly.
+//!
+//! Everything but the `OsStr` and `Path` conversions is plain Rust and builds on
+//! any platform, as does `MultiString`.
+
+use std::borrow::Borrow;
 use std::fmt;
-use std::fmt::Display;
-use std::os::windows::ffi::{OsStrExt, OsStringExt};
-use std::vec::Vec;
-
-// Helper struct for *u16 manipulation.
-#[allow(missing_copy_implementations)]
-pub struct CU16String {
-    buf: *const u16,
-    /// length of buffer, including null
-    len: usize,
-}
-
-impl CU16String {
-    /// Create a CU16String from a pointer.
-    pub unsafe fn new(buf: *const u16) -> CU16String {
-        CU16String {
-            buf: buf,
-            len: {
-                let mut length_counter = 0;
-                loop {
-                    if *buf.offset(length_counter)==0 {
-                        break;
-                    }
-                    length_counter += 1;
-                }
-                length_counter += 1;
-                length_counter as usize
+use std::ops::Deref;
+use std::string::FromUtf16Error;
+
+#[cfg(windows)] use std::ffi::{OsStr, OsString};
+#[cfg(windows)] use std::os::windows::ffi::{OsStrExt, OsStringExt};
+#[cfg(windows)] use std::path::{Path, PathBuf};
+
+use error::{Error, E_INVALIDARG};
+
+/// A string to be converted contains a NUL before its end.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub struct NulError {
+    position: usize,
+    wide: Vec<u16>,
+}
+
+impl NulError {
+    /// The index of the first NUL, in UTF-16 units.
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+
+    /// The UTF-16 string that failed to convert.
+    pub fn into_vec(self) -> Vec<u16> {
+        self.wide
+    }
+}
+
+impl fmt::Display for NulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NUL found at position {} of a wide string", self.position)
+    }
+}
+
+impl ::std::error::Error for NulError {
+}
+
+/// A string with a NUL is an invalid argument.
+impl From<NulError> for Error {
+    fn from(_: NulError) -> Error {
+        Error::from_hresult(E_INVALIDARG)
+    }
+}
+
+/// A string that cannot be added to a `MultiString`.
+#[derive(Clone,PartialEq,Eq,Debug)]
+pub enum MultiStringError {
+    /// The string at `index` contains a NUL.
+    Nul { index: usize, error: NulError },
+    /// The string at `index` is empty, which would end the list early.
+    Empty { index: usize },
+}
+
+impl MultiStringError {
+    /// The index of the string that failed, among those given.
+    pub fn index(&self) -> usize {
+        match *self {
+            MultiStringError::Nul { index, .. } | MultiStringError::Empty { index } => index,
+        }
+    }
+}
+
+impl fmt::Display for MultiStringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MultiStringError::Nul { index, ref error } => write!(f, "string {}: {}", index, error),
+            MultiStringError::Empty { index } => {
+                write!(f, "string {} is empty, which would end the list", index)
             }
         }
     }
+}
+
+impl ::std::error::Error for MultiStringError {
+}
+
+impl From<MultiStringError> for Error {
+    fn from(_: MultiStringError) -> Error {
+        Error::from_hresult(E_INVALIDARG)
+    }
+}
+
+/// An owned NUL-terminated UTF-16 string without interior NULs.
+#[derive(Clone,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct WideCString {
+    // Ends with the only NUL.
+    inner: Vec<u16>,
+}
+
+impl WideCString {
+    /// Fails if `s` contains a NUL.
+    pub fn new(s: &str) -> Result<WideCString, NulError> {
+        WideCString::from_vec(s.encode_utf16().collect())
+    }
+
+    /// Converts `s` up to its first NUL, which is what an API would see of it.
+    /// Suits text such as window titles, where a NUL is not worth failing over.
+    pub fn from_str_truncate(s: &str) -> WideCString {
+        WideCString::from_vec_truncate(s.encode_utf16().collect())
+    }
+
+    /// Converts `s` without loss, including unpaired surrogates. Fails if `s`
+    /// contains a NUL.
+    #[cfg(windows)]
+    pub fn from_os_str<S: AsRef<OsStr>>(s: S) -> Result<WideCString, NulError> {
+        WideCString::from_vec(s.as_ref().encode_wide().collect())
+    }
+
+    #[cfg(windows)]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<WideCString, NulError> {
+        WideCString::from_os_str(path.as_ref().as_os_str())
+    }
+
+    /// Takes UTF-16 units without a terminator. Fails if they contain a NUL.
+    pub fn from_vec(mut wide: Vec<u16>) -> Result<WideCString, NulError> {
+        if let Some(position) = wide.iter().position(|&c| c == 0) {
+            return Err(NulError { position, wide });
+        }
+        wide.push(0);
+        Ok(WideCString { inner: wide })
+    }
+
+    /// Takes UTF-16 units up to the first NUL, if any.
+    pub fn from_vec_truncate(mut wide: Vec<u16>) -> WideCString {
+        if let Some(position) = wide.iter().position(|&c| c == 0) {
+            wide.truncate(position);
+        }
+        wide.push(0);
+        WideCString { inner: wide }
+    }
+
+    /// The UTF-16 units, without the terminator.
+    pub fn into_vec(mut self) -> Vec<u16> {
+        self.inner.pop();
+        self.inner
+    }
+
+    pub fn into_vec_with_nul(self) -> Vec<u16> {
+        self.inner
+    }
+
+    pub fn as_wide_cstr(&self) -> &WideCStr {
+        unsafe { WideCStr::from_slice_with_nul_unchecked(&self.inner) }
+    }
+}
+
+impl Default for WideCString {
+    fn default() -> WideCString {
+        WideCString { inner: vec![0] }
+    }
+}
+
+impl Deref for WideCString {
+    type Target = WideCStr;
+
+    fn deref(&self) -> &WideCStr {
+        self.as_wide_cstr()
+    }
+}
+
+impl Borrow<WideCStr> for WideCString {
+    fn borrow(&self) -> &WideCStr {
+        self.as_wide_cstr()
+    }
+}
+
+impl AsRef<WideCStr> for WideCString {
+    fn as_ref(&self) -> &WideCStr {
+        self.as_wide_cstr()
+    }
+}
+
+impl<'a> From<&'a WideCStr> for WideCString {
+    fn from(s: &'a WideCStr) -> WideCString {
+        s.to_owned()
+    }
+}
+
+impl fmt::Display for WideCString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_wide_cstr(), f)
+    }
+}
 
-    /// Converts the CU16String into a `&[u16]` without copying.
-    /// NULL is not included.
+impl fmt::Debug for WideCString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_wide_cstr(), f)
+    }
+}
+
+/// A borrowed NUL-terminated UTF-16 string without interior NULs.
+#[derive(PartialEq,Eq,PartialOrd,Ord,Hash)]
+#[repr(transparent)]
+pub struct WideCStr {
+    // Ends with the only NUL.
+    inner: [u16],
+}
+
+impl WideCStr {
+    /// Borrows the string `ptr` points to, up to its NUL.
     ///
-    /// # Failure
+    /// # Safety
     ///
-    /// Fails if the CU16String is null.
-    #[inline]
-    pub fn as_u16_vec<'a>(&'a self) -> &'a [u16] {
-        if self.buf.is_null() { panic!("CU16String is null!"); }
-        unsafe {
-            mem::transmute((self.buf, self.len - 1))
+    /// `ptr` must point to a NUL-terminated string that outlives `'a` and is not
+    /// changed meanwhile.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a WideCStr {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
         }
+        WideCStr::from_slice_with_nul_unchecked(std::slice::from_raw_parts(ptr, len + 1))
+    }
+
+    /// Borrows a slice that ends with its only NUL.
+    pub fn from_slice_with_nul(s: &[u16]) -> Option<&WideCStr> {
+        match s.iter().position(|&c| c == 0) {
+            Some(position) if position + 1 == s.len() => {
+                Some(unsafe { WideCStr::from_slice_with_nul_unchecked(s) })
+            }
+            _ => None,
+        }
+    }
+
+    /// Borrows a slice up to and including its first NUL, such as a buffer filled
+    /// by an API. `None` if there is no NUL.
+    pub fn from_slice_until_nul(s: &[u16]) -> Option<&WideCStr> {
+        s.iter().position(|&c| c == 0).map(|position| {
+            unsafe { WideCStr::from_slice_with_nul_unchecked(&s[..position + 1]) }
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `s` must end with its only NUL.
+    pub unsafe fn from_slice_with_nul_unchecked(s: &[u16]) -> &WideCStr {
+        &*(s as *const [u16] as *const WideCStr)
+    }
+
+    /// The pointer to pass to APIs, valid as long as the string.
+    pub fn as_ptr(&self) -> *const u16 {
+        self.inner.as_ptr()
+    }
+
+    /// The UTF-16 units, without the terminator.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.inner[..self.inner.len() - 1]
+    }
+
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.inner
+    }
+
+    /// The length in UTF-16 units, without the terminator.
+    pub fn len(&self) -> usize {
+        self.inner.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Converts without loss, keeping unpaired surrogates.
+    #[cfg(windows)]
+    pub fn to_os_string(&self) -> OsString {
+        OsString::from_wide(self.as_slice())
+    }
+
+    #[cfg(windows)]
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf::from(self.to_os_string())
+    }
+
+    /// Converts to UTF-8, replacing unpaired surrogates with U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+
+    /// Converts to UTF-8, failing on unpaired surrogates.
+    pub fn to_string_strict(&self) -> Result<String, FromUtf16Error> {
+        String::from_utf16(self.as_slice())
+    }
+}
+
+impl ToOwned for WideCStr {
+    type Owned = WideCString;
+
+    fn to_owned(&self) -> WideCString {
+        WideCString { inner: self.inner.to_vec() }
     }
 }
 
-impl fmt::Display for CU16String {
-    #[inline]
+impl AsRef<WideCStr> for WideCStr {
+    fn as_ref(&self) -> &WideCStr {
+        self
+    }
+}
+
+impl<'a> Default for &'a WideCStr {
+    fn default() -> &'a WideCStr {
+        const EMPTY: &[u16] = &[0];
+        unsafe { WideCStr::from_slice_with_nul_unchecked(EMPTY) }
+    }
+}
+
+impl fmt::Display for WideCStr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = if self.buf.is_null() {
-            "".to_string()
-        } else {
-            String::from_utf16_lossy(self.as_u16_vec())
-        };
-        s.fmt(f)
+        fmt::Display::fmt(&self.to_string_lossy(), f)
     }
 }
 
-/// Parses a C utf-16 "multistring".
-/// See `std::c_str::from_c_multistring` for detailed explanation.
-pub unsafe fn from_c_u16_multistring<F>(buf: *const u16, count: Option<usize>, mut f: F) -> usize
-    where F: FnMut(&[u16]) {
-    let mut curr_ptr: usize = buf as usize;
-    let mut ctr = 0;
-    let (limited_count, limit) = match count {
-        Some(limit) => (true, limit),
-        None => (false, 0)
-    };
-    while ((limited_count && ctr < limit) || !limited_count)
-          && *(curr_ptr as *const u16) != 0 as u16 {
-        let cstr = CU16String::new(curr_ptr as *const u16);
-        f(cstr.as_u16_vec());
-        curr_ptr += cstr.len * 2;
-        ctr += 1;
+/// Quoted like a `str`, with unpaired surrogates shown as `\u{d800}` escapes.
+impl fmt::Debug for WideCStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in std::char::decode_utf16(self.as_slice().iter().cloned()) {
+            match c {
+                Ok(c) => write!(f, "{}", c.escape_debug())?,
+                Err(e) => write!(f, "\\u{{{:x}}}", e.unpaired_surrogate())?,
+            }
+        }
+        f.write_str("\"")
     }
-    return ctr;
 }
 
-/// A generic trait for converting a value to a `CU16String`, like `ToCStr`.
-pub trait ToCU16Str {
-    fn to_c_u16(&self) -> Vec<u16>;
+/// A list of strings as many APIs take and return them: each string followed by
+/// a NUL, and the list by one more, as in `REG_MULTI_SZ` values, environment
+/// blocks, file dialog filters and `HDROP` file lists.
+///
+/// ```ignore
+/// let filter = MultiString::from_strs(&["Text files", "*.txt", "All files", "*.*"])?;
+/// ofn.lpstrFilter = filter.as_ptr();
+///
+/// for name in MultiString::parse(&buf) {
+///     files.push(name.to_path_buf());
+/// }
+/// ```
+///
+/// An empty string would end the list early, so none can be added.
+///
+/// Replaces `from_c_u16_multistring`, which can read past the buffer.
+#[derive(Clone,PartialEq,Eq,Hash)]
+pub struct MultiString {
+    // Always ends with two NULs: an empty list is `[0, 0]`, which every reader
+    // takes as empty.
+    inner: Vec<u16>,
 }
 
-impl<'a> ToCU16Str for &'a str {
-    fn to_c_u16(&self) -> Vec<u16> {
-        let mut t : Vec<u16> = OsStr::new( self )
-			.encode_wide()
-			.chain(Some(0).into_iter())
-			.collect::<Vec<_>>();
-        t.push(0u16);
-        t
+impl MultiString {
+    pub fn new() -> MultiString {
+        MultiString { inner: vec![0, 0] }
+    }
+
+    /// Fails on the first string that is empty or contains a NUL.
+    pub fn from_strs<I, S>(strings: I) -> Result<MultiString, MultiStringError>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+        let mut list = MultiString::new();
+        for (index, s) in strings.into_iter().enumerate() {
+            let s = WideCString::new(s.as_ref())
+                .map_err(|error| MultiStringError::Nul { index, error })?;
+            list.push(&s)?;
+        }
+        Ok(list)
+    }
+
+    /// Like `from_strs`, but without loss for strings such as paths.
+    #[cfg(windows)]
+    pub fn from_os_strs<I, S>(strings: I) -> Result<MultiString, MultiStringError>
+        where I: IntoIterator<Item=S>, S: AsRef<OsStr> {
+        let mut list = MultiString::new();
+        for (index, s) in strings.into_iter().enumerate() {
+            let s = WideCString::from_os_str(s)
+                .map_err(|error| MultiStringError::Nul { index, error })?;
+            list.push(&s)?;
+        }
+        Ok(list)
+    }
+
+    /// Parses a multistring held in a slice, such as a buffer filled by an API,
+    /// without reading past it. Stops at the empty string that ends the list, or
+    /// at the end of the slice; a last string missing its NUL is left out, as it
+    /// may have been cut short.
+    pub fn parse<'a>(buf: &'a [u16]) -> MultiStringIter<'a> {
+        MultiStringIter { rest: buf }
+    }
+
+    /// Appends `s`. Fails if it is empty, with the index it would have had.
+    pub fn push(&mut self, s: &WideCStr) -> Result<(), MultiStringError> {
+        if s.is_empty() {
+            return Err(MultiStringError::Empty { index: self.len() });
+        }
+        // Drop the final NUL, or both of an empty list.
+        let end = if self.is_empty() { 0 } else { self.inner.len() - 1 };
+        self.inner.truncate(end);
+        self.inner.extend_from_slice(s.as_slice_with_nul());
+        self.inner.push(0);
+        Ok(())
+    }
+
+    pub fn iter<'a>(&'a self) -> MultiStringIter<'a> {
+        MultiString::parse(&self.inner)
+    }
+
+    /// The number of strings.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner[0] == 0
+    }
+
+    /// The pointer to pass to APIs, valid as long as the list.
+    pub fn as_ptr(&self) -> *const u16 {
+        self.inner.as_ptr()
+    }
+
+    /// The whole list, with its NULs.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.inner
+    }
+
+    pub fn into_vec(self) -> Vec<u16> {
+        self.inner
     }
 }
 
-impl ToCU16Str for String {
-    fn to_c_u16(&self) -> Vec<u16> {
-        let x : &str = &self;
-        x.to_c_u16()
+impl Default for MultiString {
+    fn default() -> MultiString {
+        MultiString::new()
     }
 }
 
-impl ToCU16Str for Option<String> {
-    fn to_c_u16(&self) -> Vec<u16> {
-        match self {
-            &None => Vec::new(),
-            &Some(ref s) => (&s).to_c_u16(),
+impl<'a> IntoIterator for &'a MultiString {
+    type Item = &'a WideCStr;
+    type IntoIter = MultiStringIter<'a>;
+
+    fn into_iter(self) -> MultiStringIter<'a> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for MultiString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// The strings of a multistring, returned by `MultiString::parse` and
+/// `MultiString::iter`.
+#[derive(Clone,Debug)]
+pub struct MultiStringIter<'a> {
+    rest: &'a [u16],
+}
+
+impl<'a> Iterator for MultiStringIter<'a> {
+    type Item = &'a WideCStr;
+
+    fn next(&mut self) -> Option<&'a WideCStr> {
+        match self.rest.iter().position(|&c| c == 0) {
+            Some(0) | None => {
+                self.rest = &[];
+                None
+            }
+            Some(len) => {
+                let s = unsafe { WideCStr::from_slice_with_nul_unchecked(&self.rest[..len + 1]) };
+                self.rest = &self.rest[len + 1..];
+                Some(s)
+            }
         }
     }
 }
 
-pub trait FromCU16Str {
-    fn from_c_u16(wide: &Vec<u16>) -> Option<String>;
+/// Parses a C utf-16 "multistring": NUL-terminated strings ended by an empty
+/// one. Calls `f` on each string, at most `count` times, and returns how many
+/// were read.
+///
+/// # Safety
+///
+/// `buf` must point to a multistring that is terminated, or has at least `count`
+/// strings.
+#[deprecated(note = "reads up to a terminator it cannot bound; use `MultiString::parse`")]
+pub unsafe fn from_c_u16_multistring<F>(buf: *const u16, count: Option<usize>, mut f: F) -> usize
+    where F: FnMut(&[u16]) {
+    // Find the extent of the strings to read, then parse them as a slice.
+    let limit = count.unwrap_or(usize::MAX);
+    let mut len = 0;
+    let mut strings = 0;
+    while strings < limit && *buf.add(len) != 0 {
+        while *buf.add(len) != 0 {
+            len += 1;
+        }
+        len += 1;
+        strings += 1;
+    }
+    for s in MultiString::parse(std::slice::from_raw_parts(buf, len)) {
+        f(s.as_slice());
+    }
+    strings
 }
 
-impl FromCU16Str for String {
-    fn from_c_u16(wide: &Vec<u16>) -> Option<String> {
-        let t = OsString::from_wide(wide);
-        t.to_str().map( str::to_string )
+/// Converts a fixed-size u16 buffer such as `LOGFONTW::lfFaceName` into a `String`.
+/// Everything from the first NULL on is ignored; invalid UTF-16 is replaced.
+pub fn from_c_u16_buf(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// Copies `s` into a fixed-size u16 buffer such as `NOTIFYICONDATAW::szTip`,
+/// truncating it to leave room for the NUL and never splitting a surrogate pair.
+pub fn to_c_u16_buf(buf: &mut [u16], s: &str) {
+    if buf.is_empty() {
+        return;
+    }
+    let mut len = 0;
+    for c in s.encode_utf16().take(buf.len() - 1) {
+        buf[len] = c;
+        len += 1;
     }
+    if len > 0 && (0xD800..0xDC00).contains(&buf[len - 1]) {
+        len -= 1;
+    }
+    buf[len] = 0;
 }
 
 #[cfg(test)]
 mod test {
-    use super::CU16String;
-    use super::from_c_u16_multistring;
+    use super::{MultiString, MultiStringError, NulError, WideCStr, WideCString};
+    use super::{from_c_u16_buf, to_c_u16_buf};
+    use std::collections::HashSet;
+
+    #[cfg(windows)] use std::ffi::OsString;
+    #[cfg(windows)] use std::os::windows::ffi::OsStringExt;
+    #[cfg(windows)] use std::path::Path;
 
     #[test]
-    fn test_as_u16_vec() {
-        let u16s: &[u16] = &[
-            0xac00, 0x20, 0xac00, 0x00,
-        ];
+    fn test_new() {
+        let s = WideCString::new("가 A").unwrap();
+        assert_eq!(s.as_slice(), &[0xac00, 0x20, 0x41]);
+        assert_eq!(s.as_slice_with_nul(), &[0xac00, 0x20, 0x41, 0]);
+        assert_eq!(s.len(), 3);
+        assert!(!s.is_empty());
+        assert_eq!(s.into_vec(), vec![0xac00, 0x20, 0x41]);
+
+        // One terminator, not two.
+        assert_eq!(WideCString::new("").unwrap().into_vec_with_nul(), vec![0]);
+        assert!(WideCString::new("").unwrap().is_empty());
+        assert_eq!(WideCString::default(), WideCString::new("").unwrap());
 
-        let cu = unsafe { CU16String::new(u16s.as_ptr()) };
-        let v = cu.as_u16_vec();
-        assert_eq!(v, &u16s[..u16s.len() - 1]);
+        // Astral characters are surrogate pairs.
+        assert_eq!(WideCString::new("😀").unwrap().as_slice(), &[0xd83d, 0xde00]);
     }
 
     #[test]
-    fn test_from_c_u16_multistring() {
+    fn test_interior_nul() {
+        let err = WideCString::new("ab\0cd").unwrap_err();
+        assert_eq!(err.nul_position(), 2);
+        assert_eq!(err.into_vec(), vec![0x61, 0x62, 0, 0x63, 0x64]);
+        assert_eq!(WideCString::new("\0").unwrap_err().nul_position(), 0);
+        assert_eq!(WideCString::new("abc\0").unwrap_err().nul_position(), 3);
+        assert_eq!(WideCString::from_vec(vec![0x61, 0]).unwrap_err(),
+                   NulError { position: 1, wide: vec![0x61, 0] });
+        assert!(WideCString::new("ab\0cd").unwrap_err().to_string().contains("position 2"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(WideCString::from_str_truncate("ab\0cd").as_slice(), &[0x61, 0x62]);
+        assert_eq!(WideCString::from_str_truncate("abc").as_slice(), &[0x61, 0x62, 0x63]);
+        assert_eq!(WideCString::from_str_truncate("\0").as_slice_with_nul(), &[0]);
+        assert_eq!(WideCString::from_vec_truncate(vec![0x61, 0, 0]).into_vec_with_nul(),
+                   vec![0x61, 0]);
+    }
+
+    #[test]
+    fn test_strict_and_lossy() {
+        let s = WideCString::from_vec(vec![0x61, 0xd800, 0x62]).unwrap();
+        assert_eq!(s.to_string_lossy(), "a\u{fffd}b");
+        assert!(s.to_string_strict().is_err());
+
+        // A lone low surrogate, and a high one at the end.
+        let s = WideCString::from_vec(vec![0xdc00, 0x61, 0xd83d]).unwrap();
+        assert_eq!(s.to_string_lossy(), "\u{fffd}a\u{fffd}");
+        assert!(s.to_string_strict().is_err());
+
+        let s = WideCString::new("가😀").unwrap();
+        assert_eq!(s.to_string_strict().unwrap(), "가😀");
+        assert_eq!(s.to_string_lossy(), "가😀");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_os_string() {
+        assert!(WideCString::from_os_str("a\0b").is_err());
+        assert!(WideCString::from_path(Path::new("C:\\a\0b")).is_err());
+
+        // An unpaired surrogate survives a round trip.
+        let os = OsString::from_wide(&[0x61, 0xd800, 0x62]);
+        let s = WideCString::from_os_str(&os).unwrap();
+        assert_eq!(s.as_slice(), &[0x61, 0xd800, 0x62]);
+        assert_eq!(s.to_os_string(), os);
+
+        let s = WideCString::new("가나").unwrap();
+        assert_eq!(s.to_os_string(), OsString::from("가나"));
+        assert_eq!(s.to_string_strict().unwrap(), "가나");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_path() {
+        let path = Path::new("C:\\Program Files\\앱\\app.exe");
+        let s = WideCString::from_path(path).unwrap();
+        assert_eq!(s.to_path_buf(), path);
+        assert_eq!(s.to_string_lossy(), "C:\\Program Files\\앱\\app.exe");
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let buf: [u16; 5] = [0x41, 0x42, 0, 0x43, 0];
+        assert_eq!(WideCStr::from_slice_until_nul(&buf).unwrap().as_slice(), &[0x41, 0x42]);
+        assert_eq!(WideCStr::from_slice_until_nul(&buf[3..]).unwrap().as_slice(), &[0x43]);
+        assert_eq!(WideCStr::from_slice_until_nul(&buf[..2]), None);
+        assert_eq!(WideCStr::from_slice_until_nul(&[]), None);
+
+        assert_eq!(WideCStr::from_slice_with_nul(&buf[..3]).unwrap().as_slice(), &[0x41, 0x42]);
+        // Interior NUL, or none at all.
+        assert_eq!(WideCStr::from_slice_with_nul(&buf), None);
+        assert_eq!(WideCStr::from_slice_with_nul(&buf[..2]), None);
+        assert_eq!(WideCStr::from_slice_with_nul(&[]), None);
+        assert!(WideCStr::from_slice_with_nul(&[0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_from_ptr() {
+        let buf: [u16; 4] = [0xac00, 0x20, 0xac00, 0x00];
+        let s = unsafe { WideCStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(s.as_slice(), &buf[..3]);
+        assert_eq!(s.as_ptr(), buf.as_ptr());
+        assert_eq!(s.to_owned(), WideCString::new("가 가").unwrap());
+    }
+
+    #[test]
+    fn test_owned_and_borrowed() {
+        let owned = WideCString::new("abc").unwrap();
+        let borrowed: &WideCStr = &owned;
+        assert_eq!(borrowed.as_ptr(), owned.as_ptr());
+        assert_eq!(WideCString::from(borrowed), owned);
+        assert_eq!(&*borrowed.to_owned(), borrowed);
+
+        // Hashing agrees between the two, as `Borrow` requires.
+        let mut set = HashSet::new();
+        set.insert(owned.clone());
+        assert!(set.contains(borrowed));
+
+        let empty: &WideCStr = Default::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.as_slice_with_nul(), &[0]);
+
+        assert!(WideCString::new("a").unwrap() < WideCString::new("b").unwrap());
+    }
+
+    #[test]
+    fn test_fmt() {
+        let s = WideCString::new("say \"가\"\n").unwrap();
+        assert_eq!(s.to_string(), "say \"가\"\n");
+        assert_eq!(format!("{:?}", s), "\"say \\\"가\\\"\\n\"");
+
+        let s = WideCString::from_vec(vec![0x61, 0xdc00]).unwrap();
+        assert_eq!(s.to_string(), "a\u{fffd}");
+        assert_eq!(format!("{:?}", s), "\"a\\u{dc00}\"");
+    }
+
+    #[test]
+    fn test_multistring_parse() {
         let test: &[u16] = &[
             0xac00, 0x00,
             0xac00, 0xac00, 0x00,
             0xac00, 0xac00, 0xac00, 0x00,
             0xac00, 0xac00, 0xac00, 0xac02, 0x00,
             0x00,
+            0x61, 0x00,
         ];
-        let compare = [
-            "가",
-            "가가",
-            "가가가",
-            "가가가갂",
-        ];
-        let mut i = 0;
-        let buf = test.as_ptr();
-        unsafe {
-            from_c_u16_multistring(buf, None, |p| {
-                let b = String::from_utf16(p).unwrap();
-                assert_eq!(b, compare[i].to_owned());
-                assert_eq!(b.chars().count(), i + 1);
-                i += 1;
-            });
+        let parts: Vec<String> = MultiString::parse(test).map(|s| s.to_string_lossy()).collect();
+        assert_eq!(parts, vec!["가", "가가", "가가가", "가가가갂"]);
+        assert_eq!(MultiString::parse(test).nth(1).unwrap().as_slice_with_nul(),
+                   &[0xac00, 0xac00, 0]);
+    }
+
+    #[test]
+    fn test_multistring_missing_terminator() {
+        let buf: [u16; 6] = [0x61, 0, 0x62, 0x63, 0, 0x64];
+        // No final NUL: the complete strings are still read.
+        let parts: Vec<&[u16]> = MultiString::parse(&buf[..5]).map(|s| s.as_slice()).collect();
+        assert_eq!(parts, vec![&[0x61][..], &[0x62, 0x63][..]]);
+        // A last string without its NUL is left out, and nothing past the slice is read.
+        let parts: Vec<&[u16]> = MultiString::parse(&buf).map(|s| s.as_slice()).collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(MultiString::parse(&buf[..4]).count(), 1);
+        assert_eq!(MultiString::parse(&[0x61]).count(), 0);
+    }
+
+    #[test]
+    fn test_multistring_empty() {
+        let list = MultiString::new();
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.as_slice(), &[0, 0]);
+        assert_eq!(list, MultiString::default());
+        assert_eq!(MultiString::from_strs(Vec::<String>::new()).unwrap(), list);
+
+        assert_eq!(MultiString::parse(&[]).count(), 0);
+        assert_eq!(MultiString::parse(&[0]).count(), 0);
+        assert_eq!(MultiString::parse(&[0, 0]).count(), 0);
+        // An empty string ends the list.
+        assert_eq!(MultiString::parse(&[0, 0x61, 0, 0]).count(), 0);
+    }
+
+    #[test]
+    fn test_multistring_build() {
+        let list = MultiString::from_strs(["a", "가나"]).unwrap();
+        assert_eq!(list.as_slice(), &[0x61, 0, 0xac00, 0xb098, 0, 0]);
+        assert_eq!(list.len(), 2);
+        assert!(!list.is_empty());
+        assert_eq!(list.as_ptr(), list.as_slice().as_ptr());
+
+        let list = MultiString::from_strs(vec!["PATH=C:\\".to_string(), "A=1".to_string()]).unwrap();
+        let vars: Vec<String> = list.iter().map(|s| s.to_string_lossy()).collect();
+        assert_eq!(vars, vec!["PATH=C:\\", "A=1"]);
+        assert_eq!(MultiString::parse(list.as_slice()).collect::<Vec<_>>(),
+                   (&list).into_iter().collect::<Vec<_>>());
+        assert_eq!(format!("{:?}", list), "[\"PATH=C:\\\\\", \"A=1\"]");
+
+        let mut list = MultiString::new();
+        list.push(&WideCString::new("x").unwrap()).unwrap();
+        assert_eq!(list.as_slice(), &[0x78, 0, 0]);
+        list.push(&WideCString::new("yz").unwrap()).unwrap();
+        assert_eq!(list.clone().into_vec(), vec![0x78, 0, 0x79, 0x7a, 0, 0]);
+
+        // An empty string is refused and leaves the list as it was.
+        assert_eq!(list.push(&WideCString::default()).unwrap_err(),
+                   MultiStringError::Empty { index: 2 });
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_multistring_errors() {
+        let err = MultiString::from_strs(["a", "b", "c\0d"]).unwrap_err();
+        assert_eq!(err.index(), 2);
+        match err {
+            MultiStringError::Nul { error, .. } => assert_eq!(error.nul_position(), 1),
+            err => panic!("{:?}", err),
         }
-        assert_eq!(i, 4);
+        // Told apart from a string starting with a NUL.
+        assert_eq!(MultiString::from_strs(["a", ""]).unwrap_err(),
+                   MultiStringError::Empty { index: 1 });
+        assert_eq!(MultiString::from_strs(["\0"]).unwrap_err().index(), 0);
+        assert!(MultiString::from_strs(["", "a"]).unwrap_err().to_string().contains("empty"));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_from_c_u16_multistring() {
+        let test: &[u16] = &[0xac00, 0x00, 0xac00, 0xac00, 0x00, 0x00, 0x61];
+        let mut parts = Vec::new();
+        let buf = test.as_ptr();
+        let read = unsafe { super::from_c_u16_multistring(buf, None, |p| parts.push(p.to_vec())) };
+        assert_eq!(read, 2);
+        assert_eq!(parts, vec![vec![0xac00], vec![0xac00, 0xac00]]);
+        assert_eq!(unsafe { super::from_c_u16_multistring(buf, Some(1), |_| {}) }, 1);
+        assert_eq!(unsafe { super::from_c_u16_multistring(test[5..].as_ptr(), None, |_| {}) }, 0);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_multistring_os_strs() {
+        let files = [Path::new("C:\\a.txt"), Path::new("D:\\앱\\b")];
+        let list = MultiString::from_os_strs(&files).unwrap();
+        let parsed: Vec<_> = list.iter().map(|s| s.to_path_buf()).collect();
+        assert_eq!(parsed, files);
+        assert!(MultiString::from_os_strs(&[Path::new("")]).is_err());
+    }
+
+    #[test]
+    fn test_from_c_u16_buf() {
+        let buf: [u16; 6] = [0x41, 0xac00, 0x00, 0x42, 0x00, 0x00];
+        assert_eq!(from_c_u16_buf(&buf), "A가");
+
+        let unterminated: [u16; 2] = [0x41, 0x42];
+        assert_eq!(from_c_u16_buf(&unterminated), "AB");
+        assert_eq!(from_c_u16_buf(&[]), "");
+    }
+
+    #[test]
+    fn test_to_c_u16_buf() {
+        let mut buf = [0xFFFFu16; 4];
+        to_c_u16_buf(&mut buf, "AB");
+        assert_eq!(buf, [0x41, 0x42, 0, 0xFFFF]);
+        to_c_u16_buf(&mut buf, "ABCDE");
+        assert_eq!(buf, [0x41, 0x42, 0x43, 0]);
+
+        // U+1F600 does not fit after "AB", so neither of its halves is kept.
+        to_c_u16_buf(&mut buf, "AB\u{1F600}");
+        assert_eq!(from_c_u16_buf(&buf), "AB");
+        assert_eq!(buf[2], 0);
+        to_c_u16_buf(&mut buf, "A\u{1F600}");
+        assert_eq!(buf, [0x41, 0xD83D, 0xDE00, 0]);
+
+        to_c_u16_buf(&mut [], "A");
     }
 }
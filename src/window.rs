@@ -12,15 +12,19 @@ use std;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use user32;
 use winapi::{
-    BOOL, CREATESTRUCTW, HBRUSH, HCURSOR, HICON, HMENU, HWND, INT, LPARAM, LRESULT, RECT, UINT,
-    WNDCLASSEXW, WPARAM, c_int,
+    BOOL, CREATESTRUCTW, HBRUSH, HCURSOR, HICON, HMENU, HWND, INT, LONG, LPARAM, LRESULT, POINT,
+    RECT, UINT, WNDCLASSEXW, WPARAM, c_int,
 };
 
-use wchar::{FromCU16Str,ToCU16Str};
+use wchar::{WideCString, from_c_u16_buf};
 use instance::Instance;
+use input::{KeyFlags, MouseButton, MouseState, VirtualKey};
+use placement::{ShowState, WindowPlacement};
+use tray::TrayEvent;
 use resource::*;
 
 pub struct WndClass {
@@ -38,7 +42,7 @@ pub struct WndClass {
 impl WndClass {
     pub fn register(&self, instance: Instance) -> bool {
         self.menu.with_menu_p(|menu_p| {
-            let clsname_u = self.classname.to_c_u16();
+            let clsname_u = WideCString::from_str_truncate(&self.classname);
             let wcex = WNDCLASSEXW {
                 cbSize: std::mem::size_of::<WNDCLASSEXW>() as UINT,
                 style: self.style as UINT,
@@ -103,6 +107,82 @@ pub static ES_RIGHT: u32 = 2;
 pub static ES_UPPERCASE: u32 = 8;
 pub static ES_WANTRETURN: u32 = 4096;
 
+/// The `SWP_*` flags of `Window::set_window_pos_ex`.
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct SetWindowPosFlags {
+    pub no_size: bool,
+    pub no_move: bool,
+    pub no_zorder: bool,
+    pub no_redraw: bool,
+    pub no_activate: bool,
+    /// Sends `WM_NCCALCSIZE`, e.g. after changing the style.
+    pub frame_changed: bool,
+    pub show_window: bool,
+    pub hide_window: bool,
+    pub no_copy_bits: bool,
+    pub no_owner_zorder: bool,
+    pub no_send_changing: bool,
+    pub defer_erase: bool,
+    /// Don't block if the window belongs to another thread.
+    pub async_window_pos: bool,
+}
+
+impl SetWindowPosFlags {
+    pub fn to_raw(&self) -> UINT {
+        let flags = [
+            (self.no_size, 0x0001),
+            (self.no_move, 0x0002),
+            (self.no_zorder, 0x0004),
+            (self.no_redraw, 0x0008),
+            (self.no_activate, 0x0010),
+            (self.frame_changed, 0x0020),
+            (self.show_window, 0x0040),
+            (self.hide_window, 0x0080),
+            (self.no_copy_bits, 0x0100),
+            (self.no_owner_zorder, 0x0200),
+            (self.no_send_changing, 0x0400),
+            (self.defer_erase, 0x2000),
+            (self.async_window_pos, 0x4000),
+        ];
+        flags.iter().filter(|f| f.0).fold(0, |acc, f| acc | f.1)
+    }
+}
+
+/// Where `Window::set_window_pos_ex` puts the window in the z-order.
+#[derive(Clone,Copy)]
+pub enum InsertAfter {
+    Top,
+    Bottom,
+    /// Above all non-topmost windows, and makes the window topmost.
+    TopMost,
+    /// Below all topmost windows, and makes the window non-topmost.
+    NoTopMost,
+    /// Directly below the given window.
+    Window(Window),
+}
+
+impl InsertAfter {
+    pub fn to_raw(&self) -> HWND {
+        match *self {
+            InsertAfter::Top => 0 as HWND,
+            InsertAfter::Bottom => 1 as HWND,
+            InsertAfter::TopMost => -1isize as HWND,
+            InsertAfter::NoTopMost => -2isize as HWND,
+            InsertAfter::Window(w) => w.wnd,
+        }
+    }
+}
+
+/// The window rectangle needed for a client area of `client`, given the window's
+/// `WS_*` and `WS_EX_*` styles.
+pub fn adjust_window_rect(client: RECT, style: u32, ex_style: u32, has_menu: bool) -> Option<RECT> {
+    let mut rect = client;
+    match unsafe { user32::AdjustWindowRectEx(&mut rect, style, has_menu as BOOL, ex_style) } {
+        0 => None,
+        _ => Some(rect),
+    }
+}
+
 pub struct WindowParams {
     pub window_name: String,
     pub style: u32,
@@ -145,8 +225,8 @@ impl Window {
         KEY_INIT_WND.with(move |f| *f.borrow_mut() = wproc);
 
         let wnd = unsafe {
-            let clsname_u = classname.to_c_u16();
-            let title_u = params.window_name.to_c_u16();
+            let clsname_u = WideCString::from_str_truncate(classname);
+            let title_u = WideCString::from_str_truncate(&params.window_name);
             let wnd = user32::CreateWindowExW(
                 params.ex_style, clsname_u.as_ptr(), title_u.as_ptr(), params.style,
                 params.x as c_int, params.y as c_int,
@@ -192,10 +272,42 @@ impl Window {
         }
     }
 
+    pub fn window_rect(&self) -> Option<RECT> {
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        let res = unsafe {
+            user32::GetWindowRect(self.wnd, &mut rect as *mut RECT)
+        } != 0;
+        match res {
+            true => Some(rect),
+            false => None,
+        }
+    }
+
+    pub fn screen_to_client(&self, x: isize, y: isize) -> Option<(isize, isize)> {
+        let mut pt = POINT { x: x as LONG, y: y as LONG };
+        match unsafe { user32::ScreenToClient(self.wnd, &mut pt) } {
+            0 => None,
+            _ => Some((pt.x as isize, pt.y as isize)),
+        }
+    }
+
+    pub fn client_to_screen(&self, x: isize, y: isize) -> Option<(isize, isize)> {
+        let mut pt = POINT { x: x as LONG, y: y as LONG };
+        match unsafe { user32::ClientToScreen(self.wnd, &mut pt) } {
+            0 => None,
+            _ => Some((pt.x as isize, pt.y as isize)),
+        }
+    }
+
+    /// Keeps the current z-order; with raw `SWP_*` flags. See `set_window_pos_ex`.
     pub fn set_window_pos(
         &self, x: isize, y: isize, width: isize, height: isize, flags: UINT
     ) -> bool {
-        // TODO: hwndInsertAfter
         unsafe {
             user32::SetWindowPos(
                 self.wnd, ptr::null_mut(), x as c_int, y as c_int,
@@ -204,6 +316,114 @@ impl Window {
         }
     }
 
+    /// `SetWindowPos` with typed flags. `insert_after` is ignored if
+    /// `flags.no_zorder` is set.
+    pub fn set_window_pos_ex(
+        &self, insert_after: InsertAfter, x: isize, y: isize, width: isize, height: isize,
+        flags: SetWindowPosFlags
+    ) -> bool {
+        unsafe {
+            user32::SetWindowPos(
+                self.wnd, insert_after.to_raw(), x as c_int, y as c_int,
+                width as c_int, height as c_int, flags.to_raw()
+            ) != 0
+        }
+    }
+
+    /// Moves the window without resizing it or changing the z-order. Child windows
+    /// are positioned in parent client coordinates.
+    pub fn move_to(&self, x: isize, y: isize) -> bool {
+        let flags = SetWindowPosFlags { no_size: true, no_zorder: true, no_activate: true,
+                                        ..Default::default() };
+        self.set_window_pos_ex(InsertAfter::Top, x, y, 0, 0, flags)
+    }
+
+    /// Resizes the whole window, including its frame.
+    pub fn resize(&self, width: isize, height: isize) -> bool {
+        let flags = SetWindowPosFlags { no_move: true, no_zorder: true, no_activate: true,
+                                        ..Default::default() };
+        self.set_window_pos_ex(InsertAfter::Top, 0, 0, width, height, flags)
+    }
+
+    /// Resizes the window so that its client area has the given size.
+    pub fn resize_client(&self, width: isize, height: isize) -> bool {
+        let client = RECT { left: 0, top: 0, right: width as LONG, bottom: height as LONG };
+        match adjust_window_rect(client, self.style(), self.ex_style(), self.has_menu()) {
+            Some(r) => self.resize((r.right - r.left) as isize, (r.bottom - r.top) as isize),
+            None => false,
+        }
+    }
+
+    /// Changes the z-order only.
+    pub fn set_z_order(&self, insert_after: InsertAfter) -> bool {
+        let flags = SetWindowPosFlags { no_move: true, no_size: true, no_activate: true,
+                                        ..Default::default() };
+        self.set_window_pos_ex(insert_after, 0, 0, 0, 0, flags)
+    }
+
+    /// The `WS_*` window style.
+    pub fn style(&self) -> u32 {
+        // GWL_STYLE
+        unsafe { user32::GetWindowLongW(self.wnd, -16) as u32 }
+    }
+
+    /// The `WS_EX_*` extended window style.
+    pub fn ex_style(&self) -> u32 {
+        // GWL_EXSTYLE
+        unsafe { user32::GetWindowLongW(self.wnd, -20) as u32 }
+    }
+
+    fn has_menu(&self) -> bool {
+        // Child windows use the menu handle as their id.
+        self.style() & WS_CHILD == 0 && unsafe { !user32::GetMenu(self.wnd).is_null() }
+    }
+
+    pub fn minimize(&self) {
+        self.show(6); // SW_MINIMIZE
+    }
+
+    pub fn maximize(&self) {
+        self.show(3); // SW_MAXIMIZE
+    }
+
+    /// Restores a minimized or maximized window.
+    pub fn restore(&self) {
+        self.show(9); // SW_RESTORE
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        unsafe { user32::IsIconic(self.wnd) != 0 }
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        unsafe { user32::IsZoomed(self.wnd) != 0 }
+    }
+
+    pub fn show_state(&self) -> ShowState {
+        if self.is_minimized() {
+            ShowState::Minimized
+        } else if self.is_maximized() {
+            ShowState::Maximized
+        } else {
+            ShowState::Normal
+        }
+    }
+
+    /// The restored rectangle and show state, e.g. to save on exit.
+    pub fn placement(&self) -> Option<WindowPlacement> {
+        let mut wp = WindowPlacement::new(ShowState::Normal, Default::default()).to_raw();
+        match unsafe { user32::GetWindowPlacement(self.wnd, &mut wp) } {
+            0 => None,
+            _ => Some(WindowPlacement::from_raw(&wp)),
+        }
+    }
+
+    /// Also shows the window in `placement.show` state.
+    pub fn set_placement(&self, placement: &WindowPlacement) -> bool {
+        let wp = placement.to_raw();
+        unsafe { user32::SetWindowPlacement(self.wnd, &wp) != 0 }
+    }
+
     pub fn set_focus(&self) -> Window {
         unsafe {
             Window {
@@ -243,10 +463,7 @@ impl Window {
 
             let read = user32::GetWindowTextW(self.wnd, buf.as_mut_ptr(), (len+1) );
             if read == len {
-                match String::from_c_u16(&buf) {
-                    None => String::new(),
-                    Some(s) => s
-                }
+                from_c_u16_buf(&buf)
             } else {
                 String::new()
             }
@@ -254,11 +471,159 @@ impl Window {
     }
 
     pub fn set_window_text(&self, text: &str ) -> bool {
-        let text_u = text.to_c_u16();
+        let text_u = WideCString::from_str_truncate(text);
         1 == unsafe {
             user32::SetWindowTextW(self.wnd, text_u.as_ptr())
         }
     }
+
+    fn from_raw(wnd: HWND) -> Option<Window> {
+        if wnd.is_null() { None } else { Some(Window { wnd: wnd }) }
+    }
+
+    /// The desktop window, which is the parent of all top-level windows.
+    pub fn desktop() -> Window {
+        Window { wnd: unsafe { user32::GetDesktopWindow() } }
+    }
+
+    /// The parent of a child window; `None` for top-level windows.
+    pub fn parent(&self) -> Option<Window> {
+        // GA_PARENT
+        let parent = unsafe { user32::GetAncestor(self.wnd, 1) };
+        if parent == Window::desktop().wnd {
+            None
+        } else {
+            Window::from_raw(parent)
+        }
+    }
+
+    /// The owner of a top-level window, e.g. the main window of a dialog.
+    pub fn owner(&self) -> Option<Window> {
+        // GW_OWNER
+        Window::from_raw(unsafe { user32::GetWindow(self.wnd, 4) })
+    }
+
+    /// The top-level window containing this one, which may be the window itself.
+    pub fn root(&self) -> Option<Window> {
+        // GA_ROOT
+        Window::from_raw(unsafe { user32::GetAncestor(self.wnd, 2) })
+    }
+
+    /// `root`, then its owners up to the first unowned window.
+    pub fn root_owner(&self) -> Option<Window> {
+        // GA_ROOTOWNER
+        Window::from_raw(unsafe { user32::GetAncestor(self.wnd, 3) })
+    }
+
+    /// The parent, its parent and so on up to the top-level window.
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors { next: self.parent() }
+    }
+
+    /// Whether this window is a child or other descendant of `parent`.
+    pub fn is_descendant_of(&self, parent: Window) -> bool {
+        unsafe { user32::IsChild(parent.wnd, self.wnd) != 0 }
+    }
+
+    /// All descendants of the window, not just its direct children.
+    pub fn children(&self) -> std::vec::IntoIter<Window> {
+        let mut windows = Vec::new();
+        unsafe {
+            user32::EnumChildWindows(self.wnd, Some(collect_windows),
+                                     &mut windows as *mut Vec<Window> as LPARAM);
+        }
+        windows.into_iter()
+    }
+
+    /// All top-level windows, including hidden ones.
+    pub fn top_level_windows() -> std::vec::IntoIter<Window> {
+        let mut windows = Vec::new();
+        unsafe {
+            user32::EnumWindows(Some(collect_windows), &mut windows as *mut Vec<Window> as LPARAM);
+        }
+        windows.into_iter()
+    }
+
+    /// Finds a top-level window by class name and/or exact title.
+    pub fn find(class: Option<&str>, title: Option<&str>) -> Option<Window> {
+        let class_u = class.map(WideCString::from_str_truncate);
+        let title_u = title.map(WideCString::from_str_truncate);
+        Window::from_raw(unsafe {
+            user32::FindWindowW(
+                class_u.as_ref().map_or(ptr::null(), |c| c.as_ptr()),
+                title_u.as_ref().map_or(ptr::null(), |t| t.as_ptr()))
+        })
+    }
+
+    /// Finds a direct child by class name and/or exact title, starting after
+    /// `after` in z-order.
+    pub fn find_child(&self, after: Option<Window>, class: Option<&str>, title: Option<&str>)
+                      -> Option<Window> {
+        let class_u = class.map(WideCString::from_str_truncate);
+        let title_u = title.map(WideCString::from_str_truncate);
+        Window::from_raw(unsafe {
+            user32::FindWindowExW(
+                self.wnd, after.map_or(ptr::null_mut(), |a| a.wnd),
+                class_u.as_ref().map_or(ptr::null(), |c| c.as_ptr()),
+                title_u.as_ref().map_or(ptr::null(), |t| t.as_ptr()))
+        })
+    }
+
+    pub fn class_name(&self) -> String {
+        // Class names are limited to 256 characters.
+        let mut buf = [0u16; 257];
+        let len = unsafe {
+            user32::GetClassNameW(self.wnd, buf.as_mut_ptr(), buf.len() as c_int)
+        };
+        String::from_utf16_lossy(&buf[..len.max(0) as usize])
+    }
+
+    /// The id of the process that created the window.
+    pub fn process_id(&self) -> u32 {
+        let mut process_id = 0;
+        unsafe { user32::GetWindowThreadProcessId(self.wnd, &mut process_id) };
+        process_id
+    }
+
+    /// The id of the thread that created the window, and processes its messages.
+    pub fn thread_id(&self) -> u32 {
+        unsafe { user32::GetWindowThreadProcessId(self.wnd, ptr::null_mut()) }
+    }
+
+    /// Whether the window has `WS_VISIBLE`. It may still be covered or off-screen.
+    pub fn is_visible(&self) -> bool {
+        unsafe { user32::IsWindowVisible(self.wnd) != 0 }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        unsafe { user32::IsWindowEnabled(self.wnd) != 0 }
+    }
+
+    /// Whether the handle identifies an existing window.
+    pub fn is_window(&self) -> bool {
+        unsafe { user32::IsWindow(self.wnd) != 0 }
+    }
+}
+
+unsafe extern "system" fn collect_windows(wnd: HWND, l: LPARAM) -> BOOL {
+    let windows = &mut *(l as *mut Vec<Window>);
+    windows.push(Window { wnd: wnd });
+    1
+}
+
+/// Iterates over the ancestors of a window; see `Window::ancestors`.
+pub struct Ancestors {
+    next: Option<Window>,
+}
+
+impl Iterator for Ancestors {
+    type Item = Window;
+
+    fn next(&mut self) -> Option<Window> {
+        let current = self.next.take();
+        self.next = current.and_then(|w| w.parent());
+        current
+    }
 }
 
 pub trait WindowImpl {
@@ -308,6 +673,18 @@ pub unsafe extern "system" fn main_wnd_proc(wnd: HWND,
                                             l: LPARAM) -> LRESULT {
     promote_init_wnd(Window { wnd: wnd });
 
+    if msg == 0x0082 { // WM_NCDESTROY
+        ::timer::remove_window_timers(Window { wnd: wnd });
+        ::dispatch::close_window(Window { wnd: wnd });
+        ::ole::revoke_window(Window { wnd: wnd });
+        ::tray::forget_window(Window { wnd: wnd });
+    } else if msg != 0 && msg == ::dispatch::dispatch_message() {
+        ::dispatch::run_pending(Window { wnd: wnd });
+        return 0;
+    } else if msg != 0 && msg == ::tray::taskbar_created_message() {
+        ::tray::readd_icons(Window { wnd: wnd });
+    }
+
     if let Some(wnd_impl) = lookup_wnd_impl(wnd) {
         wnd_impl.wnd_proc(msg, w, l)
     } else {
@@ -345,25 +722,134 @@ pub trait OnFocus {
     }
 }
 
+pub trait OnMouseMove {
+    fn on_mouse_move(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
 pub trait OnLeftButtonDown {
-    fn on_left_button_down(&self, _x: isize, _y: isize, _flags: u32) {
+    fn on_left_button_down(&self, _x: isize, _y: isize, _state: MouseState) {
     }
 }
 
 pub trait OnLeftButtonUp {
-    fn on_left_button_up(&self, _x: isize, _y: isize, _flags: u32) {
+    fn on_left_button_up(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+/// Double clicks are only reported if the window class has `CS_DBLCLKS` (0x0008).
+pub trait OnLeftButtonDoubleClick {
+    fn on_left_button_double_click(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnRightButtonDown {
+    fn on_right_button_down(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnRightButtonUp {
+    fn on_right_button_up(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnRightButtonDoubleClick {
+    fn on_right_button_double_click(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnMiddleButtonDown {
+    fn on_middle_button_down(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnMiddleButtonUp {
+    fn on_middle_button_up(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnMiddleButtonDoubleClick {
+    fn on_middle_button_double_click(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnXButtonDown {
+    fn on_x_button_down(&self, _button: MouseButton, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnXButtonUp {
+    fn on_x_button_up(&self, _button: MouseButton, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+pub trait OnXButtonDoubleClick {
+    fn on_x_button_double_click(&self, _button: MouseButton, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+/// `x` and `y` are in screen coordinates. `delta` is a multiple or fraction of
+/// `input::WHEEL_DELTA`; see `input::WheelAccumulator`.
+pub trait OnMouseWheel {
+    fn on_mouse_wheel(&self, _x: isize, _y: isize, _delta: isize, _state: MouseState) {
+    }
+}
+
+/// Like `OnMouseWheel`, for tilt wheels; positive `delta` is to the right.
+pub trait OnMouseHWheel {
+    fn on_mouse_hwheel(&self, _x: isize, _y: isize, _delta: isize, _state: MouseState) {
+    }
+}
+
+/// Only sent after `Window::track_mouse_event`; see `input::MouseTracker`.
+pub trait OnMouseHover {
+    fn on_mouse_hover(&self, _x: isize, _y: isize, _state: MouseState) {
+    }
+}
+
+/// Only sent after `Window::track_mouse_event`; see `input::MouseTracker`.
+pub trait OnMouseLeave {
+    fn on_mouse_leave(&self) {
+    }
+}
+
+pub trait OnCaptureChanged {
+    fn on_capture_changed(&self, _new: Window) {
     }
 }
 
 pub trait OnKeyDown {
-    fn on_key_down(&self, _keycode: u8, _flags: u32) -> bool {
-        return false;
+    fn on_key_down(&self, _key: VirtualKey, _flags: KeyFlags) -> bool {
+        false
     }
 }
 
 pub trait OnKeyUp {
-    fn on_key_up(&self, _keycode: u8, _flags: u32) -> bool {
-        return false;
+    fn on_key_up(&self, _key: VirtualKey, _flags: KeyFlags) -> bool {
+        false
+    }
+}
+
+/// F10 and keys pressed while Alt is down. Return `false` to let
+/// `DefWindowProc` handle them, which is what makes Alt+F4 and menu
+/// mnemonics work.
+pub trait OnSysKeyDown {
+    fn on_sys_key_down(&self, _key: VirtualKey, _flags: KeyFlags) -> bool {
+        false
+    }
+}
+
+pub trait OnSysKeyUp {
+    fn on_sys_key_up(&self, _key: VirtualKey, _flags: KeyFlags) -> bool {
+        false
+    }
+}
+
+/// Text input from `WM_CHAR` or `WM_UNICHAR`. Surrogate pairs are assembled into
+/// a single call. Control characters such as `'\r'`, `'\t'` and `'\x08'`
+/// (backspace) are included.
+pub trait OnChar {
+    fn on_char(&self, _c: char, _flags: KeyFlags) {
     }
 }
 
@@ -373,6 +859,54 @@ pub trait OnEraseBackground {
     }
 }
 
+pub trait OnDpiChanged: WindowImpl {
+    /// Called when the window moves to a monitor with a different DPI, or the DPI
+    /// setting changes. `suggested` is the window rect scaled for the new DPI.
+    /// The default implementation moves the window there; override to also
+    /// recreate fonts and relayout, calling `set_window_pos` with `suggested`.
+    fn on_dpi_changed(&self, _dpi_x: u32, _dpi_y: u32, suggested: &RECT) {
+        // SWP_NOZORDER | SWP_NOACTIVATE
+        self.wnd().set_window_pos(
+            suggested.left as isize, suggested.top as isize,
+            (suggested.right - suggested.left) as isize,
+            (suggested.bottom - suggested.top) as isize,
+            0x4 | 0x10
+        );
+    }
+}
+
+pub trait OnClipboardUpdate {
+    /// Called when the clipboard contents change; see
+    /// `Window::add_clipboard_listener`.
+    fn on_clipboard_update(&self) {
+    }
+}
+
+pub trait OnDropFiles {
+    /// Called with the files dropped on a window that accepts them (see
+    /// `Window::accept_files`) and the drop point in client coordinates, which is
+    /// `None` for a drop on the non-client area.
+    fn on_drop_files(&self, _files: Vec<PathBuf>, _point: Option<(isize, isize)>) {
+    }
+}
+
+pub trait OnTrayIcon {
+    /// Called for events of the window's tray icons; see `tray::TrayIcon`.
+    fn on_tray_icon(&self, _id: u32, _event: TrayEvent) {
+    }
+}
+
+pub trait OnTaskbar {
+    /// Called when the window's taskbar button is created, or again after Explorer
+    /// restarts; see `taskbar::Taskbar`.
+    fn on_taskbar_button_created(&self) {
+    }
+
+    /// Called when a thumbnail toolbar button is clicked.
+    fn on_thumb_button(&self, _id: u16) {
+    }
+}
+
 pub trait OnMessage {
     fn on_message(&self, _message: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
         None